@@ -0,0 +1,38 @@
+// Description: Embed the git commit and the build date in the binary for --version.
+
+use std::{env, fs, path::Path, process::Command, time::SystemTime};
+
+// shared with the library, which uses more of it than we do here
+#[allow(dead_code, clippy::wrong_self_convention)]
+#[path = "src/time.rs"]
+mod time;
+
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        .filter(|hash| !hash.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    let date = time::DateTime::from_system_time(SystemTime::now()).date_string();
+
+    let out_dir = env::var("OUT_DIR").expect("cargo sets OUT_DIR for build scripts");
+    let contents = format!(
+        "/// Short hash of the commit the binary was built from\n\
+         pub const GIT_COMMIT: &str = {commit:?};\n\
+         /// Day the binary was built (UTC)\n\
+         pub const BUILD_DATE: &str = {date:?};\n"
+    );
+    fs::write(Path::new(&out_dir).join("build_info.rs"), contents)
+        .expect("unable to write build_info.rs");
+
+    // pick up new commits and checkouts, a source archive without .git just keeps "unknown"
+    for path in [".git/HEAD", ".git/refs/heads"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
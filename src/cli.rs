@@ -0,0 +1,964 @@
+// Description: Turn the command line into `GenOptions` plus the dirs to process.
+
+use std::path::{Path, PathBuf};
+
+use m3ugen::{
+    ColorChoice, DEFAULT_CONFLICT_LOG, DEFAULT_EXTENSIONS, DEFAULT_NFO_EXTENSIONS,
+    DEFAULT_SUBDIR_LEAF_NAME, DEFAULT_SUBDIR_PREFIX, DigestAlgorithm, FormatPreference, GenOptions,
+    IGNORE_FILE_NAME, MAX_SUBDIR_DEPTH, NameTemplate, Newline, OutputEncoding, PROFILE_NAMES,
+    PathSeparator, Pattern, PlaylistFormat, RenameStrategy, ScriptFormat, SortOrder, SymlinkMode,
+    normalize_path, parse_extension_list, parse_since, profile_extensions, profile_with_extras,
+    validate_subdir_name, validate_subdir_prefix,
+};
+
+include!(concat!(env!("OUT_DIR"), "/build_info.rs"));
+
+/// What --version prints, like `m3ugen 0.1.0 (abc1234 2025-10-15)`
+pub fn version_string() -> String {
+    format!(
+        "m3ugen {} ({GIT_COMMIT} {BUILD_DATE})",
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// One line summary of how to call m3ugen
+pub const USAGE: &str = "Usage: m3ugen [COMMAND] [OPTIONS] <PARENT_DIR>...";
+
+/// What --help prints
+pub fn help_text() -> String {
+    format!(
+        "Create a .m3u playlist for every game dir in PARENT_DIR and move the game files into a
+hidden sub-dir next to it, so frontends list each multi-disc game once.
+
+{USAGE}
+
+Commands:
+  validate  Check the existing playlists for entries that point at missing files
+  unpack    Move the files back out of the sub-dirs and remove the playlists
+  stats     Count the files, playlists, sub-dirs and discs of every game dir
+  merge     Combine the playlists in each given game dir into one <dir name>.m3u
+  dedupe    Drop the entries each playlist lists more than once, keeping the first
+  find-orphans
+            List the files in the hidden sub-dirs that no playlist references
+  diff      Compare the playlists and sub-dirs of two parent dirs, e.g. a backup and the live
+            library
+
+Arguments:
+  <PARENT_DIR>...  The dirs that hold one dir per game, processed one after the other
+
+Options:
+  -n, --dry-run              Print what would be done without changing anything
+      --create-parent        Create a PARENT_DIR that doesn't exist yet instead of failing
+      --no-move, --flat      Leave the files where they are, only write the playlists
+      --copy                 Copy the files into the sub-dir instead of moving them
+      --include-nfo          Copy sidecar files (.nfo, .txt, ...) into the sub-dir as well,
+                             they stay in place and aren't listed in the playlist
+      --nfo-extensions <LIST>
+                             Sidecar extensions to copy, implies --include-nfo
+                             [default: {}]
+      --follow-symlinks      Process symlinked game dirs, and copy what symlinked files point
+                             at into the sub-dir (they're skipped by default)
+      --copy-symlinks        Recreate symlinked files in the sub-dir, pointing at the same
+                             target
+      --preserve-xattr       Keep extended attributes when a file is copied (Linux only)
+      --overwrite            Rebuild the playlists of processed dirs from what's in the
+                             sub-dir plus any new files, and replace files already in the
+                             sub-dir when copying
+      --extended             Write #EXTM3U/#EXTINF lines
+      --output-format <FMT>  m3u, m3u8 or pls [default: m3u]
+      --newline <NL>         lf, crlf or platform [default: lf]
+      --bom                  Start .m3u8 playlists with a UTF-8 BOM
+      --output-encoding <ENC>
+                             utf8, latin1 or shiftjis, for frontends that can't read UTF-8
+                             [default: utf8]
+      --encoding-fallback    Write lines the --output-encoding can't hold as UTF-8 instead of
+                             failing the playlist
+      --compress-m3u         Write the playlists gzipped, as NAME.m3u.gz
+      --compression-level <N>
+                             1 (fastest) to 9 (smallest) [default: 6]
+      --checksums            Write <dir>.sha256 with the hashes of the moved files
+      --digest-algorithm <ALG>
+                             md5, sha1 or sha256, also the extension of the checksum file
+                             [default: sha256]
+      --absolute-paths       Write absolute paths instead of paths relative to the playlist
+      --strip-prefix <TEXT>  Take TEXT off the front of every playlist entry
+      --prepend-path <PATH>  Put PATH in front of every playlist entry
+      --strip-prepend        With validate, take the --prepend-path off entries before checking
+      --platform-separator <OS>
+                             Separator to write entries with: linux (/), windows (\\) or
+                             auto (this platform's) [default: auto]
+      --windows-paths        Same as --platform-separator windows, for building a library on
+                             this machine that a frontend on Windows reads
+      --windows-compat       Add a BOM to .m3u8 playlists for Windows players
+      --m3u-filename <TMPL>  Playlist file name, {{dir}}, {{ext}} and {{date}} are filled in
+                             [default: {{dir}}.<format extension>]
+      --pbp-first-disc-name <TMPL>
+                             Name of a PSP game's first disc .pbp, which is disc 1 whatever its
+                             name says, {{dir}} is filled in [default: EBOOT.PBP]
+      --group-by-extension   Write a playlist per extension (NAME_chd.m3u, NAME_cue.m3u, ...)
+                             instead of one, a dir with both formats isn't a conflict then
+      --max-files-per-m3u <N>
+                             Split playlists with more than N entries into NAME_2, NAME_3, ...
+      --one-per-disc         List only the .cue of each disc, its .bin tracks are still moved
+                             but don't get entries of their own
+      --max-m3u-line-length <N>
+                             Warn about entries longer than N characters, for frontends that
+                             can't read longer lines
+      --error-on-long-lines  Leave those entries out of the playlist as an error instead
+      --rename-dir <HOW>     Rename the game dirs first: snake-to-title, nointro or
+                             custom:TMPL (same variables as --m3u-filename)
+      --rename-files         Rename the game files after their dir before moving them, adding
+                             (Disc N) when there's more than one
+      --backup <DEST>        Copy every game dir to DEST/<dir name> before it's changed, a
+                             dir whose backup fails is left alone
+      --m3u-comment <TEXT>   Comment line to start every playlist with (repeatable)
+      --no-auto-comment      Leave out the \"Generated by m3ugen\" comment
+      --auto-comment-date    Say when the playlist was written in that comment, a rerun then
+                             rewrites every playlist
+      --template-m3u <FILE>  Write the contents of FILE at the top of every playlist, after
+                             the comments
+      --output-dir <DIR>     Write every playlist to DIR instead of its game dir
+      --ext, --extensions <LIST>
+                             Comma separated file extensions to pick up
+                             [default: {}]
+      --profile <NAME>       Extensions for a system: {}
+      --sort <ORDER>         name, natural, extension, size or none [default: natural]
+      --extension-priority-order <EXTS>
+                             List the files of these extensions first, in this order (like
+                             chd,cue), each group still sorted by --sort. Dirs with both a
+                             .chd and a .cue/.bin set list both instead of being skipped
+      --prefix <PREFIX>      Put in front of the dir name to name the sub-dir [default: {}]
+      --no-hidden            Name the sub-dir after the game dir without a prefix
+      --subdir-name <NAME>   Use NAME for every sub-dir instead of the prefix and dir name
+      --subdir-depth <N>     Levels of sub-dirs to move the files into, 1 to 5 [default: 1]
+      --subdir-leaf-name <NAME>
+                             Name of the levels below the first one, so 2 moves the files
+                             into .Game/NAME [default: {DEFAULT_SUBDIR_LEAF_NAME}]
+      --filter <GLOB>        Only process dirs whose name matches (repeatable)
+      --exclude <GLOB>       Leave dirs whose name matches alone (repeatable)
+      --include-dirs <GLOB>  Only process dirs whose name matches any of these (repeatable,
+                             case-insensitive on Windows)
+      --exclude-dirs <GLOB>  Then leave the dirs whose name matches any of these alone
+                             (repeatable, case-insensitive on Windows)
+      --since <DATE>         Only process dirs modified on or after DATE (YYYY-MM-DD, UTC)
+      --ignore-file <FILE>   Ignore file to use instead of PARENT_DIR/{IGNORE_FILE_NAME}
+      --exclude-hidden       Skip hidden dirs while scanning [default: on when the prefix is .]
+      --include-hidden       Scan hidden dirs too
+      --only-multi-file      Leave dirs with a single game file alone
+      --skip-dirs-with-subdirs
+                             Leave dirs that already hold other dirs (saves, soundtrack, ...)
+                             alone
+      --allow-existing-subdirs
+                             Process those dirs anyway, to override the config file [default]
+  -r, --recursive            Look for game dirs below the child dirs too
+      --max-depth <N>        How deep to look when recursing (implies --recursive)
+      --offset <N>           Skip the first N dirs (in name order)
+      --limit <N>            Process at most N dirs
+      --skip-existing        Leave dirs that already have a playlist alone
+      --update               Add new files to existing playlists
+      --append               Add new files to the end of existing playlists as they are
+      --force                Process dirs holding both a .chd and a .cue set
+      --prefer <FMT>         Which one to keep with --force: chd or cue [default: chd]
+      --auto-detect-format   Settle those dirs by keeping the best format from --format-priority,
+                             the others are left where they are
+      --format-priority <EXTS>
+                             Formats ranked by --auto-detect-format, the best first
+                             [default: chd,cue,bin]
+      --archive-secondary-formats
+                             Move the formats --auto-detect-format passed over into
+                             .Game.secondary instead of leaving them
+      --conflict-log <FILE>  Where to list the dirs with both formats
+                             [default: {DEFAULT_CONFLICT_LOG}]
+      --transaction-log <FILE>
+                             Where to record the changes [default: PARENT_DIR/.m3ugen-<time>.json]
+      --undo <LOG>           Roll back the run recorded in LOG
+      --watch                Keep processing new dirs as they appear
+      --debounce-ms <MS>     How long a new dir has to stay unchanged [default: 500]
+      --watch-poll           Watch by re-reading PARENT_DIR instead of waiting for inotify
+                             events, for CIFS and NFS mounts that don't send them
+      --watch-interval <MS>  How often --watch-poll re-reads PARENT_DIR [default: 5000]
+      --lock-timeout-ms <MS> How long to wait for another run on PARENT_DIR [default: 5000]
+      --retry-count <N>      Times to retry a move that failed on a locked file [default: 3]
+      --retry-delay-ms <MS>  How long to wait between those retries [default: 500]
+  -j, --jobs <N>             Dirs to process at the same time [default: number of CPUs]
+      --stdin                Read the parent dirs from stdin instead, one per line (blank
+                             lines and lines starting with # are skipped)
+      --fix                  With validate, point broken entries at the sub-dir
+      --verify-checksums     Check the moved files against their <dir>.sha256 (.md5, .sha1),
+                             on its own or together with validate
+      --json                 With stats or diff, print JSON instead of text
+      --keep-originals       With merge, leave the merged playlists in place
+      --in-place             With dedupe, rewrite the playlists themselves [default]
+      --no-in-place          With dedupe, write NAME.dedupe.m3u next to each playlist instead
+      --delete-orphans       With find-orphans, remove the files it found after asking
+  -y, --yes                  With --delete-orphans, remove them without asking
+  -v, --verbose              With --verify-checksums, print OK or FAIL for every file
+      --summary              Print a summary when done
+      --summary-json         Print the summary as JSON
+      --summary-file <FILE>  Write the summary to FILE instead of stdout
+      --script-output <FILE> Change nothing and write a script to FILE that makes the changes
+                             instead, add --dry-run to see them as well
+      --script-output-format <FMT>
+                             sh or ps1 (PowerShell) [default: sh]
+      --report-csv <FILE>    Write a CSV row for every file operation to FILE
+      --no-progress          Don't draw the progress bar
+      --log-file <FILE>      Also write every line, debug output included, to FILE
+      --color <WHEN>         auto, always or never [default: auto]
+      --no-color             Same as --color never
+      --tui                  Show a full screen view of the run
+  -i, --interactive          Ask before processing each dir
+      --config <FILE>        Config file to use [default: {}]
+  -h, --help                 Print this help
+  -V, --version              Print the version
+
+Environment:
+  RUST_LOG                   How much to print: off, error, warn, info or debug
+                             [default: info]
+  M3UGEN_DEFAULT_EXTENSIONS  Comma separated extensions to use when neither --extensions
+                             nor the config file gives any
+
+Example:
+  m3ugen --dry-run --extended ~/roms/psx
+",
+        DEFAULT_NFO_EXTENSIONS.join(","),
+        DEFAULT_EXTENSIONS.join(","),
+        PROFILE_NAMES.join(", "),
+        DEFAULT_SUBDIR_PREFIX,
+        crate::config::default_config_path()
+            .map_or_else(|| "none".to_string(), |p| p.display().to_string()),
+    )
+}
+
+/// Format of the report printed at the end of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryFormat {
+    Text,
+    Json,
+}
+
+/// What to do with the parent dir
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Command {
+    /// Write playlists and move the files (no subcommand given)
+    #[default]
+    Generate,
+    /// Check the existing playlists for missing files
+    Validate,
+    /// Move the files back out of the hidden sub-dirs and remove the playlists
+    Unpack,
+    /// Report what every child dir holds without changing anything
+    Stats,
+    /// Fold the playlists in each given dir into one
+    Merge,
+    /// Compare two parent dirs without changing anything
+    Diff,
+    /// Drop the duplicate entries from every playlist
+    Dedupe,
+    /// Look for files in the hidden sub-dirs that no playlist references
+    FindOrphans,
+}
+
+/// Everything the user asked for on the command line
+#[derive(Debug, Default)]
+pub struct Cli {
+    /// Subcommand, given as the first positional argument
+    pub command: Command,
+    /// Repair broken entries when validating
+    pub fix: bool,
+    /// Take the --prepend-path off entries when validating
+    pub strip_prepend: bool,
+    /// Check the files against their .sha256 files (on its own, or as part of validate)
+    pub verify_checksums: bool,
+    /// Print the stats or the diff as JSON
+    pub json: bool,
+    /// Leave the playlists that were merged in place
+    pub keep_originals: bool,
+    /// Print a line for every file checked instead of just the totals
+    pub verbose: bool,
+    /// Options handed to the library
+    pub options: GenOptions,
+    /// Positional arguments (the parent dirs)
+    pub parents: Vec<PathBuf>,
+    /// Read the parent dirs from stdin instead, one per line
+    pub stdin: bool,
+    /// Transaction log of a run to roll back instead of processing anything
+    pub undo: Option<PathBuf>,
+    /// Print a summary of the run when it's done
+    pub summary: Option<SummaryFormat>,
+    /// Write the summary here instead of stdout
+    pub summary_file: Option<PathBuf>,
+    /// Rewrite the deduplicated playlists instead of writing a copy, set by --in-place and
+    /// --no-in-place
+    pub in_place: Option<bool>,
+    /// Remove the files find-orphans found
+    pub delete_orphans: bool,
+    /// Don't ask before removing them
+    pub yes: bool,
+    /// Write a script making the changes here instead of making them
+    pub script_output: Option<PathBuf>,
+    /// Language of the --script-output script
+    pub script_format: ScriptFormat,
+    /// Show the full screen interface while processing
+    pub tui: bool,
+    /// Ask before processing each child dir
+    pub interactive: bool,
+    /// Print the version and exit
+    pub version: bool,
+    /// Print the help and exit
+    pub help: bool,
+}
+
+/// Grab the value that follows a flag like `--ext chd`
+fn next_value<'a, I: Iterator<Item = &'a String>>(
+    args: &mut I,
+    flag: &str,
+) -> Result<&'a String, String> {
+    args.next()
+        .ok_or_else(|| format!("{flag} requires a value"))
+}
+
+/// Split `--flag=value` into `--flag value` so both spellings work
+fn split_values(args: &[String]) -> Vec<String> {
+    let mut ret = Vec::with_capacity(args.len());
+    for arg in args {
+        match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                ret.push(flag.to_string());
+                ret.push(value.to_string());
+            }
+            _ => ret.push(arg.clone()),
+        }
+    }
+    ret
+}
+
+/// Find the value of --config, which has to be known before the rest of the flags are parsed
+pub fn find_config_flag(args: &[String]) -> Result<Option<PathBuf>, String> {
+    let args = split_values(args);
+    let mut iter = args.iter();
+    let mut ret = None;
+    while let Some(arg) = iter.next() {
+        if arg == "--config" {
+            ret = Some(PathBuf::from(next_value(&mut iter, arg)?));
+        }
+    }
+    Ok(ret)
+}
+
+/// Parse the arguments (without the program name) on top of `options`, which hold the defaults
+/// (possibly already changed by a config file)
+pub fn parse_args(args: &[String], options: GenOptions) -> Result<Cli, String> {
+    let mut cli = Cli {
+        options,
+        ..Cli::default()
+    };
+    let mut extensions: Vec<String> = Vec::new();
+    let mut profile: Option<&'static [&'static str]> = None;
+
+    let args = split_values(args);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--config" => {
+                // already loaded by find_config_flag
+                next_value(&mut iter, arg)?;
+            }
+            "--help" | "-h" => cli.help = true,
+            "--version" | "-V" => cli.version = true,
+            "--dry-run" | "-n" => cli.options.dry_run = true,
+            "--no-move" | "--flat" => cli.options.no_move = true,
+            "--extended" => cli.options.extended = true,
+            "--output-format" => {
+                let name = next_value(&mut iter, arg)?;
+                cli.options.format = PlaylistFormat::parse(name).ok_or_else(|| {
+                    format!("unknown output format '{name}' (expected m3u, m3u8 or pls)")
+                })?;
+            }
+            "--windows-compat" => cli.options.windows_compat = true,
+            "--no-progress" => cli.options.progress = false,
+            "--skip-existing" => cli.options.skip_existing = true,
+            "--update" => cli.options.update = true,
+            "--append" => cli.options.append = true,
+            "--force" => cli.options.force = true,
+            "--prefer" => {
+                let name = next_value(&mut iter, arg)?;
+                cli.options.prefer = FormatPreference::parse(name)
+                    .ok_or_else(|| format!("unknown format '{name}' (expected chd or cue)"))?;
+            }
+            "--create-parent" => cli.options.create_parent = true,
+            "--auto-detect-format" => cli.options.auto_detect_format = true,
+            "--format-priority" => {
+                let priority = parse_extension_list(next_value(&mut iter, arg)?)?;
+                if priority.is_empty() {
+                    return Err(format!(
+                        "{arg} expects a list of extensions like chd,cue,bin"
+                    ));
+                }
+                cli.options.format_priority = priority;
+            }
+            "--archive-secondary-formats" => cli.options.archive_secondary_formats = true,
+            "--conflict-log" => {
+                cli.options.conflict_log = Some(PathBuf::from(next_value(&mut iter, arg)?));
+            }
+            "--transaction-log" => {
+                cli.options.transaction_log = Some(PathBuf::from(next_value(&mut iter, arg)?));
+            }
+            "--undo" => cli.undo = Some(PathBuf::from(next_value(&mut iter, arg)?)),
+            "--watch" => cli.options.watch = true,
+            "--debounce-ms" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.debounce_ms = value.parse().map_err(|_| {
+                    format!("{arg} expects a number of milliseconds, got '{value}'")
+                })?;
+            }
+            "--watch-poll" => cli.options.watch_poll = true,
+            "--watch-interval" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.watch_interval_ms =
+                    value.parse().ok().filter(|ms| *ms > 0).ok_or_else(|| {
+                        format!("{arg} expects a number of milliseconds above 0, got '{value}'")
+                    })?;
+            }
+            "--report-csv" => {
+                cli.options.report_csv = Some(PathBuf::from(next_value(&mut iter, arg)?));
+            }
+            "--log-file" => {
+                cli.options.log_file = Some(PathBuf::from(next_value(&mut iter, arg)?));
+            }
+            "--ignore-file" => {
+                cli.options.ignore_file = Some(PathBuf::from(next_value(&mut iter, arg)?));
+            }
+            "--retry-count" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.retry_count = value
+                    .parse()
+                    .map_err(|_| format!("{arg} expects a number, got '{value}'"))?;
+            }
+            "--retry-delay-ms" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.retry_delay_ms = value.parse().map_err(|_| {
+                    format!("{arg} expects a number of milliseconds, got '{value}'")
+                })?;
+            }
+            "--lock-timeout-ms" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.lock_timeout_ms = value.parse().map_err(|_| {
+                    format!("{arg} expects a number of milliseconds, got '{value}'")
+                })?;
+            }
+            "--sort" => {
+                let name = next_value(&mut iter, arg)?;
+                cli.options.sort = SortOrder::parse(name).ok_or_else(|| {
+                    format!(
+                        "unknown sort order '{name}' (expected name, natural, extension, size \
+                         or none)"
+                    )
+                })?;
+            }
+            "--extension-priority-order" => {
+                cli.options.extension_priority_order =
+                    parse_extension_list(next_value(&mut iter, arg)?)?;
+            }
+            "--prefix" => {
+                let prefix = next_value(&mut iter, arg)?;
+                validate_subdir_prefix(prefix)?;
+                cli.options.subdir_prefix = prefix.clone();
+            }
+            "--no-hidden" => cli.options.subdir_prefix = String::new(),
+            "--subdir-name" => {
+                let name = next_value(&mut iter, arg)?;
+                validate_subdir_name(name)?;
+                cli.options.subdir_name = Some(name.clone());
+            }
+            "--subdir-depth" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.subdir_depth = match value.parse() {
+                    Ok(n @ 1..=MAX_SUBDIR_DEPTH) => n,
+                    _ => {
+                        return Err(format!(
+                            "{arg} expects a number from 1 to {MAX_SUBDIR_DEPTH}, got '{value}'"
+                        ));
+                    }
+                };
+            }
+            "--subdir-leaf-name" => {
+                let name = next_value(&mut iter, arg)?;
+                validate_subdir_name(name)?;
+                cli.options.subdir_leaf_name = name.clone();
+            }
+            "--filter" => cli
+                .options
+                .filters
+                .push(Pattern::new(next_value(&mut iter, arg)?)?),
+            "--exclude" => cli
+                .options
+                .excludes
+                .push(Pattern::new(next_value(&mut iter, arg)?)?),
+            "--include-dirs" => cli
+                .options
+                .include_dirs
+                .push(Pattern::new(next_value(&mut iter, arg)?)?),
+            "--exclude-dirs" => cli
+                .options
+                .exclude_dirs
+                .push(Pattern::new(next_value(&mut iter, arg)?)?),
+            "--output-dir" => {
+                cli.options.output_dir = Some(PathBuf::from(next_value(&mut iter, arg)?));
+            }
+            "--m3u-filename" => {
+                cli.options.m3u_filename = Some(NameTemplate::parse(next_value(&mut iter, arg)?)?);
+            }
+            "--pbp-first-disc-name" => {
+                cli.options.pbp_first_disc_name =
+                    Some(NameTemplate::parse(next_value(&mut iter, arg)?)?);
+            }
+            "--summary" => cli.summary = Some(SummaryFormat::Text),
+            "--summary-json" => cli.summary = Some(SummaryFormat::Json),
+            "--summary-file" => {
+                cli.summary_file = Some(PathBuf::from(next_value(&mut iter, arg)?));
+            }
+            "--in-place" => cli.in_place = Some(true),
+            "--no-in-place" => cli.in_place = Some(false),
+            "--delete-orphans" => cli.delete_orphans = true,
+            "--yes" | "-y" => cli.yes = true,
+            "--script-output" => {
+                cli.script_output = Some(PathBuf::from(next_value(&mut iter, arg)?));
+            }
+            "--script-output-format" => {
+                let name = next_value(&mut iter, arg)?;
+                cli.script_format = ScriptFormat::parse(name).ok_or_else(|| {
+                    format!("unknown script format '{name}' (expected sh or ps1)")
+                })?;
+            }
+            "--color" => {
+                let name = next_value(&mut iter, arg)?;
+                cli.options.color = ColorChoice::parse(name).ok_or_else(|| {
+                    format!("unknown color mode '{name}' (expected auto, always or never)")
+                })?;
+            }
+            "--no-color" => cli.options.color = ColorChoice::Never,
+            "--tui" => cli.tui = true,
+            "--interactive" | "-i" => cli.interactive = true,
+            "--bom" => cli.options.bom = true,
+            "--output-encoding" => {
+                let name = next_value(&mut iter, arg)?;
+                cli.options.output_encoding = OutputEncoding::parse(name).ok_or_else(|| {
+                    format!("unknown encoding '{name}' (expected utf8, latin1 or shiftjis)")
+                })?;
+            }
+            "--encoding-fallback" => cli.options.encoding_fallback = true,
+            "--compress-m3u" => cli.options.compress_m3u = true,
+            "--compression-level" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.compression_level = match value.parse() {
+                    Ok(n @ 1..=9) => n,
+                    _ => return Err(format!("{arg} expects a number from 1 to 9, got '{value}'")),
+                };
+            }
+            "--checksums" => cli.options.checksums = true,
+            "--digest-algorithm" => {
+                let name = next_value(&mut iter, arg)?;
+                cli.options.digest_algorithm = DigestAlgorithm::parse(name).ok_or_else(|| {
+                    format!("unknown digest algorithm '{name}' (expected md5, sha1 or sha256)")
+                })?;
+            }
+            "--m3u-comment" => {
+                cli.options
+                    .m3u_comments
+                    .push(next_value(&mut iter, arg)?.to_string());
+            }
+            "--no-auto-comment" => cli.options.auto_comment = false,
+            "--auto-comment-date" => cli.options.auto_comment_date = true,
+            "--template-m3u" => {
+                cli.options.template_m3u = Some(PathBuf::from(next_value(&mut iter, arg)?));
+            }
+            "--fix" => cli.fix = true,
+            "--verify-checksums" => cli.verify_checksums = true,
+            "--verbose" | "-v" => cli.verbose = true,
+            "--only-multi-file" => cli.options.only_multi_file = true,
+            "--skip-dirs-with-subdirs" => cli.options.skip_dirs_with_subdirs = true,
+            "--allow-existing-subdirs" => cli.options.skip_dirs_with_subdirs = false,
+            "--exclude-hidden" => cli.options.exclude_hidden = Some(true),
+            "--include-hidden" => cli.options.exclude_hidden = Some(false),
+            "--absolute-paths" => cli.options.absolute_paths = true,
+            "--strip-prefix" => {
+                cli.options.strip_prefix = Some(next_value(&mut iter, arg)?.to_string());
+            }
+            "--prepend-path" => {
+                cli.options.prepend_path = Some(next_value(&mut iter, arg)?.to_string());
+            }
+            "--strip-prepend" => cli.strip_prepend = true,
+            "--json" => cli.json = true,
+            "--stdin" => cli.stdin = true,
+            "--keep-originals" => cli.keep_originals = true,
+            "--windows-paths" => cli.options.platform_separator = PathSeparator::Windows,
+            "--platform-separator" => {
+                let name = next_value(&mut iter, arg)?;
+                cli.options.platform_separator = PathSeparator::parse(name).ok_or_else(|| {
+                    format!("unknown separator '{name}' (expected linux, windows or auto)")
+                })?;
+            }
+            "--preserve-xattr" => cli.options.preserve_xattr = true,
+            "--rename-files" => cli.options.rename_files = true,
+            "--rename-dir" => {
+                cli.options.rename_dir = Some(RenameStrategy::parse(next_value(&mut iter, arg)?)?);
+            }
+            "--backup" => {
+                cli.options.backup = Some(PathBuf::from(next_value(&mut iter, arg)?));
+            }
+            "--newline" => {
+                let name = next_value(&mut iter, arg)?;
+                cli.options.newline = Newline::parse(name).ok_or_else(|| {
+                    format!("unknown line ending '{name}' (expected lf, crlf or platform)")
+                })?;
+            }
+            "--copy" => cli.options.copy = true,
+            "--overwrite" => cli.options.overwrite = true,
+            "--recursive" | "-r" => cli.options.recursive = true,
+            "--max-depth" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.max_depth = match value.parse() {
+                    Ok(n) if n > 0 => Some(n),
+                    _ => return Err(format!("{arg} expects a number above 0, got '{value}'")),
+                };
+                cli.options.recursive = true;
+            }
+            "--offset" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.offset = value
+                    .parse()
+                    .map_err(|_| format!("{arg} expects a number, got '{value}'"))?;
+            }
+            "--limit" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.limit = match value.parse() {
+                    Ok(n) if n > 0 => Some(n),
+                    _ => return Err(format!("{arg} expects a number above 0, got '{value}'")),
+                };
+            }
+            "--include-nfo" => cli.options.include_nfo = true,
+            "--group-by-extension" => cli.options.group_by_extension = true,
+            // only in debug builds (or with the simulate-errors feature), and not in --help
+            #[cfg(any(debug_assertions, feature = "simulate-errors"))]
+            "--simulate-errors" => {
+                let mode = next_value(&mut iter, arg)?;
+                m3ugen::enable_simulated_errors(m3ugen::SimulatedErrors::parse(mode)?);
+            }
+            "--follow-symlinks" => cli.options.symlinks = SymlinkMode::Follow,
+            "--copy-symlinks" => cli.options.symlinks = SymlinkMode::Copy,
+            "--since" => cli.options.since = Some(parse_since(next_value(&mut iter, arg)?)?),
+            "--nfo-extensions" => {
+                cli.options.include_nfo = true;
+                cli.options.nfo_extensions = parse_extension_list(next_value(&mut iter, arg)?)?;
+            }
+            "--one-per-disc" => cli.options.one_per_disc = true,
+            "--max-m3u-line-length" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.max_m3u_line_length = match value.parse() {
+                    Ok(n) if n > 0 => Some(n),
+                    _ => return Err(format!("{arg} expects a number above 0, got '{value}'")),
+                };
+            }
+            "--error-on-long-lines" => cli.options.error_on_long_lines = true,
+            "--max-files-per-m3u" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.max_files_per_m3u = match value.parse() {
+                    Ok(n) if n > 0 => Some(n),
+                    _ => return Err(format!("{arg} expects a number above 0, got '{value}'")),
+                };
+            }
+            "--jobs" | "-j" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.jobs = match value.parse() {
+                    Ok(n) if n > 0 => n,
+                    _ => return Err(format!("{arg} expects a number above 0, got '{value}'")),
+                };
+            }
+            "--extensions" => {
+                extensions.extend(parse_extension_list(next_value(&mut iter, arg)?)?);
+            }
+            "--ext" => extensions.extend(parse_extension_list(next_value(&mut iter, arg)?)?),
+            "--profile" | "--include" => {
+                let name = next_value(&mut iter, arg)?;
+                profile = Some(profile_extensions(name).ok_or_else(|| {
+                    format!(
+                        "unknown profile '{name}' (expected one of: {})",
+                        PROFILE_NAMES.join(", ")
+                    )
+                })?);
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!(
+                    "unknown flag {arg}\n\n{USAGE}\n\nFor more information, try '--help'"
+                ));
+            }
+            "validate" if cli.parents.is_empty() && cli.command == Command::Generate => {
+                cli.command = Command::Validate;
+            }
+            "unpack" if cli.parents.is_empty() && cli.command == Command::Generate => {
+                cli.command = Command::Unpack;
+            }
+            "stats" if cli.parents.is_empty() && cli.command == Command::Generate => {
+                cli.command = Command::Stats;
+            }
+            "merge" if cli.parents.is_empty() && cli.command == Command::Generate => {
+                cli.command = Command::Merge;
+            }
+            "dedupe" if cli.parents.is_empty() && cli.command == Command::Generate => {
+                cli.command = Command::Dedupe;
+            }
+            "find-orphans" if cli.parents.is_empty() && cli.command == Command::Generate => {
+                cli.command = Command::FindOrphans;
+            }
+            "diff" if cli.parents.is_empty() && cli.command == Command::Generate => {
+                cli.command = Command::Diff;
+            }
+            _ => cli.parents.push(normalize_path(Path::new(arg))),
+        }
+    }
+    if cli.help || cli.version {
+        return Ok(cli);
+    }
+    if cli.stdin {
+        if !cli.parents.is_empty() || cli.undo.is_some() {
+            return Err("--stdin can't be used with a <PARENT_DIR> or --undo".to_string());
+        }
+        if !matches!(
+            cli.command,
+            Command::Generate | Command::Unpack | Command::Merge
+        ) || cli.verify_checksums
+            || cli.options.watch
+            || cli.tui
+            || cli.interactive
+        {
+            return Err(
+                "--stdin only works with unpack, merge or without a subcommand, and can't be used \
+                 with --verify-checksums, --watch, --tui or --interactive"
+                    .to_string(),
+            );
+        }
+        if cli.options.transaction_log.is_some() || cli.options.conflict_log.is_some() {
+            return Err(
+                "--transaction-log and --conflict-log can't be used with --stdin, each parent \
+                 gets its own logs"
+                    .to_string(),
+            );
+        }
+    }
+    if cli.parents.is_empty() && cli.undo.is_none() && !cli.stdin {
+        return Err(format!(
+            "missing <PARENT_DIR>\n\n{USAGE}\n\nFor more information, try '--help'"
+        ));
+    }
+
+    // a summary file on its own still wants a summary in it
+    if cli.summary_file.is_some() && cli.summary.is_none() {
+        cli.summary = Some(SummaryFormat::Text);
+    }
+    if cli.fix && cli.command != Command::Validate {
+        return Err("--fix only works with the validate subcommand".to_string());
+    }
+    if cli.strip_prepend && cli.command != Command::Validate {
+        return Err("--strip-prepend only works with the validate subcommand".to_string());
+    }
+    if cli.json && !matches!(cli.command, Command::Stats | Command::Diff) {
+        return Err("--json only works with the stats and diff subcommands".to_string());
+    }
+    if cli.in_place.is_some() && cli.command != Command::Dedupe {
+        return Err(
+            "--in-place and --no-in-place only work with the dedupe subcommand".to_string(),
+        );
+    }
+    if cli.delete_orphans && cli.command != Command::FindOrphans {
+        return Err("--delete-orphans only works with the find-orphans subcommand".to_string());
+    }
+    if cli.yes && !cli.delete_orphans {
+        return Err("--yes only works with --delete-orphans".to_string());
+    }
+    if cli.command == Command::Diff && cli.parents.len() != 2 {
+        return Err("diff needs exactly two <PARENT_DIR>s to compare".to_string());
+    }
+    if cli.keep_originals && cli.command != Command::Merge {
+        return Err("--keep-originals only works with the merge subcommand".to_string());
+    }
+    if cli.parents.len() > 1 && (cli.options.watch || cli.tui) {
+        return Err("--watch and --tui only work with a single <PARENT_DIR>".to_string());
+    }
+    if cli.parents.len() > 1
+        && (cli.options.transaction_log.is_some() || cli.options.conflict_log.is_some())
+    {
+        return Err(
+            "--transaction-log and --conflict-log can't be used with more than one <PARENT_DIR>, \
+             each parent gets its own logs"
+                .to_string(),
+        );
+    }
+    if cli.command != Command::Generate && (cli.undo.is_some() || cli.options.watch) {
+        return Err("--undo and --watch can't be used with a subcommand".to_string());
+    }
+    if cli.tui && (cli.command != Command::Generate || cli.undo.is_some() || cli.options.watch) {
+        return Err("--tui can't be used with a subcommand, --undo or --watch".to_string());
+    }
+    if cli.interactive
+        && (cli.tui || cli.command != Command::Generate || cli.undo.is_some() || cli.options.watch)
+    {
+        return Err(
+            "--interactive can't be used with --tui, a subcommand, --undo or --watch".to_string(),
+        );
+    }
+    if cli.verify_checksums
+        && (matches!(
+            cli.command,
+            Command::Unpack
+                | Command::Stats
+                | Command::Merge
+                | Command::Diff
+                | Command::Dedupe
+                | Command::FindOrphans
+        ) || cli.undo.is_some()
+            || cli.options.watch
+            || cli.tui
+            || cli.interactive)
+    {
+        return Err(
+            "--verify-checksums only works on its own or with the validate subcommand".to_string(),
+        );
+    }
+    if cli.command == Command::Unpack && cli.options.copy {
+        return Err("--copy can't be used with unpack".to_string());
+    }
+    if cli.script_output.is_some() {
+        if !matches!(
+            cli.command,
+            Command::Generate | Command::Unpack | Command::Merge
+        ) || cli.undo.is_some()
+            || cli.stdin
+            || cli.options.watch
+            || cli.tui
+            || cli.interactive
+            || cli.verify_checksums
+        {
+            return Err(
+                "--script-output only works with unpack, merge or without a subcommand, and \
+                 can't be used with --undo, --stdin, --watch, --tui, --interactive or \
+                 --verify-checksums"
+                    .to_string(),
+            );
+        }
+        // the script writes the playlists as plain UTF-8 text
+        if cli.options.compress_m3u || cli.options.output_encoding != OutputEncoding::Utf8 {
+            return Err(
+                "--script-output can't be used with --compress-m3u or --output-encoding"
+                    .to_string(),
+            );
+        }
+    }
+    if cli.options.error_on_long_lines && cli.options.max_m3u_line_length.is_none() {
+        return Err("--error-on-long-lines only works with --max-m3u-line-length".to_string());
+    }
+    if cli.options.create_parent
+        && (cli.command != Command::Generate || cli.undo.is_some() || cli.stdin)
+    {
+        return Err(
+            "--create-parent only works without a subcommand, and can't be used with --undo or \
+             --stdin"
+                .to_string(),
+        );
+    }
+    if cli.options.archive_secondary_formats && !cli.options.auto_detect_format {
+        return Err("--archive-secondary-formats only works with --auto-detect-format".to_string());
+    }
+    if cli.options.archive_secondary_formats && cli.options.no_move {
+        return Err("--archive-secondary-formats can't be used with --no-move".to_string());
+    }
+    if cli.options.watch_poll && !cli.options.watch {
+        return Err("--watch-poll only works with --watch".to_string());
+    }
+    if cli.options.watch && cli.undo.is_some() {
+        return Err("--watch and --undo can't be used together".to_string());
+    }
+    if cli.options.template_m3u.is_some() && cli.options.format == PlaylistFormat::Pls {
+        return Err("--template-m3u can't be used with --output-format pls".to_string());
+    }
+    if cli.options.auto_comment_date && !cli.options.auto_comment {
+        return Err("--auto-comment-date can't be used with --no-auto-comment".to_string());
+    }
+    if cli.options.bom && cli.options.format != PlaylistFormat::M3u8 {
+        return Err("--bom only works with --output-format m3u8".to_string());
+    }
+    // .m3u8 is UTF-8 by definition, and --update/--append read the old playlist back as UTF-8
+    if cli.options.output_encoding != OutputEncoding::Utf8 {
+        if cli.options.format == PlaylistFormat::M3u8 || cli.options.bom {
+            return Err("--output-encoding can't be used with .m3u8 playlists".to_string());
+        }
+        if cli.options.update || cli.options.append {
+            return Err("--output-encoding can't be used with --update or --append".to_string());
+        }
+    }
+    if cli.options.encoding_fallback && cli.options.output_encoding == OutputEncoding::Utf8 {
+        return Err("--encoding-fallback only works with --output-encoding".to_string());
+    }
+    if cli.options.copy && cli.options.no_move {
+        return Err("--copy and --no-move can't be used together".to_string());
+    }
+    if cli.options.skip_existing && cli.options.update {
+        return Err("--skip-existing and --update can't be used together".to_string());
+    }
+    if cli.options.append && cli.options.compress_m3u {
+        return Err("--append can't be used with --compress-m3u".to_string());
+    }
+    if cli.options.append && cli.options.group_by_extension {
+        return Err("--append can't be used with --group-by-extension".to_string());
+    }
+    if cli.options.overwrite
+        && (cli.options.skip_existing || cli.options.update || cli.options.append)
+    {
+        return Err(
+            "--overwrite can't be used with --skip-existing, --update or --append".to_string(),
+        );
+    }
+    if cli.options.append && (cli.options.skip_existing || cli.options.update) {
+        return Err("--append can't be used with --skip-existing or --update".to_string());
+    }
+
+    // a profile sets the base list and any extra extensions are added on top of it, otherwise
+    // a user supplied list replaces the defaults
+    if let Some(profile) = profile {
+        cli.options.extensions = profile_with_extras(profile, extensions);
+    } else if !extensions.is_empty() {
+        cli.options.extensions = extensions;
+    }
+    Ok(cli)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, String> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        parse_args(&args, GenOptions::default())
+    }
+
+    #[test]
+    fn bom_needs_m3u8() {
+        let cli = parse(&["--bom", "--output-format", "m3u8", "games"]).unwrap();
+        assert!(cli.options.bom);
+        assert_eq!(
+            parse(&["--bom", "games"]).err().as_deref(),
+            Some("--bom only works with --output-format m3u8")
+        );
+        assert!(parse(&["--bom", "--output-format", "pls", "games"]).is_err());
+    }
+
+    #[test]
+    fn auto_comment_date_needs_the_comment() {
+        assert!(
+            parse(&["--auto-comment-date", "games"])
+                .unwrap()
+                .options
+                .auto_comment_date
+        );
+        assert!(parse(&["--no-auto-comment", "--auto-comment-date", "games"]).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+// Description: ANSI colors for terminal output, picked from how a line starts.
+
+use std::io::{self, IsTerminal};
+
+pub const RED: &str = "31";
+pub const GREEN: &str = "32";
+pub const YELLOW: &str = "33";
+pub const BLUE: &str = "34";
+
+/// When to color the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Only when the stream is a terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Parse the name used by --color
+    pub fn parse(s: &str) -> Option<ColorChoice> {
+        match s.to_lowercase().as_str() {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+
+    /// Check if output to stdout should be colored
+    pub fn stdout(&self) -> bool {
+        match self {
+            ColorChoice::Auto => io::stdout().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+
+    /// Check if output to stderr should be colored, decided apart from stdout since either one
+    /// can be redirected on its own
+    pub fn stderr(&self) -> bool {
+        match self {
+            ColorChoice::Auto => io::stderr().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Wrap `text` in the ANSI escape for `color`
+pub fn paint(text: &str, color: &str) -> String {
+    format!("\x1b[{color}m{text}\x1b[0m")
+}
+
+/// The color for a line of output: red for errors, yellow for warnings, green for changes and
+/// blue for everything informational. Lines without a known prefix stay plain.
+pub fn line_color(line: &str) -> Option<&'static str> {
+    let tag = line.split(':').next().unwrap_or_default();
+    match tag {
+        "ERROR" | "BROKEN" | "FAIL" => Some(RED),
+        "WARNING" => Some(YELLOW),
+        "FIXED" | "OK" => Some(GREEN),
+        "INFO" => Some(BLUE),
+        _ if tag.starts_with("WOULD ") => Some(GREEN),
+        _ => None,
+    }
+}
+
+/// Color a whole line by its prefix when `enabled`
+pub fn paint_line(line: &str, enabled: bool) -> String {
+    match (enabled, line_color(line)) {
+        (true, Some(color)) => paint(line, color),
+        _ => line.to_string(),
+    }
+}
@@ -0,0 +1,807 @@
+// Description: Load default options from a TOML config file. Only the bits of TOML a flat list
+//              of settings needs are supported: `key = value` pairs with strings, integers,
+//              booleans and arrays, plus comments.
+
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use m3ugen::{
+    ColorChoice, DigestAlgorithm, FormatPreference, GenOptions, NameTemplate, Newline,
+    OutputEncoding, PROFILE_NAMES, PathSeparator, Pattern, PlaylistFormat, RenameStrategy,
+    SortOrder, SymlinkMode, parse_extension_list, parse_since, profile_extensions,
+    profile_with_extras, validate_subdir_name, validate_subdir_prefix,
+};
+
+/// A value on the right hand side of `key = value`
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+/// A problem with the config file, pointing at the line it is on
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if self.line > 0 {
+            write!(f, " line {}", self.line)?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, " ('{key}')")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Settings read from the config file. Every field is optional, anything left out keeps the
+/// built in default, and flags on the command line win over all of them.
+#[derive(Debug, Default)]
+pub struct Config {
+    pub dry_run: Option<bool>,
+    pub no_move: Option<bool>,
+    pub extensions: Option<Vec<String>>,
+    pub profile: Option<String>,
+    pub extended: Option<bool>,
+    pub output_format: Option<PlaylistFormat>,
+    pub windows_compat: Option<bool>,
+    pub jobs: Option<usize>,
+    pub progress: Option<bool>,
+    pub skip_existing: Option<bool>,
+    pub update: Option<bool>,
+    pub force: Option<bool>,
+    pub prefer: Option<FormatPreference>,
+    pub auto_detect_format: Option<bool>,
+    pub format_priority: Option<Vec<String>>,
+    pub archive_secondary_formats: Option<bool>,
+    pub conflict_log: Option<PathBuf>,
+    pub transaction_log: Option<PathBuf>,
+    pub watch: Option<bool>,
+    pub debounce_ms: Option<u64>,
+    pub watch_poll: Option<bool>,
+    pub watch_interval_ms: Option<u64>,
+    pub recursive: Option<bool>,
+    pub max_depth: Option<usize>,
+    pub copy: Option<bool>,
+    pub overwrite: Option<bool>,
+    pub sort: Option<SortOrder>,
+    pub subdir_prefix: Option<String>,
+    pub subdir_name: Option<String>,
+    pub subdir_depth: Option<usize>,
+    pub subdir_leaf_name: Option<String>,
+    pub filters: Option<Vec<Pattern>>,
+    pub excludes: Option<Vec<Pattern>>,
+    pub include_dirs: Option<Vec<Pattern>>,
+    pub exclude_dirs: Option<Vec<Pattern>>,
+    pub output_dir: Option<PathBuf>,
+    pub m3u_filename: Option<NameTemplate>,
+    pub pbp_first_disc_name: Option<NameTemplate>,
+    pub bom: Option<bool>,
+    pub compress_m3u: Option<bool>,
+    pub compression_level: Option<u32>,
+    pub checksums: Option<bool>,
+    pub digest_algorithm: Option<DigestAlgorithm>,
+    pub newline: Option<Newline>,
+    pub color: Option<ColorChoice>,
+    pub log_file: Option<PathBuf>,
+    pub report_csv: Option<PathBuf>,
+    pub template_m3u: Option<PathBuf>,
+    pub absolute_paths: Option<bool>,
+    pub exclude_hidden: Option<bool>,
+    pub only_multi_file: Option<bool>,
+    pub skip_dirs_with_subdirs: Option<bool>,
+    pub lock_timeout_ms: Option<u64>,
+    pub strip_prefix: Option<String>,
+    pub prepend_path: Option<String>,
+    pub windows_paths: Option<bool>,
+    pub platform_separator: Option<PathSeparator>,
+    pub output_encoding: Option<OutputEncoding>,
+    pub encoding_fallback: Option<bool>,
+    pub rename_dir: Option<RenameStrategy>,
+    pub rename_files: Option<bool>,
+    pub backup: Option<PathBuf>,
+    pub preserve_xattr: Option<bool>,
+    pub max_files_per_m3u: Option<usize>,
+    pub max_m3u_line_length: Option<usize>,
+    pub one_per_disc: Option<bool>,
+    pub error_on_long_lines: Option<bool>,
+    pub include_nfo: Option<bool>,
+    pub group_by_extension: Option<bool>,
+    pub since: Option<SystemTime>,
+    pub symlinks: Option<SymlinkMode>,
+    pub retry_count: Option<u32>,
+    pub retry_delay_ms: Option<u64>,
+    pub nfo_extensions: Option<Vec<String>>,
+    pub extension_priority_order: Option<Vec<String>>,
+    pub append: Option<bool>,
+    pub ignore_file: Option<PathBuf>,
+    pub m3u_comments: Option<Vec<String>>,
+    pub auto_comment: Option<bool>,
+    pub auto_comment_date: Option<bool>,
+}
+
+/// Where to look for a config file when --config isn't given
+pub fn default_config_path() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+    base.map(|dir| dir.join("m3ugen").join("config.toml"))
+}
+
+/// Read the config file at `path`
+pub fn load_config(path: &Path) -> Result<Config, ConfigError> {
+    let contents = fs::read_to_string(path).map_err(|e| ConfigError {
+        path: path.to_path_buf(),
+        line: 0,
+        key: None,
+        message: e.to_string(),
+    })?;
+    parse_config(&contents).map_err(|(line, key, message)| ConfigError {
+        path: path.to_path_buf(),
+        line,
+        key,
+        message,
+    })
+}
+
+type ParseError = (usize, Option<String>, String);
+
+/// Split off a `#` comment that isn't inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_basic = false;
+    let mut in_literal = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_basic => escaped = true,
+            '"' if !in_literal => in_basic = !in_basic,
+            '\'' if !in_basic => in_literal = !in_literal,
+            '#' if !in_basic && !in_literal => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+/// Parse a single value, returning it and whatever text follows it
+fn parse_value(text: &str) -> Result<(Value, &str), String> {
+    let text = text.trim_start();
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut ret = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((Value::String(ret), &rest[i + 1..])),
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => ret.push('\n'),
+                    Some('t') => ret.push('\t'),
+                    Some('\\') => ret.push('\\'),
+                    Some('"') => ret.push('"'),
+                    Some(other) => return Err(format!("unsupported escape '\\{other}'")),
+                    None => break,
+                },
+                _ => ret.push(c),
+            }
+        }
+        return Err("unterminated string".to_string());
+    }
+    if let Some(rest) = text.strip_prefix('\'') {
+        return match rest.find('\'') {
+            Some(end) => Ok((Value::String(rest[..end].to_string()), &rest[end + 1..])),
+            None => Err("unterminated string".to_string()),
+        };
+    }
+    if let Some(mut rest) = text.strip_prefix('[') {
+        let mut items = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(items), after));
+            }
+            if rest.is_empty() {
+                return Err("unterminated array".to_string());
+            }
+            let (item, after) = parse_value(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err("expected ',' or ']' in array".to_string());
+            }
+        }
+    }
+
+    // bare words: booleans and integers
+    let end = text
+        .find(|c: char| c == ',' || c == ']' || c.is_whitespace())
+        .unwrap_or(text.len());
+    let word = &text[..end];
+    let value = match word {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => match word.replace('_', "").parse() {
+            Ok(n) => Value::Integer(n),
+            Err(_) => return Err(format!("'{word}' is not a valid value")),
+        },
+    };
+    Ok((value, &text[end..]))
+}
+
+/// Parse the whole file into `(key, value, line)` triples
+fn parse_pairs(contents: &str) -> Result<Vec<(String, Value, usize)>, ParseError> {
+    let mut ret = Vec::new();
+    let mut lines = contents.lines().enumerate();
+    while let Some((num, line)) = lines.next() {
+        let num = num + 1;
+        let mut line = strip_comment(line).trim().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err((num, None, "tables are not supported".to_string()));
+        }
+        let Some(eq) = line.find('=') else {
+            return Err((num, None, "expected 'key = value'".to_string()));
+        };
+        let key = line[..eq].trim().trim_matches('"').to_string();
+        if key.is_empty() {
+            return Err((num, None, "missing key".to_string()));
+        }
+
+        // arrays are allowed to span several lines
+        while line[eq + 1..].trim_start().starts_with('[')
+            && parse_value(&line[eq + 1..]).is_err_and(|e| e == "unterminated array")
+        {
+            match lines.next() {
+                Some((_, next)) => {
+                    line.push(' ');
+                    line.push_str(strip_comment(next).trim());
+                }
+                None => break,
+            }
+        }
+
+        let (value, rest) =
+            parse_value(&line[eq + 1..]).map_err(|e| (num, Some(key.clone()), e))?;
+        if !rest.trim().is_empty() {
+            return Err((num, Some(key), format!("unexpected '{}'", rest.trim())));
+        }
+        if ret.iter().any(|(k, _, _)| *k == key) {
+            return Err((num, Some(key), "key is set more than once".to_string()));
+        }
+        ret.push((key, value, num));
+    }
+    Ok(ret)
+}
+
+/// Turn the contents of a config file into a `Config`
+fn parse_config(contents: &str) -> Result<Config, ParseError> {
+    let mut config = Config::default();
+    for (key, value, line) in parse_pairs(contents)? {
+        let err = |message: String| (line, Some(key.clone()), message);
+        let as_bool = || match value {
+            Value::Bool(b) => Ok(b),
+            _ => Err(err("expected true or false".to_string())),
+        };
+        let as_string = || match &value {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(err("expected a string".to_string())),
+        };
+        let as_strings = || match &value {
+            Value::String(s) => Ok(vec![s.clone()]),
+            Value::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    Value::String(s) => Ok(s.clone()),
+                    _ => Err(err("expected an array of strings".to_string())),
+                })
+                .collect(),
+            _ => Err(err("expected an array of strings".to_string())),
+        };
+        let as_patterns = || {
+            as_strings()?
+                .iter()
+                .map(|p| Pattern::new(p).map_err(err))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        match key.replace('-', "_").as_str() {
+            "dry_run" => config.dry_run = Some(as_bool()?),
+            "no_move" | "flat" => config.no_move = Some(as_bool()?),
+            "extended" => config.extended = Some(as_bool()?),
+            "windows_compat" => config.windows_compat = Some(as_bool()?),
+            "progress" => config.progress = Some(as_bool()?),
+            "skip_existing" => config.skip_existing = Some(as_bool()?),
+            "update" => config.update = Some(as_bool()?),
+            "append" => config.append = Some(as_bool()?),
+            "force" => config.force = Some(as_bool()?),
+            "prefer" => {
+                let name = as_string()?;
+                config.prefer = Some(FormatPreference::parse(&name).ok_or_else(|| {
+                    err(format!("unknown format '{name}' (expected chd or cue)"))
+                })?);
+            }
+            "auto_detect_format" => config.auto_detect_format = Some(as_bool()?),
+            "format_priority" => {
+                let list = as_strings()?.join(",");
+                let priority = parse_extension_list(&list).map_err(err)?;
+                if priority.is_empty() {
+                    return Err(err(
+                        "expected a list of extensions like chd,cue,bin".to_string()
+                    ));
+                }
+                config.format_priority = Some(priority);
+            }
+            "archive_secondary_formats" => config.archive_secondary_formats = Some(as_bool()?),
+            "conflict_log" => config.conflict_log = Some(PathBuf::from(as_string()?)),
+            "transaction_log" => config.transaction_log = Some(PathBuf::from(as_string()?)),
+            "watch" => config.watch = Some(as_bool()?),
+            "debounce_ms" => match value {
+                Value::Integer(n) if n >= 0 => config.debounce_ms = Some(n as u64),
+                _ => return Err(err("expected a number of milliseconds".to_string())),
+            },
+            "watch_poll" => config.watch_poll = Some(as_bool()?),
+            "watch_interval_ms" => match value {
+                Value::Integer(n) if n > 0 => config.watch_interval_ms = Some(n as u64),
+                _ => return Err(err("expected a number of milliseconds above 0".to_string())),
+            },
+            "lock_timeout_ms" => match value {
+                Value::Integer(n) if n >= 0 => config.lock_timeout_ms = Some(n as u64),
+                _ => return Err(err("expected a number of milliseconds".to_string())),
+            },
+            "sort" => {
+                let name = as_string()?;
+                config.sort = Some(SortOrder::parse(&name).ok_or_else(|| {
+                    err(format!(
+                        "unknown sort order '{name}' (expected name, natural, extension, size \
+                         or none)"
+                    ))
+                })?);
+            }
+            "prefix" | "subdir_prefix" => {
+                let prefix = as_string()?;
+                validate_subdir_prefix(&prefix).map_err(err)?;
+                config.subdir_prefix = Some(prefix);
+            }
+            "subdir_name" => {
+                let name = as_string()?;
+                validate_subdir_name(&name).map_err(err)?;
+                config.subdir_name = Some(name);
+            }
+            "subdir_depth" => match value {
+                Value::Integer(n @ 1..=5) => config.subdir_depth = Some(n as usize),
+                _ => return Err(err("expected a number from 1 to 5".to_string())),
+            },
+            "subdir_leaf_name" => {
+                let name = as_string()?;
+                validate_subdir_name(&name).map_err(err)?;
+                config.subdir_leaf_name = Some(name);
+            }
+            "no_hidden" => {
+                if as_bool()? {
+                    config.subdir_prefix = Some(String::new());
+                }
+            }
+            "filter" => config.filters = Some(as_patterns()?),
+            "exclude" => config.excludes = Some(as_patterns()?),
+            "include_dirs" => config.include_dirs = Some(as_patterns()?),
+            "exclude_dirs" => config.exclude_dirs = Some(as_patterns()?),
+            "log_file" => config.log_file = Some(PathBuf::from(as_string()?)),
+            "report_csv" => config.report_csv = Some(PathBuf::from(as_string()?)),
+            "template_m3u" => config.template_m3u = Some(PathBuf::from(as_string()?)),
+            "ignore_file" => config.ignore_file = Some(PathBuf::from(as_string()?)),
+            "output_dir" => config.output_dir = Some(PathBuf::from(as_string()?)),
+            "m3u_filename" => {
+                config.m3u_filename = Some(NameTemplate::parse(&as_string()?).map_err(err)?);
+            }
+            "pbp_first_disc_name" => {
+                config.pbp_first_disc_name = Some(NameTemplate::parse(&as_string()?).map_err(err)?);
+            }
+            "bom" => config.bom = Some(as_bool()?),
+            "compress_m3u" => config.compress_m3u = Some(as_bool()?),
+            "compression_level" => match value {
+                Value::Integer(n @ 1..=9) => config.compression_level = Some(n as u32),
+                _ => return Err(err("expected a number from 1 to 9".to_string())),
+            },
+            "checksums" => config.checksums = Some(as_bool()?),
+            "digest_algorithm" => {
+                let name = as_string()?;
+                config.digest_algorithm = Some(DigestAlgorithm::parse(&name).ok_or_else(|| {
+                    err(format!(
+                        "unknown digest algorithm '{name}' (expected md5, sha1 or sha256)"
+                    ))
+                })?);
+            }
+            "m3u_comment" => config.m3u_comments = Some(as_strings()?),
+            "auto_comment" => config.auto_comment = Some(as_bool()?),
+            "auto_comment_date" => config.auto_comment_date = Some(as_bool()?),
+            "only_multi_file" => config.only_multi_file = Some(as_bool()?),
+            "skip_dirs_with_subdirs" => config.skip_dirs_with_subdirs = Some(as_bool()?),
+            "exclude_hidden" => config.exclude_hidden = Some(as_bool()?),
+            "absolute_paths" => config.absolute_paths = Some(as_bool()?),
+            "strip_prefix" => config.strip_prefix = Some(as_string()?),
+            "prepend_path" => config.prepend_path = Some(as_string()?),
+            "windows_paths" => config.windows_paths = Some(as_bool()?),
+            "output_encoding" => {
+                let name = as_string()?;
+                config.output_encoding = Some(OutputEncoding::parse(&name).ok_or_else(|| {
+                    err(format!(
+                        "unknown encoding '{name}' (expected utf8, latin1 or shiftjis)"
+                    ))
+                })?);
+            }
+            "encoding_fallback" => config.encoding_fallback = Some(as_bool()?),
+            "platform_separator" => {
+                let name = as_string()?;
+                config.platform_separator = Some(PathSeparator::parse(&name).ok_or_else(|| {
+                    err(format!(
+                        "unknown separator '{name}' (expected linux, windows or auto)"
+                    ))
+                })?);
+            }
+            "preserve_xattr" => config.preserve_xattr = Some(as_bool()?),
+            "backup" => config.backup = Some(PathBuf::from(as_string()?)),
+            "rename_files" => config.rename_files = Some(as_bool()?),
+            "rename_dir" => {
+                config.rename_dir = Some(RenameStrategy::parse(&as_string()?).map_err(err)?);
+            }
+            "color" => {
+                let name = as_string()?;
+                config.color = Some(ColorChoice::parse(&name).ok_or_else(|| {
+                    err(format!(
+                        "unknown color mode '{name}' (expected auto, always or never)"
+                    ))
+                })?);
+            }
+            "newline" => {
+                let name = as_string()?;
+                config.newline = Some(Newline::parse(&name).ok_or_else(|| {
+                    err(format!(
+                        "unknown line ending '{name}' (expected lf, crlf or platform)"
+                    ))
+                })?);
+            }
+            "copy" => config.copy = Some(as_bool()?),
+            "overwrite" => config.overwrite = Some(as_bool()?),
+            "recursive" => config.recursive = Some(as_bool()?),
+            "max_depth" => match value {
+                Value::Integer(n) if n > 0 => config.max_depth = Some(n as usize),
+                _ => return Err(err("expected a number above 0".to_string())),
+            },
+            "include_nfo" => config.include_nfo = Some(as_bool()?),
+            "group_by_extension" => config.group_by_extension = Some(as_bool()?),
+            "symlinks" => {
+                let name = as_string()?;
+                config.symlinks = Some(SymlinkMode::parse(&name).ok_or_else(|| {
+                    err(format!(
+                        "unknown symlink handling '{name}' (expected skip, follow or copy)"
+                    ))
+                })?);
+            }
+            "since" => config.since = Some(parse_since(&as_string()?).map_err(err)?),
+            "retry_count" => match value {
+                Value::Integer(n) if (0..=u32::MAX as i64).contains(&n) => {
+                    config.retry_count = Some(n as u32);
+                }
+                _ => return Err(err("expected a number of 0 or more".to_string())),
+            },
+            "retry_delay_ms" => match value {
+                Value::Integer(n) if n >= 0 => config.retry_delay_ms = Some(n as u64),
+                _ => return Err(err("expected a number of 0 or more".to_string())),
+            },
+            "extension_priority_order" => {
+                let list = as_strings()?.join(",");
+                config.extension_priority_order = Some(parse_extension_list(&list).map_err(err)?);
+            }
+            "nfo_extensions" => {
+                let list = as_strings()?.join(",");
+                config.nfo_extensions = Some(parse_extension_list(&list).map_err(err)?);
+            }
+            "max_m3u_line_length" => match value {
+                Value::Integer(n) if n > 0 => config.max_m3u_line_length = Some(n as usize),
+                _ => return Err(err("expected a number above 0".to_string())),
+            },
+            "error_on_long_lines" => config.error_on_long_lines = Some(as_bool()?),
+            "one_per_disc" => config.one_per_disc = Some(as_bool()?),
+            "max_files_per_m3u" => match value {
+                Value::Integer(n) if n > 0 => config.max_files_per_m3u = Some(n as usize),
+                _ => return Err(err("expected a number above 0".to_string())),
+            },
+            "jobs" => match value {
+                Value::Integer(n) if n > 0 => config.jobs = Some(n as usize),
+                _ => return Err(err("expected a number above 0".to_string())),
+            },
+            "output_format" => {
+                let name = as_string()?;
+                config.output_format = Some(PlaylistFormat::parse(&name).ok_or_else(|| {
+                    err(format!(
+                        "unknown output format '{name}' (expected m3u, m3u8 or pls)"
+                    ))
+                })?);
+            }
+            "profile" => {
+                let name = as_string()?;
+                if profile_extensions(&name).is_none() {
+                    return Err(err(format!(
+                        "unknown profile '{name}' (expected one of: {})",
+                        PROFILE_NAMES.join(", ")
+                    )));
+                }
+                config.profile = Some(name);
+            }
+            "extensions" => {
+                let list = as_strings()?.join(",");
+                config.extensions = Some(parse_extension_list(&list).map_err(err)?);
+            }
+            _ => return Err(err("unknown setting".to_string())),
+        }
+    }
+    Ok(config)
+}
+
+impl Config {
+    /// Copy every setting that was present onto `options`
+    pub fn apply(&self, options: &mut GenOptions) {
+        if let Some(v) = self.dry_run {
+            options.dry_run = v;
+        }
+        if let Some(v) = self.no_move {
+            options.no_move = v;
+        }
+        if let Some(v) = self.extended {
+            options.extended = v;
+        }
+        if let Some(v) = self.output_format {
+            options.format = v;
+        }
+        if let Some(v) = self.windows_compat {
+            options.windows_compat = v;
+        }
+        if let Some(v) = self.jobs {
+            options.jobs = v;
+        }
+        if let Some(v) = self.progress {
+            options.progress = v;
+        }
+        if let Some(v) = self.skip_existing {
+            options.skip_existing = v;
+        }
+        if let Some(v) = self.update {
+            options.update = v;
+        }
+        if let Some(v) = self.force {
+            options.force = v;
+        }
+        if let Some(v) = self.prefer {
+            options.prefer = v;
+        }
+        if let Some(v) = self.auto_detect_format {
+            options.auto_detect_format = v;
+        }
+        if let Some(v) = &self.format_priority {
+            options.format_priority = v.clone();
+        }
+        if let Some(v) = self.archive_secondary_formats {
+            options.archive_secondary_formats = v;
+        }
+        if let Some(v) = &self.conflict_log {
+            options.conflict_log = Some(v.clone());
+        }
+        if let Some(v) = &self.transaction_log {
+            options.transaction_log = Some(v.clone());
+        }
+        if let Some(v) = self.watch {
+            options.watch = v;
+        }
+        if let Some(v) = self.debounce_ms {
+            options.debounce_ms = v;
+        }
+        if let Some(v) = self.watch_poll {
+            options.watch_poll = v;
+        }
+        if let Some(v) = self.watch_interval_ms {
+            options.watch_interval_ms = v;
+        }
+        if let Some(v) = self.sort {
+            options.sort = v;
+        }
+        if let Some(v) = &self.subdir_prefix {
+            options.subdir_prefix = v.clone();
+        }
+        if let Some(v) = &self.subdir_name {
+            options.subdir_name = Some(v.clone());
+        }
+        if let Some(v) = self.subdir_depth {
+            options.subdir_depth = v;
+        }
+        if let Some(v) = &self.subdir_leaf_name {
+            options.subdir_leaf_name = v.clone();
+        }
+        if let Some(v) = &self.filters {
+            options.filters = v.clone();
+        }
+        if let Some(v) = &self.excludes {
+            options.excludes = v.clone();
+        }
+        if let Some(v) = &self.include_dirs {
+            options.include_dirs = v.clone();
+        }
+        if let Some(v) = &self.exclude_dirs {
+            options.exclude_dirs = v.clone();
+        }
+        if let Some(v) = &self.output_dir {
+            options.output_dir = Some(v.clone());
+        }
+        if let Some(v) = &self.m3u_filename {
+            options.m3u_filename = Some(v.clone());
+        }
+        if let Some(v) = &self.pbp_first_disc_name {
+            options.pbp_first_disc_name = Some(v.clone());
+        }
+        if let Some(v) = self.bom {
+            options.bom = v;
+        }
+        if let Some(v) = self.compress_m3u {
+            options.compress_m3u = v;
+        }
+        if let Some(v) = self.compression_level {
+            options.compression_level = v;
+        }
+        if let Some(v) = self.checksums {
+            options.checksums = v;
+        }
+        if let Some(v) = self.digest_algorithm {
+            options.digest_algorithm = v;
+        }
+        if let Some(v) = self.newline {
+            options.newline = v;
+        }
+        if let Some(v) = self.color {
+            options.color = v;
+        }
+        if let Some(v) = &self.log_file {
+            options.log_file = Some(v.clone());
+        }
+        if let Some(v) = &self.report_csv {
+            options.report_csv = Some(v.clone());
+        }
+        if let Some(v) = &self.template_m3u {
+            options.template_m3u = Some(v.clone());
+        }
+        if let Some(v) = self.absolute_paths {
+            options.absolute_paths = v;
+        }
+        if let Some(v) = &self.strip_prefix {
+            options.strip_prefix = Some(v.clone());
+        }
+        if let Some(v) = &self.prepend_path {
+            options.prepend_path = Some(v.clone());
+        }
+        if self.windows_paths == Some(true) {
+            options.platform_separator = PathSeparator::Windows;
+        }
+        if let Some(v) = self.platform_separator {
+            options.platform_separator = v;
+        }
+        if let Some(v) = self.output_encoding {
+            options.output_encoding = v;
+        }
+        if let Some(v) = self.encoding_fallback {
+            options.encoding_fallback = v;
+        }
+        if let Some(v) = &self.rename_dir {
+            options.rename_dir = Some(v.clone());
+        }
+        if let Some(v) = self.rename_files {
+            options.rename_files = v;
+        }
+        if let Some(v) = &self.backup {
+            options.backup = Some(v.clone());
+        }
+        if let Some(v) = self.preserve_xattr {
+            options.preserve_xattr = v;
+        }
+        if let Some(v) = self.include_nfo {
+            options.include_nfo = v;
+        }
+        if let Some(v) = self.group_by_extension {
+            options.group_by_extension = v;
+        }
+        if let Some(v) = self.since {
+            options.since = Some(v);
+        }
+        if let Some(v) = self.symlinks {
+            options.symlinks = v;
+        }
+        if let Some(v) = self.retry_count {
+            options.retry_count = v;
+        }
+        if let Some(v) = self.retry_delay_ms {
+            options.retry_delay_ms = v;
+        }
+        if let Some(v) = &self.extension_priority_order {
+            options.extension_priority_order = v.clone();
+        }
+        if let Some(v) = &self.nfo_extensions {
+            options.include_nfo = true;
+            options.nfo_extensions = v.clone();
+        }
+        if let Some(v) = self.max_files_per_m3u {
+            options.max_files_per_m3u = Some(v);
+        }
+        if let Some(v) = self.max_m3u_line_length {
+            options.max_m3u_line_length = Some(v);
+        }
+        if let Some(v) = self.error_on_long_lines {
+            options.error_on_long_lines = v;
+        }
+        if let Some(v) = self.one_per_disc {
+            options.one_per_disc = v;
+        }
+        if let Some(v) = self.exclude_hidden {
+            options.exclude_hidden = Some(v);
+        }
+        if let Some(v) = self.only_multi_file {
+            options.only_multi_file = v;
+        }
+        if let Some(v) = self.skip_dirs_with_subdirs {
+            options.skip_dirs_with_subdirs = v;
+        }
+        if let Some(v) = self.lock_timeout_ms {
+            options.lock_timeout_ms = v;
+        }
+        if let Some(v) = self.append {
+            options.append = v;
+        }
+        if let Some(v) = &self.ignore_file {
+            options.ignore_file = Some(v.clone());
+        }
+        if let Some(v) = &self.m3u_comments {
+            options.m3u_comments = v.clone();
+        }
+        if let Some(v) = self.auto_comment {
+            options.auto_comment = v;
+        }
+        if let Some(v) = self.auto_comment_date {
+            options.auto_comment_date = v;
+        }
+        if let Some(v) = self.copy {
+            options.copy = v;
+        }
+        if let Some(v) = self.overwrite {
+            options.overwrite = v;
+        }
+        if let Some(v) = self.recursive {
+            options.recursive = v;
+        }
+        if let Some(v) = self.max_depth {
+            options.max_depth = Some(v);
+            options.recursive = true;
+        }
+
+        // same rules as the command line: a profile plus extras, or a list of replacements
+        let extra = self.extensions.clone().unwrap_or_default();
+        if let Some(profile) = self.profile.as_deref().and_then(profile_extensions) {
+            options.extensions = profile_with_extras(profile, extra);
+        } else if !extra.is_empty() {
+            options.extensions = extra;
+        }
+    }
+}
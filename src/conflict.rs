@@ -0,0 +1,129 @@
+// Description: Find child dirs that hold the same game as both a .chd and a .cue/.bin set, and
+//              write them to a report instead of guessing which one the user wants.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{get_path_dir_name, has_extension};
+
+/// Name of the report written to the parent dir when --conflict-log isn't given
+pub const DEFAULT_CONFLICT_LOG: &str = "conflicts.log";
+
+/// Formats --auto-detect-format ranks when --format-priority isn't given, the best first
+pub const DEFAULT_FORMAT_PRIORITY: &[&str] = &["chd", "cue", "bin"];
+
+/// Which format to keep when --force processes a conflicted dir
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FormatPreference {
+    #[default]
+    Chd,
+    Cue,
+}
+
+impl FormatPreference {
+    /// Parse the value given to --prefer
+    pub fn parse(name: &str) -> Option<FormatPreference> {
+        match name.to_lowercase().as_str() {
+            "chd" => Some(FormatPreference::Chd),
+            "cue" | "bin" => Some(FormatPreference::Cue),
+            _ => None,
+        }
+    }
+}
+
+/// A child dir with both formats in it
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub dir: PathBuf,
+    /// Every .chd, .cue and .bin file found
+    pub files: Vec<PathBuf>,
+    /// Whether the dir was processed anyway because of --force
+    pub forced: bool,
+    /// The format --auto-detect-format kept, if it settled the conflict
+    pub kept: Option<String>,
+}
+
+/// Check if a .cue/.bin file belongs to the cue side of a conflict
+fn is_cue_side(path: &Path) -> bool {
+    has_extension(path, &["cue", "bin"])
+}
+
+/// Get the conflicting files if `data_files` has both a .chd and a .cue or .bin
+pub fn find_conflict(data_files: &[PathBuf]) -> Option<Vec<PathBuf>> {
+    let has_chd = data_files.iter().any(|f| has_extension(f, &["chd"]));
+    let has_cue = data_files.iter().any(|f| is_cue_side(f));
+    if !has_chd || !has_cue {
+        return None;
+    }
+    Some(
+        data_files
+            .iter()
+            .filter(|f| has_extension(f, &["chd"]) || is_cue_side(f))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Drop the files of the format that isn't preferred
+pub fn apply_preference(data_files: &mut Vec<PathBuf>, prefer: FormatPreference) {
+    data_files.retain(|f| match prefer {
+        FormatPreference::Chd => !is_cue_side(f),
+        FormatPreference::Cue => !has_extension(f, &["chd"]),
+    });
+}
+
+/// Split `data_files` by `priority` (extensions, the best first) for --auto-detect-format.
+/// Returns the best format found, the files to keep (that format along with any format the
+/// priority doesn't rank) and the files of the lower ranked formats.
+pub fn pick_best_format(
+    data_files: Vec<PathBuf>,
+    priority: &[String],
+) -> Option<(String, Vec<PathBuf>, Vec<PathBuf>)> {
+    let best = priority
+        .iter()
+        .find(|ext| data_files.iter().any(|f| has_extension(f, &[ext.as_str()])))?
+        .clone();
+    let (kept, secondary) = data_files.into_iter().partition(|f| {
+        has_extension(f, &[best.as_str()])
+            || !priority.iter().any(|ext| has_extension(f, &[ext.as_str()]))
+    });
+    Some((best, kept, secondary))
+}
+
+/// Write a human readable list of the conflicts to `path`
+pub fn write_conflict_log(path: &Path, conflicts: &[Conflict]) -> io::Result<()> {
+    let mut conflicts = conflicts.to_vec();
+    conflicts.sort_by(|a, b| a.dir.cmp(&b.dir));
+
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    writeln!(
+        out,
+        "Directories that contain both .chd and .cue/.bin files ({}):",
+        conflicts.len()
+    )?;
+    for conflict in &conflicts {
+        writeln!(out)?;
+        match (&conflict.kept, conflict.forced) {
+            (Some(kept), _) => writeln!(
+                out,
+                "{} (kept the .{kept} files, --auto-detect-format)",
+                conflict.dir.display()
+            )?,
+            (None, true) => writeln!(out, "{} (processed with --force)", conflict.dir.display())?,
+            (None, false) => writeln!(out, "{} (skipped)", conflict.dir.display())?,
+        }
+        let mut names: Vec<String> = conflict
+            .files
+            .iter()
+            .map(|f| get_path_dir_name(f))
+            .collect();
+        names.sort();
+        for name in names {
+            writeln!(out, "    {name}")?;
+        }
+    }
+    out.flush()
+}
@@ -0,0 +1,124 @@
+// Description: The --report-csv audit file, a row for every file operation of a run that can be
+//              opened in a spreadsheet. Rows are written as soon as a game dir is done, so a run
+//              cut short with Ctrl+C still leaves every finished dir in the file.
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::{DirOutcome, Operation, ProcessReport, time::DateTime};
+
+/// The first line of the file
+const HEADER: &str = "timestamp,operation,source_path,destination_path,bytes,error_message\n";
+
+/// A CSV report shared by every worker, each row is written whole
+#[derive(Debug, Clone)]
+pub struct CsvReport {
+    file: Arc<Mutex<File>>,
+}
+
+/// Quote a field if it holds anything that would break the row apart
+fn quote(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+/// Size of a file, blank when it can't be read (like in a dry run)
+fn size_of(path: &Path) -> String {
+    fs::metadata(path)
+        .map(|m| m.len().to_string())
+        .unwrap_or_default()
+}
+
+impl CsvReport {
+    /// Create (or empty) `path` and write the header
+    pub fn create(path: &Path) -> io::Result<CsvReport> {
+        let mut file = File::create(path)?;
+        file.write_all(HEADER.as_bytes())?;
+        Ok(CsvReport {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// Write a row for every operation, skipped dir and error in `report`
+    pub fn record(&self, report: &ProcessReport) {
+        let lossy = |path: &Path| path.to_string_lossy().to_string();
+        let mut rows: Vec<[String; 5]> = Vec::new();
+        for op in &report.operations {
+            let path = op.path();
+            let (operation, source, destination) = match op {
+                Operation::CreateDir { .. } => ("create_dir", String::new(), lossy(path)),
+                Operation::CreateFile { .. } | Operation::Rewrite { .. } => {
+                    ("create_file", String::new(), lossy(path))
+                }
+                Operation::Rename { from, .. } => ("rename", lossy(from), lossy(path)),
+                Operation::RemoveFile { .. } => ("remove_file", lossy(path), String::new()),
+                Operation::RemoveDir { .. } => ("remove_dir", lossy(path), String::new()),
+            };
+            let bytes = match op {
+                Operation::CreateFile { .. }
+                | Operation::Rewrite { .. }
+                | Operation::Rename { .. } => size_of(path),
+                _ => String::new(),
+            };
+            rows.push([
+                operation.to_string(),
+                source,
+                destination,
+                bytes,
+                String::new(),
+            ]);
+        }
+        let skipped_dirs = report
+            .directories
+            .iter()
+            .filter(|d| {
+                matches!(
+                    d.outcome,
+                    Some(DirOutcome::Skipped | DirOutcome::Conflicted)
+                )
+            })
+            .map(|d| &d.path);
+        for path in skipped_dirs.chain(&report.symlinks_skipped) {
+            rows.push([
+                "skip".into(),
+                lossy(path),
+                String::new(),
+                String::new(),
+                String::new(),
+            ]);
+        }
+        for e in &report.errors {
+            rows.push([
+                "error".into(),
+                lossy(e.path()),
+                String::new(),
+                String::new(),
+                e.to_string(),
+            ]);
+        }
+        if rows.is_empty() {
+            return;
+        }
+
+        let timestamp = DateTime::now().iso_string();
+        let mut out = String::new();
+        for row in rows {
+            out.push_str(&timestamp);
+            for field in row {
+                out.push(',');
+                out.push_str(&quote(&field));
+            }
+            out.push('\n');
+        }
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        // a report that can't be written shouldn't stop the run
+        let _ = file.write_all(out.as_bytes());
+        let _ = file.flush();
+    }
+}
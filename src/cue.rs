@@ -0,0 +1,217 @@
+// Description: Read the track list out of .cue sheets so every file a sheet needs gets moved
+//              along with it.
+
+use std::{
+    error, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{relative_path, write_file_atomic};
+
+/// Why a .cue sheet couldn't be read
+#[derive(Debug)]
+pub enum CueParseError {
+    /// The sheet couldn't be read from disk
+    Io(io::Error),
+    /// A FILE line that doesn't have a file name in it
+    Malformed { line: usize },
+}
+
+impl fmt::Display for CueParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CueParseError::Io(e) => write!(f, "{e}"),
+            CueParseError::Malformed { line } => write!(f, "malformed FILE entry on line {line}"),
+        }
+    }
+}
+
+impl error::Error for CueParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CueParseError::Io(e) => Some(e),
+            CueParseError::Malformed { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for CueParseError {
+    fn from(e: io::Error) -> Self {
+        CueParseError::Io(e)
+    }
+}
+
+/// Pull the file name out of the rest of a FILE line, e.g. `"Track 01.bin" BINARY`
+fn parse_file_name(rest: &str) -> Option<&str> {
+    let rest = rest.trim();
+    if let Some(quoted) = rest.strip_prefix('"') {
+        return quoted.find('"').map(|end| &quoted[..end]);
+    }
+
+    // unquoted names can't contain spaces, the file type follows the name
+    rest.split_whitespace().next()
+}
+
+/// Split a line into the FILE keyword part and whatever follows it, if it is a FILE line
+fn split_file_line(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start_matches('\u{feff}').trim_start();
+    let keyword = trimmed.get(..4)?;
+    if !keyword.eq_ignore_ascii_case("FILE") || !trimmed[4..].starts_with([' ', '\t']) {
+        return None;
+    }
+    let keyword_end = line.len() - trimmed.len() + 4;
+    Some((&line[..keyword_end], &line[keyword_end..]))
+}
+
+/// Read the `FILE "..." BINARY` lines of a .cue sheet and return the referenced paths exactly as
+/// they are written in the sheet
+pub fn parse_cue_file(path: &Path) -> Result<Vec<PathBuf>, CueParseError> {
+    // cue sheets are often written by old tools in a legacy code page, so don't insist on UTF-8
+    let bytes = fs::read(path)?;
+    let contents = String::from_utf8_lossy(&bytes);
+
+    let mut ret = Vec::new();
+    for (num, line) in contents.lines().enumerate() {
+        let Some((_, rest)) = split_file_line(line) else {
+            continue;
+        };
+        match parse_file_name(rest) {
+            Some(name) if !name.is_empty() => ret.push(PathBuf::from(name)),
+            _ => return Err(CueParseError::Malformed { line: num + 1 }),
+        }
+    }
+    Ok(ret)
+}
+
+/// Work out what a FILE reference should say once the sheet lives in `new_base`. Tracks that
+/// were moved along with the sheet are referenced by name, anything else by a path relative to
+/// the new location. Absolute references to files that stayed put are left alone.
+pub(crate) fn rebase_reference(reference: &Path, old_base: &Path, new_base: &Path) -> PathBuf {
+    if let Some(name) = reference.file_name()
+        && new_base.join(name).is_file()
+    {
+        return PathBuf::from(name);
+    }
+
+    // absolute references still work from anywhere
+    if reference.is_absolute() {
+        return reference.to_path_buf();
+    }
+    relative_path(new_base, &old_base.join(reference))
+}
+
+/// Rewrite the FILE lines of a .cue sheet that was moved from `old_base` to `new_base` so they
+/// still point at the right tracks. The sheet is only rewritten when something changed.
+pub fn rewrite_cue_references(cue_path: &Path, old_base: &Path, new_base: &Path) -> io::Result<()> {
+    let bytes = fs::read(cue_path)?;
+    let contents = String::from_utf8_lossy(&bytes);
+
+    let mut changed = false;
+    let mut ret = String::with_capacity(contents.len());
+    for line in contents.split_inclusive('\n') {
+        let body = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[body.len()..];
+        let rewritten = split_file_line(body).and_then(|(keyword, rest)| {
+            let name = parse_file_name(rest)?;
+            let new_name = rebase_reference(Path::new(name), old_base, new_base);
+            if new_name == Path::new(name) {
+                return None;
+            }
+
+            // keep the file type (BINARY, WAVE, ...) that follows the name
+            let rest = rest.trim_start();
+            let after = match rest.strip_prefix('"') {
+                Some(quoted) => &quoted[name.len() + 1..],
+                None => &rest[name.len()..],
+            };
+            Some(format!("{keyword} \"{}\"{after}", new_name.display()))
+        });
+        match rewritten {
+            Some(new_line) => {
+                changed = true;
+                ret.push_str(&new_line);
+            }
+            None => ret.push_str(body),
+        }
+        ret.push_str(ending);
+    }
+
+    if changed {
+        write_file_atomic(cue_path, ret.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn parses_multi_track_sheets() {
+        let tmp = TempDir::new("cue-parse");
+        let sheet = tmp.write(
+            "Game.cue",
+            b"FILE \"Game (Track 01).bin\" BINARY\r\n  TRACK 01 MODE2/2352\r\n\
+              file Track02.bin BINARY\r\n  TRACK 02 AUDIO\r\n",
+        );
+        assert_eq!(
+            parse_cue_file(&sheet).unwrap(),
+            [
+                PathBuf::from("Game (Track 01).bin"),
+                PathBuf::from("Track02.bin")
+            ]
+        );
+
+        let sheet = tmp.write("Bad.cue", b"FILE \"Track01.bin\" BINARY\nFILE \n");
+        assert!(matches!(
+            parse_cue_file(&sheet),
+            Err(CueParseError::Malformed { line: 2 })
+        ));
+    }
+
+    // the rewritten references use the native separator
+    #[cfg(unix)]
+    #[test]
+    fn rewrites_multi_track_sheets() {
+        let tmp = TempDir::new("cue-rewrite");
+        let old_base = tmp.path().join("Game");
+        let new_base = old_base.join(".Game");
+        // the first two tracks were moved along with the sheet, the third one wasn't
+        tmp.write("Game/.Game/Track 01.bin", b"");
+        tmp.write("Game/.Game/Track 02.bin", b"");
+        tmp.write("Game/audio/Track 03.bin", b"");
+        let absolute = old_base.join("Track 02.bin");
+        let sheet = tmp.write(
+            "Game/.Game/Game.cue",
+            format!(
+                "REM GENRE Platformer\r\n\
+                 FILE \"bins/Track 01.bin\" BINARY\r\n  TRACK 01 MODE1/2352\r\n\
+                 FILE \"{}\" BINARY\r\n  TRACK 02 AUDIO\r\n\
+                 FILE \"audio/Track 03.bin\" WAVE\r\n  TRACK 03 AUDIO\r\n",
+                absolute.display()
+            )
+            .as_bytes(),
+        );
+
+        rewrite_cue_references(&sheet, &old_base, &new_base).unwrap();
+        assert_eq!(
+            fs::read_to_string(&sheet).unwrap(),
+            "REM GENRE Platformer\r\n\
+             FILE \"Track 01.bin\" BINARY\r\n  TRACK 01 MODE1/2352\r\n\
+             FILE \"Track 02.bin\" BINARY\r\n  TRACK 02 AUDIO\r\n\
+             FILE \"../audio/Track 03.bin\" WAVE\r\n  TRACK 03 AUDIO\r\n"
+        );
+    }
+
+    #[test]
+    fn leaves_up_to_date_sheets_alone() {
+        let tmp = TempDir::new("cue-unchanged");
+        let new_base = tmp.path().join("Game/.Game");
+        tmp.write("Game/.Game/Track 01.bin", b"");
+        let contents = b"FILE \"Track 01.bin\" BINARY\n  TRACK 01 MODE1/2352\n";
+        let sheet = tmp.write("Game/.Game/Game.cue", contents);
+        rewrite_cue_references(&sheet, &tmp.path().join("Game"), &new_base).unwrap();
+        assert_eq!(fs::read(&sheet).unwrap(), contents);
+    }
+}
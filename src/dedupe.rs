@@ -0,0 +1,194 @@
+// Description: The dedupe subcommand: drop the entries a playlist lists more than once (which
+//              --append runs can pile up), keeping the first of each.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    GenError, GenOptions, get_path_dir_name, gzip, has_extension, is_playlist_file,
+    read_playlist_file, strip_gz, validate::child_dirs, verify_dir, write_file_atomic,
+};
+
+/// What a dedupe run found
+#[derive(Debug, Default)]
+pub struct DedupeReport {
+    pub playlists_checked: usize,
+    /// Playlists that had duplicates, with how many entries were dropped and where the result
+    /// was written
+    pub deduped: Vec<DedupedPlaylist>,
+    pub errors: Vec<GenError>,
+}
+
+impl DedupeReport {
+    /// Entries dropped over every playlist
+    pub fn removed(&self) -> usize {
+        self.deduped.iter().map(|d| d.removed).sum()
+    }
+}
+
+/// A playlist that listed some entries more than once
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DedupedPlaylist {
+    pub playlist: PathBuf,
+    /// Where the deduplicated playlist went, the playlist itself when rewritten in place
+    pub written: PathBuf,
+    pub removed: usize,
+}
+
+/// How two entries are compared, without case on Windows where the filesystem ignores it
+fn entry_key(path: &str) -> String {
+    match cfg!(windows) {
+        true => path.to_lowercase(),
+        false => path.to_string(),
+    }
+}
+
+/// Drop every entry of an .m3u whose path was listed before, along with the #EXTINF line in
+/// front of it. Every other line (and the line endings) is kept as it was. Returns the new
+/// contents and how many entries were dropped.
+pub fn dedupe_playlist(contents: &str) -> (String, usize) {
+    let mut ret = String::with_capacity(contents.len());
+    let contents = match contents.strip_prefix('\u{feff}') {
+        Some(rest) => {
+            ret.push('\u{feff}');
+            rest
+        }
+        None => contents,
+    };
+    let mut seen: Vec<String> = Vec::new();
+    let mut removed = 0;
+    // an #EXTINF line waits for the entry it describes
+    let mut info: Option<&str> = None;
+    for line in contents.split_inclusive('\n') {
+        let body = line.trim_end_matches(['\r', '\n']).trim();
+        if body.is_empty() || body.starts_with('#') {
+            if let Some(pending) = info.take() {
+                ret.push_str(pending);
+            }
+            match body.starts_with("#EXTINF:") {
+                true => info = Some(line),
+                false => ret.push_str(line),
+            }
+            continue;
+        }
+        let key = entry_key(body);
+        if seen.contains(&key) {
+            removed += 1;
+            info = None;
+            continue;
+        }
+        seen.push(key);
+        if let Some(pending) = info.take() {
+            ret.push_str(pending);
+        }
+        ret.push_str(line);
+    }
+    if let Some(pending) = info {
+        ret.push_str(pending);
+    }
+    (ret, removed)
+}
+
+/// `Game.m3u` becomes `Game.dedupe.m3u`, next to it
+fn dedupe_path(playlist: &Path) -> PathBuf {
+    let plain = strip_gz(playlist);
+    let ext = plain
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = plain
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match has_extension(playlist, &["gz"]) {
+        true => format!("{stem}.dedupe.{ext}.gz"),
+        false => format!("{stem}.dedupe.{ext}"),
+    };
+    playlist.with_file_name(name)
+}
+
+/// Remove the duplicate entries of one playlist, in place or into a copy next to it
+fn dedupe_file(playlist: &Path, options: &GenOptions, in_place: bool, report: &mut DedupeReport) {
+    let contents = match read_playlist_file(playlist) {
+        Ok(c) => c,
+        Err(source) => {
+            report.errors.push(GenError::AccessFailed {
+                path: playlist.to_path_buf(),
+                source,
+            });
+            return;
+        }
+    };
+    report.playlists_checked += 1;
+    let (deduped, removed) = dedupe_playlist(&String::from_utf8_lossy(&contents));
+    if removed == 0 {
+        return;
+    }
+    let written = match in_place {
+        true => playlist.to_path_buf(),
+        false => dedupe_path(playlist),
+    };
+    if !options.dry_run {
+        // a compressed playlist stays compressed
+        let bytes = match has_extension(playlist, &["gz"]) {
+            true => gzip(
+                deduped.as_bytes(),
+                &get_path_dir_name(&strip_gz(&written)),
+                options.compression_level,
+            ),
+            false => deduped.into_bytes(),
+        };
+        if let Err(source) = write_file_atomic(&written, &bytes) {
+            report.errors.push(GenError::WriteEntryFailed {
+                path: written,
+                source,
+            });
+            return;
+        }
+    }
+    report.deduped.push(DedupedPlaylist {
+        playlist: playlist.to_path_buf(),
+        written,
+        removed,
+    });
+}
+
+/// Remove the duplicate entries from every .m3u and .m3u8 in the child dirs of `parent`. With
+/// `in_place` unset the playlists are left alone and the result is written next to each one
+/// as `NAME.dedupe.m3u`.
+pub fn dedupe_parent_dir(
+    parent: &Path,
+    options: &GenOptions,
+    in_place: bool,
+) -> Result<DedupeReport, GenError> {
+    verify_dir(parent, !options.dry_run)?;
+    let mut report = DedupeReport::default();
+    for child in child_dirs(parent, options)? {
+        let files = match fs::read_dir(&child) {
+            Ok(files) => files,
+            Err(source) => {
+                report.errors.push(GenError::ReadDirFailed {
+                    path: child.clone(),
+                    source,
+                });
+                continue;
+            }
+        };
+        // .pls entries are numbered, dropping one would mean renumbering the rest
+        let mut playlists: Vec<PathBuf> = files
+            .filter_map(Result::ok)
+            .map(|f| f.path())
+            .filter(|f| {
+                f.is_file() && is_playlist_file(f) && !has_extension(&strip_gz(f), &["pls"])
+            })
+            .filter(|f| !get_path_dir_name(&strip_gz(f)).contains(".dedupe."))
+            .collect();
+        playlists.sort();
+        for playlist in playlists {
+            dedupe_file(&playlist, options, in_place, &mut report);
+        }
+    }
+    Ok(report)
+}
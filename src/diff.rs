@@ -0,0 +1,245 @@
+// Description: Compare the playlists of two parent dirs for the diff subcommand, e.g. a backup of
+//              a library against the live one. Nothing is moved or created.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    GREEN, GenError, GenOptions, Json, PlaylistFormat, RED, YELLOW, build_path_from_parts,
+    get_path_dir_name, has_extension, is_playlist_file, paint, parse_playlist_entries,
+    read_playlist_file, strip_gz, validate::child_dirs, verify_dir,
+};
+
+/// How a game dir found in both parents differs between them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirDiff {
+    /// Name of the dir under both parents
+    pub name: String,
+    /// Entries only the first parent's playlists list
+    pub removed: Vec<String>,
+    /// Entries only the second parent's playlists list
+    pub added: Vec<String>,
+    /// Both list the same entries, in a different order
+    pub reordered: bool,
+    /// The hidden sub-dir is there in the first parent
+    pub left_sub_dir: bool,
+    /// The hidden sub-dir is there in the second parent
+    pub right_sub_dir: bool,
+}
+
+impl DirDiff {
+    /// Check if the dir is the same in both parents
+    pub fn is_same(&self) -> bool {
+        self.removed.is_empty()
+            && self.added.is_empty()
+            && !self.reordered
+            && self.left_sub_dir == self.right_sub_dir
+    }
+
+    /// The dir as JSON
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("name", self.name.as_str().into()),
+            ("removed", strings_json(&self.removed)),
+            ("added", strings_json(&self.added)),
+            ("reordered", self.reordered.into()),
+            ("left_sub_dir", self.left_sub_dir.into()),
+            ("right_sub_dir", self.right_sub_dir.into()),
+        ])
+    }
+}
+
+/// What a diff run found
+#[derive(Debug, Default)]
+pub struct LibraryDiff {
+    pub left: PathBuf,
+    pub right: PathBuf,
+    /// Dirs only the first parent has
+    pub only_left: Vec<String>,
+    /// Dirs only the second parent has
+    pub only_right: Vec<String>,
+    /// Dirs in both parents that differ
+    pub changed: Vec<DirDiff>,
+    pub errors: Vec<GenError>,
+}
+
+impl LibraryDiff {
+    /// Check if the two parents hold the same playlists
+    pub fn is_same(&self) -> bool {
+        self.only_left.is_empty() && self.only_right.is_empty() && self.changed.is_empty()
+    }
+
+    /// The differences the way diff -u shows them, `-` for what only the first parent has and `+`
+    /// for what only the second one has
+    pub fn text(&self, color: bool) -> String {
+        let line = |text: String, code: &str| match color {
+            true => format!("{}\n", paint(&text, code)),
+            false => format!("{text}\n"),
+        };
+        let mut ret = line(format!("--- {}", self.left.display()), RED);
+        ret.push_str(&line(format!("+++ {}", self.right.display()), GREEN));
+        for name in &self.only_left {
+            ret.push_str(&line(format!("- {name}/"), RED));
+        }
+        for name in &self.only_right {
+            ret.push_str(&line(format!("+ {name}/"), GREEN));
+        }
+        for dir in &self.changed {
+            ret.push_str(&line(format!("~ {}/", dir.name), YELLOW));
+            for entry in &dir.removed {
+                ret.push_str(&line(format!("  - {entry}"), RED));
+            }
+            for entry in &dir.added {
+                ret.push_str(&line(format!("  + {entry}"), GREEN));
+            }
+            if dir.reordered {
+                ret.push_str("  entries are in a different order\n");
+            }
+            match (dir.left_sub_dir, dir.right_sub_dir) {
+                (true, false) => ret.push_str(&line("  - hidden sub-dir".to_string(), RED)),
+                (false, true) => ret.push_str(&line("  + hidden sub-dir".to_string(), GREEN)),
+                _ => (),
+            }
+        }
+        ret.push_str(&format!(
+            "\n{} dirs only in {}, {} only in {}, {} that differ\n",
+            self.only_left.len(),
+            self.left.display(),
+            self.only_right.len(),
+            self.right.display(),
+            self.changed.len()
+        ));
+        ret
+    }
+
+    /// Every difference as JSON
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("left", self.left.to_string_lossy().as_ref().into()),
+            ("right", self.right.to_string_lossy().as_ref().into()),
+            ("only_left", strings_json(&self.only_left)),
+            ("only_right", strings_json(&self.only_right)),
+            (
+                "changed",
+                Json::Array(self.changed.iter().map(DirDiff::to_json).collect()),
+            ),
+            (
+                "errors",
+                Json::Array(self.errors.iter().map(|e| e.to_string().into()).collect()),
+            ),
+        ])
+    }
+}
+
+fn strings_json(strings: &[String]) -> Json {
+    Json::Array(strings.iter().map(|s| s.as_str().into()).collect())
+}
+
+/// The child dirs of `parent` by name
+fn dirs_by_name(
+    parent: &Path,
+    options: &GenOptions,
+) -> Result<BTreeMap<String, PathBuf>, GenError> {
+    verify_dir(parent, false)?;
+    Ok(child_dirs(parent, options)?
+        .into_iter()
+        .map(|child| (get_path_dir_name(&child), child))
+        .collect())
+}
+
+/// The entries of every playlist in `dir`, one playlist after the other in name order
+fn dir_entries(dir: &Path) -> Result<Vec<String>, GenError> {
+    let files = fs::read_dir(dir).map_err(|source| GenError::ReadDirFailed {
+        path: dir.to_path_buf(),
+        source,
+    })?;
+    let mut playlists: Vec<PathBuf> = files
+        .filter_map(Result::ok)
+        .map(|f| f.path())
+        .filter(|f| f.is_file() && is_playlist_file(f))
+        .collect();
+    playlists.sort();
+    let mut ret = Vec::new();
+    for playlist in playlists {
+        let contents = read_playlist_file(&playlist).map_err(|source| GenError::AccessFailed {
+            path: playlist.clone(),
+            source,
+        })?;
+        let format = match has_extension(&strip_gz(&playlist), &["pls"]) {
+            true => PlaylistFormat::Pls,
+            false => PlaylistFormat::M3u,
+        };
+        ret.extend(
+            parse_playlist_entries(&contents, format)
+                .into_iter()
+                .map(|e| e.path),
+        );
+    }
+    Ok(ret)
+}
+
+/// Compare one dir found under both parents
+fn diff_dir(
+    name: &str,
+    left: &Path,
+    right: &Path,
+    options: &GenOptions,
+) -> Result<DirDiff, GenError> {
+    let left_entries = dir_entries(left)?;
+    let right_entries = dir_entries(right)?;
+    let sub_dir = options.sub_dir_path(name);
+    let mut ret = DirDiff {
+        name: name.to_string(),
+        removed: left_entries
+            .iter()
+            .filter(|e| !right_entries.contains(e))
+            .cloned()
+            .collect(),
+        added: right_entries
+            .iter()
+            .filter(|e| !left_entries.contains(e))
+            .cloned()
+            .collect(),
+        left_sub_dir: build_path_from_parts(&[left, &sub_dir]).is_dir(),
+        right_sub_dir: build_path_from_parts(&[right, &sub_dir]).is_dir(),
+        ..DirDiff::default()
+    };
+    ret.reordered = ret.removed.is_empty() && ret.added.is_empty() && left_entries != right_entries;
+    Ok(ret)
+}
+
+/// Compare the child dirs of `left` with those of `right`: the dirs only one of them has, and for
+/// the dirs both have, the playlist entries and whether the hidden sub-dir is there
+pub fn diff_parent_dirs(
+    left: &Path,
+    right: &Path,
+    options: &GenOptions,
+) -> Result<LibraryDiff, GenError> {
+    let left_dirs = dirs_by_name(left, options)?;
+    let right_dirs = dirs_by_name(right, options)?;
+    let mut ret = LibraryDiff {
+        left: left.to_path_buf(),
+        right: right.to_path_buf(),
+        ..LibraryDiff::default()
+    };
+    for (name, left_dir) in &left_dirs {
+        let Some(right_dir) = right_dirs.get(name) else {
+            ret.only_left.push(name.clone());
+            continue;
+        };
+        match diff_dir(name, left_dir, right_dir, options) {
+            Ok(diff) if diff.is_same() => (),
+            Ok(diff) => ret.changed.push(diff),
+            Err(e) => ret.errors.push(e),
+        }
+    }
+    ret.only_right = right_dirs
+        .keys()
+        .filter(|name| !left_dirs.contains_key(*name))
+        .cloned()
+        .collect();
+    Ok(ret)
+}
@@ -0,0 +1,166 @@
+// Description: Work out which disc of a multi-disc game a file belongs to from its name.
+
+use std::path::{Path, PathBuf};
+
+use crate::{get_path_dir_name, has_extension};
+
+/// Words that can come right before a disc number
+const DISC_LABELS: &[&str] = &["disc", "disk", "cd", "side"];
+
+/// What the first disc of a multi-disc PSP game is called, the others being DISC2.PBP and up
+pub const DEFAULT_PBP_FIRST_DISC_NAME: &str = "EBOOT.PBP";
+
+/// A data file and the disc it holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscEntry {
+    pub path: PathBuf,
+    pub disc: u32,
+}
+
+/// Get the disc number from a marker like `(Disc 2)`, `(Disk 2)`, `(CD 2)`, `Disc_2` or `CD2`
+/// anywhere in a file name. A lone letter counts too, so `Disk B` and `Side B` are disc 2.
+pub fn disc_number(file_name: &str) -> Option<u32> {
+    // drop the extension so `.cd2` style extensions aren't mistaken for a marker
+    let stem = match file_name.rfind('.') {
+        Some(dot) if dot > 0 => &file_name[..dot],
+        _ => file_name,
+    };
+    let lower = stem.to_ascii_lowercase();
+    let bytes = lower.as_bytes();
+    for start in 0..bytes.len() {
+        // the label has to start a word, so `abcd 2` doesn't count
+        if start > 0 && bytes[start - 1].is_ascii_alphanumeric() {
+            continue;
+        }
+        for label in DISC_LABELS {
+            if !bytes[start..].starts_with(label.as_bytes()) {
+                continue;
+            }
+            let rest = &lower[start + label.len()..];
+            let rest = rest.trim_start_matches([' ', '_', '-', '.']);
+            let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+            if let Ok(number) = digits.parse() {
+                return Some(number);
+            }
+            // a letter only when it's a word of its own, so `Side Story` doesn't count
+            let mut letters = rest.bytes();
+            if let Some(letter @ b'a'..=b'z') = letters.next()
+                && !letters.next().is_some_and(|b| b.is_ascii_alphanumeric())
+            {
+                return Some(u32::from(letter - b'a') + 1);
+            }
+        }
+    }
+    None
+}
+
+/// Like `disc_number`, except that a .pbp called `first_pbp` (in any case) is always disc 1
+pub fn file_disc_number(file_name: &str, first_pbp: &str) -> Option<u32> {
+    match has_extension(Path::new(file_name), &["pbp"]) && file_name.eq_ignore_ascii_case(first_pbp)
+    {
+        true => Some(1),
+        false => disc_number(file_name),
+    }
+}
+
+/// Pair every file with its disc number and order them by disc. Files without a marker are
+/// disc 1, as is a .pbp called `first_pbp`. Files on the same disc keep their order.
+pub fn detect_discs(files: &[PathBuf], first_pbp: &str) -> Vec<DiscEntry> {
+    let mut ret: Vec<DiscEntry> = files
+        .iter()
+        .map(|path| DiscEntry {
+            disc: file_disc_number(&get_path_dir_name(path), first_pbp).unwrap_or(1),
+            path: path.clone(),
+        })
+        .collect();
+    ret.sort_by_key(|entry| entry.disc);
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_disc_numbers() {
+        let cases = [
+            ("Final Fantasy VII (Disc 1).chd", Some(1)),
+            ("Final Fantasy VII (Disc 2).chd", Some(2)),
+            ("Metal Gear Solid [Disc 1 of 2].cue", Some(1)),
+            ("Metal Gear Solid [Disc 2 of 2].cue", Some(2)),
+            ("Riven CD1.bin", Some(1)),
+            ("Riven (CD 3).bin", Some(3)),
+            ("Myst (Disk 2).chd", Some(2)),
+            ("Myst Disk A.chd", Some(1)),
+            ("Myst Disk B.chd", Some(2)),
+            ("Album (Side B).chd", Some(2)),
+            ("Game_Disc_4.chd", Some(4)),
+            ("Game - disc-10.chd", Some(10)),
+            ("GAME (DISC 3).CHD", Some(3)),
+            ("Game (Disc 02).chd", Some(2)),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(disc_number(name), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn handles_non_ascii_names() {
+        // the label search walks bytes, it must never slice inside a multi-byte character
+        let cases = [
+            ("Café (Disc 1).chd", Some(1)),
+            ("Pokémon Stadium (Disc 2).chd", Some(2)),
+            ("ファイナルファンタジーVII (Disc 3).chd", Some(3)),
+            ("Ōkami CD2.bin", Some(2)),
+            ("Café.chd", None),
+            ("ディスク.chd", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(disc_number(name), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn ignores_names_without_a_marker() {
+        let cases = [
+            "Crash Bandicoot.chd",
+            "Abcd 2.chd",
+            "Side Story.chd",
+            "Discworld.chd",
+            "Game (Disc).chd",
+            "Game.cd2",
+        ];
+        for name in cases {
+            assert_eq!(disc_number(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn first_pbp_is_disc_one() {
+        assert_eq!(
+            file_disc_number("eboot.pbp", DEFAULT_PBP_FIRST_DISC_NAME),
+            Some(1)
+        );
+        assert_eq!(
+            file_disc_number("DISC2.PBP", DEFAULT_PBP_FIRST_DISC_NAME),
+            Some(2)
+        );
+        assert_eq!(
+            file_disc_number("EBOOT.PBP.bak", DEFAULT_PBP_FIRST_DISC_NAME),
+            None
+        );
+    }
+
+    #[test]
+    fn orders_files_by_disc() {
+        let files: Vec<PathBuf> = ["Game (Disc 2).chd", "Game.chd", "Game (Disc 10).chd"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let discs: Vec<u32> = detect_discs(&files, DEFAULT_PBP_FIRST_DISC_NAME)
+            .iter()
+            .map(|entry| entry.disc)
+            .collect();
+        assert_eq!(discs, [1, 2, 10]);
+    }
+}
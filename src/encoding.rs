@@ -0,0 +1,118 @@
+// Description: Character encodings a playlist can be written in with --output-encoding, for
+//              frontends that can't read UTF-8.
+
+use crate::sjis::SHIFT_JIS;
+
+/// Encoding of the playlist text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputEncoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1, every character below U+0100
+    Latin1,
+    /// windows-31J (JIS X 0208, half-width katakana and the NEC and IBM extensions), the way
+    /// older Japanese loaders expect it
+    ShiftJis,
+}
+
+impl OutputEncoding {
+    /// Parse the value given to --output-encoding
+    pub fn parse(name: &str) -> Option<OutputEncoding> {
+        match name.to_lowercase().replace(['-', '_'], "").as_str() {
+            "utf8" => Some(OutputEncoding::Utf8),
+            "latin1" | "iso88591" => Some(OutputEncoding::Latin1),
+            "shiftjis" | "sjis" => Some(OutputEncoding::ShiftJis),
+            _ => None,
+        }
+    }
+
+    /// Name as given to --output-encoding
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputEncoding::Utf8 => "utf8",
+            OutputEncoding::Latin1 => "latin1",
+            OutputEncoding::ShiftJis => "shiftjis",
+        }
+    }
+
+    /// Encode `text`, or return the first character this encoding has no bytes for
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, char> {
+        match self {
+            OutputEncoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            OutputEncoding::Latin1 => text
+                .chars()
+                .map(|c| u8::try_from(u32::from(c)).map_err(|_| c))
+                .collect(),
+            OutputEncoding::ShiftJis => {
+                let mut ret = Vec::with_capacity(text.len());
+                for c in text.chars() {
+                    if c.is_ascii() {
+                        ret.push(c as u8);
+                        continue;
+                    }
+                    let code = u16::try_from(u32::from(c)).map_err(|_| c)?;
+                    let index = SHIFT_JIS
+                        .binary_search_by_key(&code, |&(unicode, _)| unicode)
+                        .map_err(|_| c)?;
+                    match SHIFT_JIS[index].1 {
+                        byte @ 0..=0xff => ret.push(byte as u8),
+                        pair => ret.extend_from_slice(&pair.to_be_bytes()),
+                    }
+                }
+                Ok(ret)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift_jis_covers_jis_x_0208() {
+        let encoded = OutputEncoding::ShiftJis.encode("ファイナルファンタジー7 ｶﾀｶﾅ");
+        assert_eq!(
+            encoded.unwrap(),
+            b"\x83\x74\x83\x40\x83\x43\x83\x69\x83\x8b\x83\x74\x83\x40\x83\x93\x83\x5e\
+              \x83\x57\x81\x5b7 \xb6\xc0\xb6\xc5"
+        );
+    }
+
+    #[test]
+    fn shift_jis_covers_the_windows_extensions() {
+        let cases = [
+            ('～', [0x81, 0x60]),
+            ('〜', [0x81, 0x60]),
+            ('￢', [0x81, 0xca]),
+            ('¬', [0x81, 0xca]),
+            ('－', [0x81, 0x7c]),
+            ('①', [0x87, 0x40]),
+            ('Ⅰ', [0x87, 0x54]),
+            ('㈱', [0x87, 0x8a]),
+            ('ⅰ', [0xfa, 0x40]),
+            // the IBM codes win over the NEC-selected copies at 0xED/0xEE
+            ('髙', [0xfb, 0xfc]),
+            ('﨑', [0xfa, 0xb1]),
+        ];
+        for (c, expected) in cases {
+            let encoded = OutputEncoding::ShiftJis.encode(&c.to_string());
+            assert_eq!(encoded, Ok(expected.to_vec()), "{c}");
+        }
+    }
+
+    #[test]
+    fn shift_jis_rejects_what_it_lacks() {
+        assert_eq!(OutputEncoding::ShiftJis.encode("Pokémon"), Err('é'));
+        assert_eq!(OutputEncoding::ShiftJis.encode("🎮"), Err('🎮'));
+    }
+
+    #[test]
+    fn latin1_stops_at_u_0100() {
+        assert_eq!(
+            OutputEncoding::Latin1.encode("Pokémon"),
+            Ok(b"Pok\xe9mon".to_vec())
+        );
+        assert_eq!(OutputEncoding::Latin1.encode("Ōkami"), Err('Ō'));
+    }
+}
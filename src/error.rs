@@ -0,0 +1,258 @@
+// Description: Error type returned by the library API.
+
+use std::{error, fmt, io, path::PathBuf};
+
+/// Anything that can go wrong while processing a parent dir
+#[derive(Debug)]
+pub enum GenError {
+    /// The path does not exist
+    PathNotFound(PathBuf),
+    /// The path exists but is not a directory
+    NotADirectory(PathBuf),
+    /// The current user isn't allowed to list the dir
+    NotReadable(PathBuf),
+    /// The current user isn't allowed to change the dir
+    NotWritable(PathBuf),
+    /// The path's name can't be used to build the sub-dir and .m3u names
+    InvalidName(PathBuf),
+    /// The --m3u-filename template gives an unusable name for this dir
+    InvalidPlaylistName { path: PathBuf, reason: String },
+    /// Checking whether the path exists failed
+    AccessFailed { path: PathBuf, source: io::Error },
+    /// Listing the contents of a directory failed
+    ReadDirFailed { path: PathBuf, source: io::Error },
+    /// Creating the hidden sub-dir failed
+    CreateSubdirFailed { path: PathBuf, source: io::Error },
+    /// Creating the .m3u file failed
+    CreateM3uFailed { path: PathBuf, source: io::Error },
+    /// Moving a data file into the sub-dir failed
+    MoveFileFailed {
+        src: PathBuf,
+        dst: PathBuf,
+        source: io::Error,
+    },
+    /// Renaming a game dir for --rename-dir failed
+    RenameDirFailed {
+        src: PathBuf,
+        dst: PathBuf,
+        source: io::Error,
+    },
+    /// Copying a game dir to the --backup dir failed
+    BackupFailed {
+        src: PathBuf,
+        dst: PathBuf,
+        source: io::Error,
+    },
+    /// Writing an entry to the .m3u file failed
+    WriteEntryFailed { path: PathBuf, source: io::Error },
+    /// Writing the transaction log failed
+    WriteLogFailed { path: PathBuf, source: io::Error },
+    /// A transaction log given to --undo couldn't be understood
+    InvalidTransactionLog { path: PathBuf, reason: String },
+    /// Reversing one of the operations in a transaction log failed
+    UndoFailed { path: PathBuf, source: io::Error },
+    /// Creating the --output-dir failed
+    CreateOutputDirFailed { path: PathBuf, source: io::Error },
+    /// Creating a missing parent dir for --create-parent failed
+    CreateParentFailed { path: PathBuf, source: io::Error },
+    /// The parent dir couldn't be watched for new child dirs
+    WatchFailed { path: PathBuf, source: io::Error },
+    /// Another run is already processing the parent dir
+    AlreadyLocked(PathBuf),
+    /// Creating or locking the lock file failed
+    LockFailed { path: PathBuf, source: io::Error },
+    /// The destination filesystem doesn't have room for the files
+    InsufficientSpace {
+        path: PathBuf,
+        required: u64,
+        available: u64,
+    },
+    /// A playlist entry is longer than --max-m3u-line-length allows
+    EntryTooLong {
+        path: PathBuf,
+        entry: String,
+        length: usize,
+        max: usize,
+    },
+}
+
+impl GenError {
+    /// Path the error is about
+    pub fn path(&self) -> &PathBuf {
+        match self {
+            GenError::PathNotFound(path)
+            | GenError::NotADirectory(path)
+            | GenError::NotReadable(path)
+            | GenError::NotWritable(path)
+            | GenError::InvalidName(path)
+            | GenError::AlreadyLocked(path)
+            | GenError::AccessFailed { path, .. }
+            | GenError::ReadDirFailed { path, .. }
+            | GenError::CreateSubdirFailed { path, .. }
+            | GenError::CreateM3uFailed { path, .. }
+            | GenError::WriteEntryFailed { path, .. }
+            | GenError::WriteLogFailed { path, .. }
+            | GenError::InvalidTransactionLog { path, .. }
+            | GenError::InvalidPlaylistName { path, .. }
+            | GenError::UndoFailed { path, .. }
+            | GenError::CreateOutputDirFailed { path, .. }
+            | GenError::CreateParentFailed { path, .. }
+            | GenError::WatchFailed { path, .. }
+            | GenError::LockFailed { path, .. }
+            | GenError::InsufficientSpace { path, .. }
+            | GenError::EntryTooLong { path, .. } => path,
+            GenError::MoveFileFailed { src, .. }
+            | GenError::RenameDirFailed { src, .. }
+            | GenError::BackupFailed { src, .. } => src,
+        }
+    }
+}
+
+impl fmt::Display for GenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenError::PathNotFound(path) => write!(f, "{} does not exist", path.display()),
+            GenError::NotADirectory(path) => write!(
+                f,
+                "the path '{}' exists but is a file, not a directory",
+                path.display()
+            ),
+            GenError::NotReadable(path) => {
+                write!(f, "permission denied reading {}", path.display())
+            }
+            GenError::NotWritable(path) => {
+                write!(f, "permission denied writing to {}", path.display())
+            }
+            GenError::InvalidName(path) => {
+                write!(f, "{} does not have a valid UTF-8 name", path.display())
+            }
+            GenError::InvalidPlaylistName { path, reason } => {
+                write!(
+                    f,
+                    "unable to name the playlist for {}: {reason}",
+                    path.display()
+                )
+            }
+            GenError::AccessFailed { path, source } => {
+                write!(f, "unable to access {}: {source}", path.display())
+            }
+            GenError::ReadDirFailed { path, source } => {
+                write!(f, "unable to read {}: {source}", path.display())
+            }
+            GenError::CreateSubdirFailed { path, source } => {
+                write!(f, "unable to create sub-dir {}: {source}", path.display())
+            }
+            GenError::CreateM3uFailed { path, source } => {
+                write!(f, "unable to create {}: {source}", path.display())
+            }
+            GenError::MoveFileFailed { src, dst, source } => write!(
+                f,
+                "unable to move {} to {}: {source}",
+                src.display(),
+                dst.display()
+            ),
+            GenError::RenameDirFailed { src, dst, source } => write!(
+                f,
+                "unable to rename {} to {}: {source}",
+                src.display(),
+                dst.display()
+            ),
+            GenError::BackupFailed { src, dst, source } => write!(
+                f,
+                "unable to back up {} to {}: {source}",
+                src.display(),
+                dst.display()
+            ),
+            GenError::WriteEntryFailed { path, source } => {
+                write!(f, "unable to write to {}: {source}", path.display())
+            }
+            GenError::WriteLogFailed { path, source } => write!(
+                f,
+                "unable to write transaction log {}: {source}",
+                path.display()
+            ),
+            GenError::InvalidTransactionLog { path, reason } => write!(
+                f,
+                "{} is not a valid transaction log: {reason}",
+                path.display()
+            ),
+            GenError::UndoFailed { path, source } => write!(
+                f,
+                "unable to undo the change to {}: {source}",
+                path.display()
+            ),
+            GenError::CreateOutputDirFailed { path, source } => write!(
+                f,
+                "unable to create output dir {}: {source}",
+                path.display()
+            ),
+            GenError::CreateParentFailed { path, source } => write!(
+                f,
+                "unable to create parent dir {}: {source}",
+                path.display()
+            ),
+            GenError::WatchFailed { path, source } => {
+                write!(f, "unable to watch {}: {source}", path.display())
+            }
+            GenError::AlreadyLocked(path) => write!(
+                f,
+                "another instance of m3ugen is already processing {}",
+                path.display()
+            ),
+            GenError::LockFailed { path, source } => {
+                write!(f, "unable to lock {}: {source}", path.display())
+            }
+            GenError::InsufficientSpace {
+                path,
+                required,
+                available,
+            } => write!(
+                f,
+                "not enough free space for {}: {required} bytes needed but only {available} bytes \
+                 available",
+                path.display()
+            ),
+            GenError::EntryTooLong {
+                path,
+                entry,
+                length,
+                max,
+            } => write!(
+                f,
+                "{entry} in {} is {length} characters long, more than the {max} allowed",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl error::Error for GenError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            GenError::PathNotFound(_)
+            | GenError::NotADirectory(_)
+            | GenError::NotReadable(_)
+            | GenError::NotWritable(_)
+            | GenError::InvalidName(_)
+            | GenError::AlreadyLocked(_)
+            | GenError::InsufficientSpace { .. }
+            | GenError::InvalidTransactionLog { .. }
+            | GenError::InvalidPlaylistName { .. }
+            | GenError::EntryTooLong { .. } => None,
+            GenError::AccessFailed { source, .. }
+            | GenError::ReadDirFailed { source, .. }
+            | GenError::CreateSubdirFailed { source, .. }
+            | GenError::CreateM3uFailed { source, .. }
+            | GenError::MoveFileFailed { source, .. }
+            | GenError::RenameDirFailed { source, .. }
+            | GenError::BackupFailed { source, .. }
+            | GenError::WriteEntryFailed { source, .. }
+            | GenError::WriteLogFailed { source, .. }
+            | GenError::UndoFailed { source, .. }
+            | GenError::CreateOutputDirFailed { source, .. }
+            | GenError::CreateParentFailed { source, .. }
+            | GenError::WatchFailed { source, .. }
+            | GenError::LockFailed { source, .. } => Some(source),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+// Description: Events sent while a parent dir is processed, so an interface like the TUI can
+//              follow along from another thread.
+
+use std::path::PathBuf;
+
+use crate::DirectoryResult;
+
+/// Something that happened during a run
+#[derive(Debug, Clone)]
+pub enum TuiEvent {
+    /// The child dirs that are about to be processed, in order
+    DirsFound(Vec<PathBuf>),
+    /// A worker started on a child dir
+    DirStarted(PathBuf),
+    /// A worker is done with a child dir
+    DirFinished(DirectoryResult),
+    /// A line that would otherwise have been printed to stdout
+    Log(String),
+    /// A warning that would otherwise have been printed to stderr
+    Warning(String),
+    /// Every child dir has been processed
+    Done,
+}
@@ -0,0 +1,508 @@
+// Description: Low level file operations used when moving game files around.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{FsOp, GenError, check_simulated_error};
+
+/// Kind of access checked by `can_access`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// List a dir or read a file
+    Read,
+    /// Create, rename and remove entries in a dir, or change a file
+    Write,
+}
+
+/// How a file ended up at its destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveKind {
+    /// A plain rename on the same filesystem
+    Renamed,
+    /// Copied to another filesystem and the original deleted
+    Copied,
+}
+
+/// What to do with game dirs and files that are symlinks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkMode {
+    /// Warn and leave them alone
+    #[default]
+    Skip,
+    /// Treat them like what they point at: files are copied into the sub-dir
+    Follow,
+    /// Recreate file links in the sub-dir, pointing at the same target
+    Copy,
+}
+
+impl SymlinkMode {
+    /// Parse the name used in the config file
+    pub fn parse(s: &str) -> Option<SymlinkMode> {
+        match s.to_lowercase().as_str() {
+            "skip" => Some(SymlinkMode::Skip),
+            "follow" => Some(SymlinkMode::Follow),
+            "copy" => Some(SymlinkMode::Copy),
+            _ => None,
+        }
+    }
+}
+
+/// Check if `path` itself is a symlink, without following it
+pub fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink())
+}
+
+/// Move the file link `src` to `dst` and remove the original link. `Follow` puts a copy of what
+/// it points at in its place, `Copy` a new link to the same target (made absolute, so it still
+/// resolves from the new location).
+pub fn move_symlink(src: &Path, dst: &Path, mode: SymlinkMode) -> io::Result<()> {
+    match mode {
+        SymlinkMode::Skip => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "symlinks are skipped",
+            ));
+        }
+        SymlinkMode::Follow => copy_with_metadata(src, dst)?,
+        SymlinkMode::Copy => {
+            let target = fs::canonicalize(src)?;
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(&target, dst)?;
+            #[cfg(windows)]
+            std::os::windows::fs::symlink_file(&target, dst)?;
+        }
+    }
+    fs::remove_file(src)
+}
+
+/// Check if a rename failed because the source and destination are on different filesystems
+fn is_cross_device(e: &io::Error) -> bool {
+    if e.kind() == io::ErrorKind::CrossesDevices {
+        return true;
+    }
+
+    // EXDEV, in case the OS error didn't get mapped to the error kind
+    cfg!(unix) && e.raw_os_error() == Some(18)
+}
+
+/// Check if a failed move is worth trying again: the file may only be locked for a moment (by a
+/// virus scanner or the search indexer on Windows). A missing file or a full disk won't fix
+/// itself.
+pub fn is_retriable(e: &io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+    e.kind() == io::ErrorKind::PermissionDenied
+        || (cfg!(windows) && matches!(e.raw_os_error(), Some(32 | 33)))
+}
+
+/// Copy `src` to `dst` and make sure the copy is complete, a partial copy is removed again
+pub fn copy_verified(src: &Path, dst: &Path) -> io::Result<()> {
+    let expected = fs::metadata(src)?.len();
+    let copied = fs::copy(src, dst)?;
+    let written = fs::metadata(dst)?.len();
+    if copied != expected || written != expected {
+        let _ = fs::remove_file(dst);
+        return Err(io::Error::other(format!(
+            "copy is incomplete ({written} of {expected} bytes)"
+        )));
+    }
+    Ok(())
+}
+
+/// Copy `src` to `dst` like `copy_verified`, then give the copy the permissions and (on Unix)
+/// the owner and group of the original. Only root can hand a file to another user, so a chown
+/// that isn't permitted leaves the copy owned by whoever runs us.
+pub fn copy_with_metadata(src: &Path, dst: &Path) -> io::Result<()> {
+    let metadata = fs::metadata(src)?;
+    copy_verified(src, dst)?;
+    let result = set_metadata(dst, &metadata);
+    if result.is_err() {
+        let _ = fs::remove_file(dst);
+    }
+    result
+}
+
+/// Copy the dir `src` with everything in it to `dst`, leaving out the entries of `src` named in
+/// `skip`. Links are made again pointing at the same target (on Unix) instead of being followed.
+pub fn copy_tree(src: &Path, dst: &Path, skip: &[&str]) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if skip.iter().any(|s| name == **s) {
+            continue;
+        }
+        let from = entry.path();
+        let to = dst.join(&name);
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copy_tree(&from, &to, &[])?;
+            continue;
+        }
+        // say which file failed, the error alone only says why
+        let result = match file_type {
+            #[cfg(unix)]
+            t if t.is_symlink() => fs::read_link(&from).and_then(|target| {
+                let _ = fs::remove_file(&to);
+                std::os::unix::fs::symlink(target, &to)
+            }),
+            _ => copy_with_metadata(&from, &to),
+        };
+        result.map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", from.display())))?;
+    }
+    Ok(())
+}
+
+fn set_metadata(dst: &Path, metadata: &fs::Metadata) -> io::Result<()> {
+    fs::set_permissions(dst, metadata.permissions())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match std::os::unix::fs::chown(dst, Some(metadata.uid()), Some(metadata.gid())) {
+            Err(e) if e.kind() != io::ErrorKind::PermissionDenied => return Err(e),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Copy the extended attributes of `src` onto `dst`. Attributes the destination can't take
+/// (an unsupported filesystem, or a namespace we aren't allowed to write) are skipped.
+#[cfg(target_os = "linux")]
+pub fn copy_xattrs(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::{
+        ffi::{CString, c_char, c_int, c_void},
+        os::unix::ffi::OsStrExt,
+    };
+
+    unsafe extern "C" {
+        fn listxattr(path: *const c_char, list: *mut c_char, size: usize) -> isize;
+        fn getxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *mut c_void,
+            size: usize,
+        ) -> isize;
+        fn setxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *const c_void,
+            size: usize,
+            flags: c_int,
+        ) -> c_int;
+    }
+    const ENOTSUP: i32 = 95;
+    const EPERM: i32 = 1;
+    let skippable = |e: &io::Error| matches!(e.raw_os_error(), Some(ENOTSUP | EPERM));
+
+    let to_c = |path: &Path| CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other);
+    let (src_c, dst_c) = (to_c(src)?, to_c(dst)?);
+
+    // SAFETY: a null buffer with size 0 only asks for the size of the list
+    let size = unsafe { listxattr(src_c.as_ptr(), std::ptr::null_mut(), 0) };
+    if size < 0 {
+        let e = io::Error::last_os_error();
+        return if skippable(&e) { Ok(()) } else { Err(e) };
+    }
+    let mut names = vec![0u8; size as usize];
+    // SAFETY: the buffer is as long as we say it is
+    let size = unsafe { listxattr(src_c.as_ptr(), names.as_mut_ptr().cast(), names.len()) };
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    names.truncate(size as usize);
+
+    for name in names.split(|&b| b == 0).filter(|n| !n.is_empty()) {
+        let name = CString::new(name).map_err(io::Error::other)?;
+        // SAFETY: as with listxattr, first the size and then the value into a buffer that big
+        let size = unsafe { getxattr(src_c.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut value = vec![0u8; size as usize];
+        let size = unsafe {
+            getxattr(
+                src_c.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr().cast(),
+                value.len(),
+            )
+        };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the name is NUL terminated and the value buffer holds `size` bytes
+        let set = unsafe {
+            setxattr(
+                dst_c.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                size as usize,
+                0,
+            )
+        };
+        if set != 0 {
+            let e = io::Error::last_os_error();
+            if !skippable(&e) {
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Copy the extended attributes of `src` onto `dst`, only done on Linux
+#[cfg(not(target_os = "linux"))]
+pub fn copy_xattrs(_src: &Path, _dst: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Copy `src` to `dst` with its metadata (and its extended attributes when `xattrs` is set),
+/// make sure the copy is complete and then delete `src`
+pub fn copy_then_delete(src: &Path, dst: &Path, xattrs: bool) -> io::Result<()> {
+    copy_with_metadata(src, dst)?;
+    if xattrs && let Err(e) = copy_xattrs(src, dst) {
+        let _ = fs::remove_file(dst);
+        return Err(e);
+    }
+    fs::remove_file(src)
+}
+
+/// Move a file, falling back to copy + delete when the destination is on another filesystem.
+/// A rename keeps everything about the file, the copy keeps its permissions, owner and (with
+/// `xattrs`) extended attributes.
+pub fn move_file_with_fallback(src: &Path, dst: &Path, xattrs: bool) -> io::Result<MoveKind> {
+    match check_simulated_error(FsOp::Rename).and_then(|_| fs::rename(src, dst)) {
+        Ok(()) => Ok(MoveKind::Renamed),
+        Err(e) if is_cross_device(&e) => {
+            copy_then_delete(src, dst, xattrs).map(|_| MoveKind::Copied)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Check if the current user has `kind` access to `path`
+#[cfg(unix)]
+pub fn can_access(path: &Path, kind: Access) -> bool {
+    use std::{
+        ffi::{CString, c_char, c_int},
+        os::unix::ffi::OsStrExt,
+    };
+
+    unsafe extern "C" {
+        fn access(path: *const c_char, mode: c_int) -> c_int;
+    }
+    const W_OK: c_int = 2;
+    const R_OK: c_int = 4;
+    const X_OK: c_int = 1;
+
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // dirs also need search permission to get at their entries
+    let exec = if path.is_dir() { X_OK } else { 0 };
+    let mode = match kind {
+        Access::Read => R_OK | exec,
+        Access::Write => W_OK | exec,
+    };
+    // SAFETY: access only reads the NUL terminated path
+    unsafe { access(c_path.as_ptr(), mode) == 0 }
+}
+
+/// Check if the current user has `kind` access to `path`
+#[cfg(not(unix))]
+pub fn can_access(path: &Path, kind: Access) -> bool {
+    match kind {
+        Access::Read if path.is_dir() => fs::read_dir(path).is_ok(),
+        Access::Read => fs::File::open(path).is_ok(),
+        Access::Write => fs::metadata(path).is_ok_and(|m| !m.permissions().readonly()),
+    }
+}
+
+/// Check if a dir entry is hidden: its name starts with a dot, or on Windows it has the hidden
+/// attribute
+pub fn is_hidden(path: &Path) -> bool {
+    if path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+    {
+        return true;
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        if fs::symlink_metadata(path)
+            .is_ok_and(|m| m.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// The closest dir at or above `path` that exists, for asking about a destination that hasn't
+/// been created yet
+fn existing_ancestor(path: &Path) -> &Path {
+    path.ancestors()
+        .find(|dir| !dir.as_os_str().is_empty() && dir.exists())
+        .unwrap_or(Path::new("."))
+}
+
+/// Check if `file` and `dest_dir` (or the closest part of it that exists) are on the same
+/// filesystem, so moving between them is a rename that needs no free space
+#[cfg(unix)]
+pub fn same_filesystem(file: &Path, dest_dir: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (
+        fs::metadata(file),
+        fs::metadata(existing_ancestor(dest_dir)),
+    ) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => false,
+    }
+}
+
+/// Check if `file` and `dest_dir` are on the same filesystem. Without a portable way to tell,
+/// assume they aren't.
+#[cfg(not(unix))]
+pub fn same_filesystem(_file: &Path, _dest_dir: &Path) -> bool {
+    false
+}
+
+/// Bytes an unprivileged user can still write to the filesystem holding `path`
+#[cfg(target_os = "linux")]
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    use std::{
+        ffi::{CString, c_char, c_int, c_ulong},
+        os::unix::ffi::OsStrExt,
+    };
+
+    #[repr(C)]
+    struct StatVfs {
+        f_bsize: c_ulong,
+        f_frsize: c_ulong,
+        f_blocks: c_ulong,
+        f_bfree: c_ulong,
+        f_bavail: c_ulong,
+        f_files: c_ulong,
+        f_ffree: c_ulong,
+        f_favail: c_ulong,
+        f_fsid: c_ulong,
+        f_flag: c_ulong,
+        f_namemax: c_ulong,
+        f_spare: [c_int; 6],
+    }
+    unsafe extern "C" {
+        fn statvfs(path: *const c_char, buf: *mut StatVfs) -> c_int;
+    }
+
+    let c_path = CString::new(existing_ancestor(path).as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut buf = std::mem::MaybeUninit::<StatVfs>::uninit();
+    // SAFETY: statvfs only reads the NUL terminated path and fills in buf
+    if unsafe { statvfs(c_path.as_ptr(), buf.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: statvfs returned 0, so buf was filled in
+    let buf = unsafe { buf.assume_init() };
+    // c_ulong is only 32 bits on some targets
+    #[allow(clippy::unnecessary_cast)]
+    Ok((buf.f_bavail as u64).saturating_mul(buf.f_frsize as u64))
+}
+
+/// Bytes an unprivileged user can still write to the filesystem holding `path`
+#[cfg(not(target_os = "linux"))]
+pub fn available_space(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "free space can't be checked on this platform",
+    ))
+}
+
+/// Make sure the filesystem holding `dest_dir` has room for all of `files`. When the free space
+/// can't be found out the check is skipped rather than blocking the move.
+pub fn check_space(files: &[PathBuf], dest_dir: &Path) -> Result<(), GenError> {
+    let required: u64 = files
+        .iter()
+        .filter_map(|f| fs::metadata(f).ok())
+        .map(|m| m.len())
+        .sum();
+    if required == 0 {
+        return Ok(());
+    }
+    match available_space(dest_dir) {
+        Ok(available) if available < required => Err(GenError::InsufficientSpace {
+            path: dest_dir.to_path_buf(),
+            required,
+            available,
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn copy_keeps_contents_and_read_only() {
+        let tmp = TempDir::new("copy-readonly");
+        let src = tmp.write("Game.chd", b"game data");
+        let mut permissions = fs::metadata(&src).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&src, permissions).unwrap();
+
+        let dst = tmp.path().join("copy.chd");
+        copy_with_metadata(&src, &dst).unwrap();
+        assert_eq!(fs::read(&dst).unwrap(), b"game data");
+        assert!(fs::metadata(&dst).unwrap().permissions().readonly());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_keeps_mode_and_owner() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let tmp = TempDir::new("copy-mode");
+        for mode in [0o600, 0o640, 0o755, 0o444] {
+            let src = tmp.write(&format!("{mode:o}.bin"), b"track");
+            fs::set_permissions(&src, fs::Permissions::from_mode(mode)).unwrap();
+            let dst = tmp.path().join(format!("{mode:o}.copy"));
+            copy_with_metadata(&src, &dst).unwrap();
+
+            let (before, after) = (fs::metadata(&src).unwrap(), fs::metadata(&dst).unwrap());
+            assert_eq!(after.mode() & 0o7777, mode, "mode {mode:o}");
+            assert_eq!((after.uid(), after.gid()), (before.uid(), before.gid()));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_then_delete_moves_the_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = TempDir::new("copy-delete");
+        let src = tmp.write("Game.cue", b"FILE \"Game.bin\" BINARY\n");
+        fs::set_permissions(&src, fs::Permissions::from_mode(0o640)).unwrap();
+        let dst = tmp.path().join("moved.cue");
+        copy_then_delete(&src, &dst, false).unwrap();
+
+        assert!(!src.exists());
+        assert_eq!(fs::read(&dst).unwrap(), b"FILE \"Game.bin\" BINARY\n");
+        assert_eq!(
+            fs::metadata(&dst).unwrap().permissions().mode() & 0o777,
+            0o640
+        );
+    }
+
+    #[test]
+    fn failed_copy_leaves_nothing_behind() {
+        let tmp = TempDir::new("copy-missing");
+        let dst = tmp.path().join("copy.chd");
+        assert!(copy_with_metadata(&tmp.path().join("missing.chd"), &dst).is_err());
+        assert!(!dst.exists());
+    }
+}
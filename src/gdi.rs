@@ -0,0 +1,198 @@
+// Description: Read the track list out of .gdi files (Dreamcast GD-ROM images) so every
+//              .bin/.raw track gets moved along with it.
+
+use std::{
+    error, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{cue::rebase_reference, write_file_atomic};
+
+/// Why a .gdi file couldn't be read
+#[derive(Debug)]
+pub enum GdiParseError {
+    /// The file couldn't be read from disk
+    Io(io::Error),
+    /// A track line without the six fields a track needs
+    Malformed { line: usize },
+}
+
+impl fmt::Display for GdiParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GdiParseError::Io(e) => write!(f, "{e}"),
+            GdiParseError::Malformed { line } => write!(f, "malformed track entry on line {line}"),
+        }
+    }
+}
+
+impl error::Error for GdiParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            GdiParseError::Io(e) => Some(e),
+            GdiParseError::Malformed { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for GdiParseError {
+    fn from(e: io::Error) -> Self {
+        GdiParseError::Io(e)
+    }
+}
+
+/// Find where the file name sits in a track line like `2 756 0 2352 "track 02.raw" 0`: the
+/// byte range of the name (without quotes) and of the whole field (with them)
+fn file_name_span(line: &str) -> Option<((usize, usize), (usize, usize))> {
+    // skip the track number, LBA, type and sector size
+    let mut pos = 0;
+    for _ in 0..4 {
+        let start = pos + line[pos..].find(|c: char| !c.is_whitespace())?;
+        pos = start + line[start..].find(char::is_whitespace)?;
+    }
+    let start = pos + line[pos..].find(|c: char| !c.is_whitespace())?;
+    let field = &line[start..];
+    let (name, field_len) = match field.strip_prefix('"') {
+        Some(quoted) => {
+            let end = quoted.find('"')?;
+            ((start + 1, start + 1 + end), end + 2)
+        }
+        None => {
+            let end = field.find(char::is_whitespace)?;
+            ((start, start + end), end)
+        }
+    };
+    // the offset field has to follow the name
+    line[start + field_len..].trim().parse::<i64>().ok()?;
+    Some((name, (start, start + field_len)))
+}
+
+/// Read the track lines of a .gdi file and return the referenced paths exactly as they are
+/// written in it
+pub fn parse_gdi(path: &Path) -> Result<Vec<PathBuf>, GdiParseError> {
+    let bytes = fs::read(path)?;
+    let contents = String::from_utf8_lossy(&bytes);
+
+    let mut ret = Vec::new();
+    // the first line is the number of tracks
+    for (num, line) in contents.lines().enumerate().skip(1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match file_name_span(line) {
+            Some(((start, end), _)) if end > start => ret.push(PathBuf::from(&line[start..end])),
+            _ => return Err(GdiParseError::Malformed { line: num + 1 }),
+        }
+    }
+    Ok(ret)
+}
+
+/// Rewrite the track lines of a .gdi file that was moved from `old_base` to `new_base` so they
+/// still point at the right tracks. The file is only rewritten when something changed.
+pub fn rewrite_gdi_references(gdi_path: &Path, old_base: &Path, new_base: &Path) -> io::Result<()> {
+    let bytes = fs::read(gdi_path)?;
+    let contents = String::from_utf8_lossy(&bytes);
+
+    let mut changed = false;
+    let mut ret = String::with_capacity(contents.len());
+    for (num, line) in contents.split_inclusive('\n').enumerate() {
+        let body = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[body.len()..];
+        let rewritten = file_name_span(body).filter(|_| num > 0).and_then(
+            |((start, end), (field_start, field_end))| {
+                let name = &body[start..end];
+                let new_name = rebase_reference(Path::new(name), old_base, new_base);
+                if new_name == Path::new(name) {
+                    return None;
+                }
+                let new_name = new_name.display().to_string();
+                let field = match new_name.contains(' ') {
+                    true => format!("\"{new_name}\""),
+                    false => new_name,
+                };
+                Some(format!(
+                    "{}{field}{}",
+                    &body[..field_start],
+                    &body[field_end..]
+                ))
+            },
+        );
+        match rewritten {
+            Some(new_line) => {
+                changed = true;
+                ret.push_str(&new_line);
+            }
+            None => ret.push_str(body),
+        }
+        ret.push_str(ending);
+    }
+
+    if changed {
+        write_file_atomic(gdi_path, ret.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    const SAMPLE: &str = "3\r\n\
+                          1 0 4 2352 track01.bin 0\r\n\
+                          2 756 0 2352 \"Crazy Taxi (Track 2).raw\" 0\r\n\
+                          3 45000 4 2352 track03.bin 0\r\n";
+
+    #[test]
+    fn parses_every_track() {
+        let tmp = TempDir::new("gdi-parse");
+        let gdi = tmp.write("Crazy Taxi.gdi", SAMPLE.as_bytes());
+        assert_eq!(
+            parse_gdi(&gdi).unwrap(),
+            ["track01.bin", "Crazy Taxi (Track 2).raw", "track03.bin"].map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn rejects_short_track_lines() {
+        let tmp = TempDir::new("gdi-malformed");
+        let gdi = tmp.write(
+            "Bad.gdi",
+            b"2\n1 0 4 2352 track01.bin 0\n2 756 0 track02.raw\n",
+        );
+        assert!(matches!(
+            parse_gdi(&gdi),
+            Err(GdiParseError::Malformed { line: 3 })
+        ));
+    }
+
+    #[test]
+    fn rewrites_moved_tracks() {
+        let tmp = TempDir::new("gdi-rewrite");
+        let old_base = tmp.path().join("Crazy Taxi");
+        let new_base = old_base.join(".Crazy Taxi");
+        for track in ["track01.bin", "Crazy Taxi (Track 2).raw", "track03.bin"] {
+            tmp.write(&format!("Crazy Taxi/.Crazy Taxi/{track}"), b"");
+        }
+        let gdi = tmp.write(
+            "Crazy Taxi/.Crazy Taxi/Crazy Taxi.gdi",
+            b"3\n\
+              1 0 4 2352 tracks/track01.bin 0\n\
+              2 756 0 2352 \"tracks/Crazy Taxi (Track 2).raw\" 0\n\
+              3 45000 4 2352 track03.bin 0\n",
+        );
+        rewrite_gdi_references(&gdi, &old_base, &new_base).unwrap();
+        assert_eq!(
+            fs::read_to_string(&gdi).unwrap(),
+            "3\n\
+             1 0 4 2352 track01.bin 0\n\
+             2 756 0 2352 \"Crazy Taxi (Track 2).raw\" 0\n\
+             3 45000 4 2352 track03.bin 0\n"
+        );
+
+        // nothing left to change the second time round
+        let gdi = tmp.write("Crazy Taxi/.Crazy Taxi/Crazy Taxi.gdi", SAMPLE.as_bytes());
+        rewrite_gdi_references(&gdi, &old_base, &new_base).unwrap();
+        assert_eq!(fs::read_to_string(&gdi).unwrap(), SAMPLE);
+    }
+}
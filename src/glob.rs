@@ -0,0 +1,150 @@
+// Description: Shell style wildcard patterns (`*`, `?` and `[...]`) for matching dir and file
+//              names.
+
+use std::fmt;
+
+/// One piece of a compiled pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// A character that has to match exactly
+    Literal(char),
+    /// `?`, any single character
+    Any,
+    /// `*`, any run of characters (including none)
+    Star,
+    /// `[a-z]` or `[!a-z]`, one character from (or not from) a set of ranges
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+/// A wildcard pattern matched against a whole name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    source: String,
+    tokens: Vec<Token>,
+}
+
+impl Pattern {
+    /// Compile a pattern, failing on an unclosed `[`
+    pub fn new(pattern: &str) -> Result<Pattern, String> {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' => {
+                    // a run of stars means the same as one
+                    if tokens.last() != Some(&Token::Star) {
+                        tokens.push(Token::Star);
+                    }
+                }
+                '?' => tokens.push(Token::Any),
+                '[' => {
+                    let negated = chars.next_if(|&c| c == '!' || c == '^').is_some();
+                    let mut ranges = Vec::new();
+                    // a `]` right after the `[` is part of the set
+                    let mut first = true;
+                    loop {
+                        let Some(c) = chars.next() else {
+                            return Err(format!("'{pattern}' has an unclosed '['"));
+                        };
+                        if c == ']' && !first {
+                            break;
+                        }
+                        first = false;
+                        let end = match chars.peek() {
+                            Some('-') => {
+                                chars.next();
+                                match chars.next_if(|&c| c != ']') {
+                                    Some(end) => end,
+                                    None => {
+                                        // `[a-]` means `a` or `-`
+                                        ranges.push(('-', '-'));
+                                        c
+                                    }
+                                }
+                            }
+                            _ => c,
+                        };
+                        ranges.push((c, end));
+                    }
+                    tokens.push(Token::Class { negated, ranges });
+                }
+                '\\' => tokens.push(Token::Literal(chars.next().unwrap_or('\\'))),
+                c => tokens.push(Token::Literal(c)),
+            }
+        }
+        Ok(Pattern {
+            source: pattern.to_string(),
+            tokens,
+        })
+    }
+
+    /// The pattern as it was given
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Check if the whole of `name` matches
+    pub fn matches(&self, name: &str) -> bool {
+        self.matches_with(name, false)
+    }
+
+    /// Check if the whole of `name` matches, ignoring ASCII case
+    pub fn matches_ignore_case(&self, name: &str) -> bool {
+        self.matches_with(name, true)
+    }
+
+    /// Check if the whole of the dir name `name` matches, ignoring case on Windows where the
+    /// filesystem does too
+    pub fn matches_dir_name(&self, name: &str) -> bool {
+        self.matches_with(name, cfg!(windows))
+    }
+
+    fn matches_with(&self, name: &str, ignore_case: bool) -> bool {
+        let name: Vec<char> = name.chars().collect();
+        let same = |a: char, b: char| a == b || (ignore_case && a.eq_ignore_ascii_case(&b));
+        let one = |token: &Token, c: char| match token {
+            Token::Literal(l) => same(*l, c),
+            Token::Any => true,
+            Token::Star => false,
+            Token::Class { negated, ranges } => {
+                let lower = c.to_ascii_lowercase();
+                let upper = c.to_ascii_uppercase();
+                let hit = ranges.iter().any(|&(start, end)| {
+                    (start..=end).contains(&c)
+                        || (ignore_case
+                            && ((start..=end).contains(&lower) || (start..=end).contains(&upper)))
+                });
+                hit != *negated
+            }
+        };
+
+        // classic backtracking over the last star seen, linear for patterns without stars
+        let (mut t, mut n) = (0, 0);
+        let mut star: Option<(usize, usize)> = None;
+        while n < name.len() {
+            if t < self.tokens.len() && self.tokens[t] == Token::Star {
+                star = Some((t, n));
+                t += 1;
+            } else if t < self.tokens.len() && one(&self.tokens[t], name[n]) {
+                t += 1;
+                n += 1;
+            } else if let Some((star_t, star_n)) = star {
+                t = star_t + 1;
+                n = star_n + 1;
+                star = Some((star_t, star_n + 1));
+            } else {
+                return false;
+            }
+        }
+        self.tokens[t..].iter().all(|token| *token == Token::Star)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
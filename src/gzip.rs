@@ -0,0 +1,441 @@
+// Description: Just enough gzip (RFC 1952) and DEFLATE (RFC 1951) to write --compress-m3u
+//              playlists and read any .m3u.gz back, without pulling in a compression library.
+
+use std::{
+    error, fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::has_extension;
+
+/// Match lengths by length code (257 to 285) and the extra bits each one reads
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Match distances by distance code and the extra bits each one reads
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Order the code length code lengths are stored in by a dynamic block
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// How far back a match can reach, and how long it can be
+const WINDOW: usize = 32768;
+const MAX_MATCH: usize = 258;
+const MIN_MATCH: usize = 3;
+
+/// Header flags
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+/// Why a .gz file couldn't be decompressed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GzipError {
+    /// The data doesn't start with the gzip magic bytes
+    NotGzip,
+    /// The data ends before the stream does
+    Truncated,
+    /// The stream breaks the DEFLATE rules
+    Malformed(&'static str),
+    /// The data decompressed but doesn't match the checksum it was stored with
+    ChecksumMismatch,
+}
+
+impl fmt::Display for GzipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GzipError::NotGzip => write!(f, "not a gzip file"),
+            GzipError::Truncated => write!(f, "truncated gzip file"),
+            GzipError::Malformed(reason) => write!(f, "corrupt gzip file ({reason})"),
+            GzipError::ChecksumMismatch => write!(f, "corrupt gzip file (checksum mismatch)"),
+        }
+    }
+}
+
+impl error::Error for GzipError {}
+
+/// The path a compressed copy of `path` is written to, `Game.m3u` becomes `Game.m3u.gz`
+pub fn gz_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+/// The name of the file a .gz holds, `Game.m3u.gz` becomes `Game.m3u`. Anything else is returned
+/// as it is.
+pub fn strip_gz(path: &Path) -> PathBuf {
+    match has_extension(path, &["gz"]) {
+        true => path.with_extension(""),
+        false => path.to_path_buf(),
+    }
+}
+
+/// CRC-32 as used by gzip, bit by bit since playlists are small
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xedb8_8320,
+                _ => crc >> 1,
+            };
+        }
+    }
+    !crc
+}
+
+/// Writes bits starting at the least significant one, the way DEFLATE packs them
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, len: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += len;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes go most significant bit first
+    fn put_code(&mut self, code: u32, len: u32) {
+        self.put(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+/// Write a literal/length symbol with the fixed Huffman code
+fn put_fixed_literal(w: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => w.put_code(0x30 + symbol, 8),
+        144..=255 => w.put_code(0x190 + symbol - 144, 9),
+        256..=279 => w.put_code(symbol - 256, 7),
+        _ => w.put_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+/// Write a match of `len` bytes `dist` bytes back
+fn put_match(w: &mut BitWriter, len: usize, dist: usize) {
+    let code = LENGTH_BASE
+        .iter()
+        .rposition(|&b| b as usize <= len)
+        .unwrap_or(0);
+    put_fixed_literal(w, 257 + code as u32);
+    w.put(
+        (len - LENGTH_BASE[code] as usize) as u32,
+        LENGTH_EXTRA[code] as u32,
+    );
+    let code = DIST_BASE
+        .iter()
+        .rposition(|&b| b as usize <= dist)
+        .unwrap_or(0);
+    w.put_code(code as u32, 5);
+    w.put(
+        (dist - DIST_BASE[code] as usize) as u32,
+        DIST_EXTRA[code] as u32,
+    );
+}
+
+/// Compress `data` into a single DEFLATE block with the fixed Huffman codes. `level` (1 to 9)
+/// sets how many earlier positions are tried for each match.
+fn deflate(data: &[u8], level: u32) -> Vec<u8> {
+    const HASH_SIZE: usize = 1 << 15;
+    let max_chain = [4, 4, 8, 16, 32, 64, 128, 256, 1024, 4096][level.clamp(1, 9) as usize];
+    let hash = |i: usize| {
+        let key = (data[i] as usize) << 16 | (data[i + 1] as usize) << 8 | data[i + 2] as usize;
+        (key.wrapping_mul(2_654_435_761) >> 7) % HASH_SIZE
+    };
+    // the most recent position of every hash, and the one before each position with its hash
+    let mut head = vec![usize::MAX; HASH_SIZE];
+    let mut prev = vec![usize::MAX; data.len()];
+    let insert = |i: usize, head: &mut [usize], prev: &mut [usize]| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            prev[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut w = BitWriter::default();
+    // last block, fixed codes
+    w.put(1, 1);
+    w.put(1, 2);
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < max_chain {
+                let len = data[candidate..]
+                    .iter()
+                    .zip(&data[i..])
+                    .take(MAX_MATCH)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best.0 {
+                    best = (len, i - candidate);
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+        match best {
+            (len, dist) if len >= MIN_MATCH => {
+                put_match(&mut w, len, dist);
+                for j in i..i + len {
+                    insert(j, &mut head, &mut prev);
+                }
+                i += len;
+            }
+            _ => {
+                put_fixed_literal(&mut w, data[i] as u32);
+                insert(i, &mut head, &mut prev);
+                i += 1;
+            }
+        }
+    }
+    put_fixed_literal(&mut w, 256);
+    w.finish()
+}
+
+/// Compress `data` into a gzip file that says it holds `file_name`
+pub fn gzip(data: &[u8], file_name: &str, level: u32) -> Vec<u8> {
+    let extra_flags = match level {
+        9 => 2,
+        1 => 4,
+        _ => 0,
+    };
+    // no modification time, so the same playlist always compresses to the same bytes
+    let mut ret = vec![0x1f, 0x8b, 8, FNAME, 0, 0, 0, 0, extra_flags, 255];
+    // the name is stored nul terminated, so it can't hold a nul itself
+    ret.extend(file_name.bytes().filter(|&b| b != 0));
+    ret.push(0);
+    ret.extend(deflate(data, level));
+    ret.extend(crc32(data).to_le_bytes());
+    ret.extend((data.len() as u32).to_le_bytes());
+    ret
+}
+
+/// Reads bits starting at the least significant one
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, len: u32) -> Result<u32, GzipError> {
+        let mut ret = 0;
+        for n in 0..len {
+            let byte = *self.data.get(self.pos / 8).ok_or(GzipError::Truncated)?;
+            ret |= (((byte >> (self.pos % 8)) & 1) as u32) << n;
+            self.pos += 1;
+        }
+        Ok(ret)
+    }
+
+    /// Skip to the start of the next byte and return its offset
+    fn align(&mut self) -> usize {
+        self.pos = self.pos.div_ceil(8) * 8;
+        self.pos / 8
+    }
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the symbols in code order
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        lengths.iter().for_each(|&len| counts[len as usize] += 1);
+        counts[0] = 0;
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16)
+            .filter(|&s| lengths[s as usize] != 0)
+            .collect();
+        symbols.sort_by_key(|&s| lengths[s as usize]);
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, r: &mut BitReader) -> Result<u16, GzipError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= r.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(GzipError::Malformed("invalid Huffman code"))
+    }
+}
+
+/// The code lengths of a dynamic block
+fn read_dynamic_codes(r: &mut BitReader) -> Result<(Huffman, Huffman), GzipError> {
+    let literals = r.bits(5)? as usize + 257;
+    let distances = r.bits(5)? as usize + 1;
+    let code_lengths = r.bits(4)? as usize + 4;
+    let mut lengths = [0u8; 19];
+    for &at in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[at] = r.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&lengths);
+
+    let mut lengths: Vec<u8> = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match code_length_code.decode(r)? {
+            len @ 0..=15 => (len as u8, 1),
+            16 => {
+                let last = *lengths
+                    .last()
+                    .ok_or(GzipError::Malformed("repeat without a length"))?;
+                (last, r.bits(2)? + 3)
+            }
+            17 => (0, r.bits(3)? + 3),
+            _ => (0, r.bits(7)? + 11),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literals + distances {
+        return Err(GzipError::Malformed("too many code lengths"));
+    }
+    Ok((
+        Huffman::new(&lengths[..literals]),
+        Huffman::new(&lengths[literals..]),
+    ))
+}
+
+/// Decompress the DEFLATE stream at the start of `data`, returning it and the bytes it took up
+fn inflate(data: &[u8]) -> Result<(Vec<u8>, usize), GzipError> {
+    let mut r = BitReader { data, pos: 0 };
+    let mut out: Vec<u8> = Vec::new();
+    loop {
+        let last = r.bits(1)? == 1;
+        let (literal_code, distance_code) = match r.bits(2)? {
+            0 => {
+                let start = r.align();
+                let header = data.get(start..start + 4).ok_or(GzipError::Truncated)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                if len != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err(GzipError::Malformed("stored block length mismatch"));
+                }
+                let block = data
+                    .get(start + 4..start + 4 + len as usize)
+                    .ok_or(GzipError::Truncated)?;
+                out.extend_from_slice(block);
+                r.pos = (start + 4 + len as usize) * 8;
+                if last {
+                    break;
+                }
+                continue;
+            }
+            1 => {
+                let mut lengths = [8u8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+            }
+            2 => read_dynamic_codes(&mut r)?,
+            _ => return Err(GzipError::Malformed("invalid block type")),
+        };
+        loop {
+            let symbol = literal_code.decode(&mut r)? as usize;
+            match symbol {
+                0..=255 => out.push(symbol as u8),
+                256 => break,
+                _ => {
+                    let code = symbol - 257;
+                    let base = *LENGTH_BASE
+                        .get(code)
+                        .ok_or(GzipError::Malformed("invalid length code"))?;
+                    let len = base as usize + r.bits(LENGTH_EXTRA[code] as u32)? as usize;
+                    let code = distance_code.decode(&mut r)? as usize;
+                    let base = *DIST_BASE
+                        .get(code)
+                        .ok_or(GzipError::Malformed("invalid distance code"))?;
+                    let dist = base as usize + r.bits(DIST_EXTRA[code] as u32)? as usize;
+                    if dist > out.len() {
+                        return Err(GzipError::Malformed("distance too far back"));
+                    }
+                    // the match can overlap what it's copying, so go byte by byte
+                    for _ in 0..len {
+                        out.push(out[out.len() - dist]);
+                    }
+                }
+            }
+        }
+        if last {
+            break;
+        }
+    }
+    Ok((out, r.align()))
+}
+
+/// Decompress a gzip file, checking it against its stored checksum and size
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>, GzipError> {
+    if data.len() < 10 || data[0..2] != [0x1f, 0x8b] || data[2] != 8 {
+        return Err(GzipError::NotGzip);
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        let len = data.get(pos..pos + 2).ok_or(GzipError::Truncated)?;
+        pos += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = data
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or(GzipError::Truncated)?;
+            pos += end + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+
+    let (out, used) = inflate(data.get(pos..).ok_or(GzipError::Truncated)?)?;
+    let trailer = data
+        .get(pos + used..pos + used + 8)
+        .ok_or(GzipError::Truncated)?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc != crc32(&out) || size != out.len() as u32 {
+        return Err(GzipError::ChecksumMismatch);
+    }
+    Ok(out)
+}
@@ -0,0 +1,74 @@
+// Description: `.m3ugenignore` files, gitignore style lists of dir and file names to leave alone.
+
+use std::{fs, path::Path};
+
+use crate::Pattern;
+
+/// Name of the ignore file looked for in the parent dir
+pub const IGNORE_FILE_NAME: &str = ".m3ugenignore";
+
+/// One line of an ignore file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    pattern: Pattern,
+    /// `!pattern`, un-ignores what an earlier line ignored
+    negated: bool,
+    /// `pattern/`, only matches dirs
+    dir_only: bool,
+}
+
+/// The compiled lines of an ignore file. Like a .gitignore the last line that matches a name
+/// decides whether it's ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+impl IgnoreRules {
+    /// Compile the contents of an ignore file. `#` starts a comment line and blank lines are
+    /// skipped.
+    pub fn parse(contents: &str) -> Result<IgnoreRules, String> {
+        let mut rules = Vec::new();
+        for (num, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let pattern = Pattern::new(line).map_err(|e| format!("line {}: {e}", num + 1))?;
+            rules.push(Rule {
+                pattern,
+                negated,
+                dir_only,
+            });
+        }
+        Ok(IgnoreRules { rules })
+    }
+
+    /// Read and compile an ignore file
+    pub fn load(path: &Path) -> Result<IgnoreRules, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        IgnoreRules::parse(&contents).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// Check if there are no rules at all
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Check if a dir or file called `name` should be left alone
+    pub fn is_ignored(&self, name: &str, is_dir: bool) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.pattern.matches(name))
+            .is_some_and(|rule| !rule.negated)
+    }
+}
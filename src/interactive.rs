@@ -0,0 +1,72 @@
+// Description: The prompt shown for every child dir with --interactive.
+
+use std::io::{self, Write};
+
+use m3ugen::{Decision, DirOutcome, DirPlan, get_path_dir_name};
+
+const HELP: &str = "  y - process this dir\n  \
+                    n - skip it\n  \
+                    q - stop here, leaving the rest alone\n  \
+                    ? - show the full plan for this dir";
+
+/// Ask whether the `count` files find-orphans found should be removed, no unless told yes
+pub fn confirm_delete(count: usize) -> bool {
+    print!("remove these {count} files? [y/N] ");
+    let _ = io::stdout().flush();
+    let mut input = String::new();
+    match io::stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => {
+            println!();
+            false
+        }
+        Ok(_) => matches!(input.trim().to_lowercase().as_str(), "y" | "yes"),
+    }
+}
+
+/// Show what would happen to the dir in `plan` and ask whether to go ahead
+pub fn confirm(plan: &DirPlan) -> Decision {
+    println!("\n== {}", get_path_dir_name(&plan.dir));
+    let moves = plan.moves();
+    let playlist = plan.playlist();
+    match plan.outcome {
+        None | Some(DirOutcome::Skipped | DirOutcome::Conflicted) => {
+            // nothing to approve, but say why
+            plan.lines.iter().for_each(|line| println!("{line}"));
+        }
+        Some(_) => {
+            println!("files to move: {}", moves.len());
+            for line in &moves {
+                println!("  {line}");
+            }
+            println!("playlist:");
+            for line in &playlist {
+                println!("  {line}");
+            }
+        }
+    }
+
+    let mut input = String::new();
+    loop {
+        print!("process this dir? [y/n/q/?] ");
+        let _ = io::stdout().flush();
+        input.clear();
+        // the end of the input has no one left to answer, so stop
+        match io::stdin().read_line(&mut input) {
+            Ok(0) | Err(_) => {
+                println!();
+                return Decision::Quit;
+            }
+            Ok(_) => (),
+        }
+        match input.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Decision::Process,
+            "n" | "no" => return Decision::Skip,
+            "q" | "quit" => return Decision::Quit,
+            "?" => {
+                plan.lines.iter().for_each(|line| println!("  {line}"));
+                println!("{HELP}");
+            }
+            _ => println!("{HELP}"),
+        }
+    }
+}
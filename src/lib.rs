@@ -0,0 +1,437 @@
+// Description: Library half of m3ugen. Given a directory path of child dirs, create a .m3u file
+//              for each child dir, move its game files into a hidden sub-dir and point the
+//              .m3u at the new location. The binary in main.rs is a thin wrapper around
+//              `process_parent_dir`.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+};
+
+mod color;
+mod conflict;
+mod csv_report;
+mod cue;
+mod dedupe;
+mod diff;
+mod digest;
+mod disc;
+mod encoding;
+mod error;
+mod events;
+mod fsops;
+mod gdi;
+mod glob;
+mod gzip;
+mod ignore;
+mod json;
+mod lock;
+mod logging;
+mod mds;
+mod merge;
+mod natural_sort;
+mod options;
+mod orphans;
+mod plan;
+mod playlist;
+mod process;
+mod profiles;
+mod progress;
+mod rename;
+mod report;
+mod script;
+mod simulate;
+mod sjis;
+mod sort;
+mod stats;
+mod template;
+#[cfg(test)]
+mod testing;
+pub mod time;
+mod transaction;
+mod unpack;
+mod validate;
+mod watch;
+
+pub use color::{BLUE, ColorChoice, GREEN, RED, YELLOW, line_color, paint, paint_line};
+pub use conflict::{
+    Conflict, DEFAULT_CONFLICT_LOG, DEFAULT_FORMAT_PRIORITY, FormatPreference, apply_preference,
+    find_conflict, pick_best_format, write_conflict_log,
+};
+pub use csv_report::CsvReport;
+pub use cue::{CueParseError, parse_cue_file, rewrite_cue_references};
+pub use dedupe::{DedupeReport, DedupedPlaylist, dedupe_parent_dir, dedupe_playlist};
+pub use diff::{DirDiff, LibraryDiff, diff_parent_dirs};
+pub use digest::{
+    DigestAlgorithm, Md5, Sha1, Sha256, compute_digest, parse_checksums, render_checksums, to_hex,
+};
+pub use disc::{
+    DEFAULT_PBP_FIRST_DISC_NAME, DiscEntry, detect_discs, disc_number, file_disc_number,
+};
+pub use encoding::OutputEncoding;
+pub use error::GenError;
+pub use events::TuiEvent;
+pub use fsops::{
+    Access, MoveKind, SymlinkMode, available_space, can_access, check_space, copy_then_delete,
+    copy_tree, copy_verified, copy_with_metadata, copy_xattrs, is_hidden, is_retriable, is_symlink,
+    move_file_with_fallback, move_symlink, same_filesystem,
+};
+pub use gdi::{GdiParseError, parse_gdi, rewrite_gdi_references};
+pub use glob::Pattern;
+pub use gzip::{GzipError, gunzip, gz_path, gzip, strip_gz};
+pub use ignore::{IGNORE_FILE_NAME, IgnoreRules};
+pub use json::{Json, parse_json};
+pub use lock::{DirLock, LOCK_FILE_NAME};
+pub use logging::{Level, LogFile};
+pub use mds::{MdsParseError, parse_mds};
+pub use merge::merge_dir;
+pub use natural_sort::{NaturalOrd, natural_cmp};
+pub use options::{
+    DEFAULT_EXTENSIONS, DEFAULT_NFO_EXTENSIONS, DEFAULT_SUBDIR_LEAF_NAME, DEFAULT_SUBDIR_PREFIX,
+    EXTENSIONS_ENV, GenOptions, MAX_SUBDIR_DEPTH, extensions_from_env, parse_extension_list,
+    parse_since, validate_subdir_name, validate_subdir_prefix,
+};
+pub use orphans::{OrphanReport, delete_orphans, find_orphans};
+pub use plan::{Decision, DirPlan, plan_child_dir};
+pub use playlist::{
+    M3uWriter, Newline, PathSeparator, PlaylistEntry, PlaylistFormat, PlaylistWriter, PlsWriter,
+    UTF8_BOM, append_playlist, is_playlist_file, parse_playlist_entries, read_playlist_entries,
+    read_playlist_file, render_playlist,
+};
+pub use process::{process_child_dir, process_parent_dir};
+pub use profiles::{PROFILE_NAMES, profile_extensions, profile_with_extras};
+pub use progress::Progress;
+pub use rename::RenameStrategy;
+pub use report::{DirOutcome, DirectoryResult, ProcessReport, format_size};
+pub use script::{ScriptFormat, render_script};
+pub use simulate::{FsOp, check as check_simulated_error};
+#[cfg(any(debug_assertions, feature = "simulate-errors"))]
+pub use simulate::{SimulatedErrors, enable as enable_simulated_errors};
+pub use sort::{SortOrder, sort_by_extension_priority, sort_files};
+pub use stats::{DirStats, ExtensionStats, LibraryStats, stats_parent_dir};
+pub use template::{NameTemplate, TemplateVars};
+pub use transaction::{
+    Operation, default_transaction_log_path, read_transaction_log, undo_operations,
+    undo_transaction_log, write_transaction_log,
+};
+pub use unpack::unpack_parent_dir;
+pub use validate::{
+    BrokenEntry, ChecksumReport, ChecksumResult, ValidateReport, validate_parent_dir,
+    verify_checksums,
+};
+pub use watch::watch_parent_dir;
+
+/// What, if anything, lives at a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathStatus {
+    ExistsAsDirectory,
+    ExistsAsFile,
+    DoesNotExist,
+}
+
+/// Verify that a path exists and find out what it is
+pub fn verify_path(path: &Path) -> Result<PathStatus, GenError> {
+    match fs::metadata(path) {
+        Ok(meta) if meta.is_dir() => Ok(PathStatus::ExistsAsDirectory),
+        Ok(_) => Ok(PathStatus::ExistsAsFile),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(PathStatus::DoesNotExist),
+        Err(source) => Err(GenError::AccessFailed {
+            path: path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+/// Verify that `path` is a dir we can list, and when `write` is set that we can change it
+pub fn verify_dir(path: &Path, write: bool) -> Result<(), GenError> {
+    match verify_path(path)? {
+        PathStatus::ExistsAsDirectory => (),
+        PathStatus::ExistsAsFile => return Err(GenError::NotADirectory(path.to_path_buf())),
+        PathStatus::DoesNotExist => return Err(GenError::PathNotFound(path.to_path_buf())),
+    }
+    if !can_access(path, Access::Read) {
+        return Err(GenError::NotReadable(path.to_path_buf()));
+    }
+    if write && !can_access(path, Access::Write) {
+        return Err(GenError::NotWritable(path.to_path_buf()));
+    }
+    Ok(())
+}
+
+/// Get the name of the last chunk of a path
+pub fn get_path_dir_name(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => String::new(),
+    }
+}
+
+/// Combine the provided parts into a valid path
+pub fn build_path_from_parts(parts: &[&Path]) -> PathBuf {
+    let mut ret = PathBuf::new();
+    parts.iter().for_each(|part| ret.push(part));
+    ret
+}
+
+/// Drop trailing separators and doubled ones from a path the user typed, so `/games//` becomes
+/// `/games` and the paths built from it don't end up with `//` in them
+pub fn normalize_path(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+/// Check if a file has one of the given extensions, ignoring case
+pub fn has_extension<S: AsRef<str>>(path: &Path, extensions: &[S]) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => extensions
+            .iter()
+            .any(|allowed| allowed.as_ref().eq_ignore_ascii_case(ext)),
+        None => false,
+    }
+}
+
+/// Build the path that leads from the directory `from` to `to`. Both paths have to be absolute
+/// or both relative to the same place, otherwise `to` is returned unchanged.
+pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    if from.is_absolute() != to.is_absolute() {
+        return to.to_path_buf();
+    }
+    let from: Vec<Component> = from
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+    let to_parts: Vec<Component> = to
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+
+    // skip the part both paths share
+    let shared = from
+        .iter()
+        .zip(&to_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    // can't walk back out of a .. without knowing what it points at
+    if from[shared..].contains(&Component::ParentDir) {
+        return to.to_path_buf();
+    }
+
+    let mut ret = PathBuf::new();
+    from[shared..].iter().for_each(|_| ret.push(".."));
+    to_parts[shared..].iter().for_each(|part| ret.push(part));
+    ret
+}
+
+/// Write a file by writing a temp file next to it and renaming it into place, so a crash never
+/// leaves a half written file behind
+pub fn write_file_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(path.file_name().unwrap_or_default());
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = fs::File::create(&tmp_path).and_then(|mut f| {
+        simulate::check(FsOp::Write)?;
+        f.write_all(contents)?;
+        f.sync_all()
+    });
+    let result = result.and_then(|_| simulate::check(FsOp::Rename));
+    match result.and_then(|_| fs::rename(&tmp_path, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_path);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dir_name_is_the_last_part() {
+        assert_eq!(get_path_dir_name(Path::new("/games/Tekken 3")), "Tekken 3");
+        assert_eq!(get_path_dir_name(Path::new("/games/Tekken 3/")), "Tekken 3");
+        assert_eq!(get_path_dir_name(Path::new("Tekken 3")), "Tekken 3");
+        assert_eq!(
+            get_path_dir_name(Path::new("games/Tekken 3.chd")),
+            "Tekken 3.chd"
+        );
+        assert_eq!(get_path_dir_name(Path::new("/")), "");
+        assert_eq!(get_path_dir_name(Path::new("")), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dir_name_keeps_backslashes_on_unix() {
+        assert_eq!(
+            get_path_dir_name(Path::new("games\\Tekken 3")),
+            "games\\Tekken 3"
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn dir_name_splits_on_either_separator_on_windows() {
+        assert_eq!(
+            get_path_dir_name(Path::new(r"C:\games\Tekken 3")),
+            "Tekken 3"
+        );
+        assert_eq!(
+            get_path_dir_name(Path::new(r"C:\games/Tekken 3")),
+            "Tekken 3"
+        );
+        assert_eq!(
+            get_path_dir_name(Path::new(r"C:/games\Tekken 3\")),
+            "Tekken 3"
+        );
+        assert_eq!(
+            get_path_dir_name(Path::new(r"\\server\share\games\Tekken 3")),
+            "Tekken 3"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn builds_paths_on_unix() {
+        let parts = [
+            Path::new("/games"),
+            Path::new("Tekken 3"),
+            Path::new(".Tekken 3"),
+        ];
+        assert_eq!(
+            build_path_from_parts(&parts),
+            Path::new("/games/Tekken 3/.Tekken 3")
+        );
+        let parts = [Path::new("games/"), Path::new("Tekken 3")];
+        assert_eq!(
+            build_path_from_parts(&parts).to_str(),
+            Some("games/Tekken 3")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn builds_paths_on_windows() {
+        let parts = [
+            Path::new(r"C:\games"),
+            Path::new("Tekken 3"),
+            Path::new(".Tekken 3"),
+        ];
+        assert_eq!(
+            build_path_from_parts(&parts).to_str(),
+            Some(r"C:\games\Tekken 3\.Tekken 3")
+        );
+        let parts = [Path::new(r"\\server\share\games"), Path::new("Tekken 3")];
+        assert_eq!(
+            build_path_from_parts(&parts).to_str(),
+            Some(r"\\server\share\games\Tekken 3")
+        );
+    }
+
+    #[test]
+    fn builds_nothing_from_no_parts() {
+        assert_eq!(build_path_from_parts(&[]), PathBuf::new());
+    }
+
+    #[test]
+    fn matches_extensions_in_any_case() {
+        let allowed = ["chd", "cue", "bin"];
+        for name in [
+            "Resident Evil.CHD",
+            "Castlevania.Cue",
+            "Track01.BIN",
+            "Game.chd",
+        ] {
+            assert!(has_extension(Path::new(name), &allowed), "{name}");
+        }
+        for name in [
+            "artwork.PNG",
+            "README.TXT",
+            "chd",
+            ".cue",
+            "Game.chd.bak",
+            "Gamechd",
+        ] {
+            assert!(!has_extension(Path::new(name), &allowed), "{name}");
+        }
+    }
+
+    #[test]
+    fn default_options_want_data_files_in_any_case() {
+        let options = GenOptions::default();
+        assert!(options.wants_file(Path::new("Resident Evil.CHD")));
+        assert!(options.wants_file(Path::new("Castlevania.Cue")));
+        assert!(options.wants_file(Path::new("Track01.BIN")));
+        assert!(!options.wants_file(Path::new("artwork.PNG")));
+        assert!(!options.wants_file(Path::new("README.TXT")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn normalizes_trailing_and_doubled_separators() {
+        let cases = [
+            ("/games/", "/games"),
+            ("/games//", "/games"),
+            ("/games//Tekken 3/", "/games/Tekken 3"),
+            ("games/./", "games"),
+            ("/", "/"),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                normalize_path(Path::new(path)).to_str(),
+                Some(expected),
+                "{path}"
+            );
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn normalizes_trailing_and_doubled_separators() {
+        let cases = [
+            (r"C:\games\", r"C:\games"),
+            (r"C:\games\\Tekken 3/", r"C:\games\Tekken 3"),
+            (r"\\server\share\games\", r"\\server\share\games"),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                normalize_path(Path::new(path)).to_str(),
+                Some(expected),
+                "{path}"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn trailing_slash_builds_clean_paths() {
+        let parent = normalize_path(Path::new("/games/"));
+        let sub_dir =
+            build_path_from_parts(&[&parent, Path::new("Tekken 3"), Path::new(".Tekken 3")]);
+        assert_eq!(sub_dir.to_str(), Some("/games/Tekken 3/.Tekken 3"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn trailing_slash_parent_is_processed_cleanly() {
+        let tmp = testing::TempDir::new("trailing-slash");
+        tmp.write("Tekken 3/Tekken 3.chd", b"");
+        let parent = normalize_path(Path::new(&format!("{}//", tmp.path().display())));
+        let options = GenOptions {
+            auto_comment: false,
+            ..GenOptions::default()
+        };
+        let mut report = ProcessReport::default();
+        process_child_dir(&parent, &parent.join("Tekken 3"), &options, &mut report).unwrap();
+
+        assert!(tmp.path().join("Tekken 3/.Tekken 3/Tekken 3.chd").is_file());
+        assert!(!report.operations.is_empty());
+        for op in &report.operations {
+            let path = op.path().to_str().unwrap();
+            assert!(!path.contains("//"), "{path}");
+        }
+    }
+}
@@ -0,0 +1,66 @@
+// Description: Lock file that keeps two runs from working on the same parent dir at once.
+
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{GenError, build_path_from_parts};
+
+/// Name of the lock file created in the parent dir
+pub const LOCK_FILE_NAME: &str = ".m3ugen.lock";
+
+/// How often to try again while another run holds the lock
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An exclusive lock on a parent dir, released when dropped. The lock file itself is left in
+/// place, removing it would let a waiting run lock a file nobody else can see anymore.
+#[derive(Debug)]
+pub struct DirLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl DirLock {
+    /// Lock `parent`, waiting up to `timeout` for another run to finish with it
+    pub fn acquire(parent: &Path, timeout: Duration) -> Result<DirLock, GenError> {
+        let path = build_path_from_parts(&[parent, Path::new(LOCK_FILE_NAME)]);
+        let lock_err = |source| GenError::LockFailed {
+            path: path.clone(),
+            source,
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(lock_err)?;
+
+        let started = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(DirLock { file, path }),
+                Err(TryLockError::WouldBlock) if started.elapsed() < timeout => {
+                    thread::sleep(RETRY_INTERVAL);
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(GenError::AlreadyLocked(parent.to_path_buf()));
+                }
+                Err(TryLockError::Error(source)) => return Err(lock_err(source)),
+            }
+        }
+    }
+
+    /// Path of the lock file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
@@ -0,0 +1,109 @@
+// Description: Log levels for the output, picked with RUST_LOG, and the --log-file that gets
+//              every line (debug included) with a timestamp.
+
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::time::DateTime;
+
+/// How much to print, from nothing at all up to every debug line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl Level {
+    /// Parse a level name, `trace` is treated as `debug` since nothing logs below it
+    pub fn parse(s: &str) -> Option<Level> {
+        match s.trim().to_lowercase().as_str() {
+            "off" => Some(Level::Off),
+            "error" => Some(Level::Error),
+            "warn" | "warning" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" | "trace" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+
+    /// The level asked for in RUST_LOG. Like env_logger it takes a list of `level` and
+    /// `target=level` directives, where a directive for m3ugen beats a global one.
+    pub fn from_env() -> Option<Level> {
+        let value = env::var("RUST_LOG").ok()?;
+        let mut global = None;
+        let mut ours = None;
+        for directive in value.split(',') {
+            match directive.split_once('=') {
+                Some((target, level)) if target.trim().starts_with("m3ugen") => {
+                    ours = Level::parse(level).or(ours);
+                }
+                Some(_) => (),
+                None => global = Level::parse(directive).or(global),
+            }
+        }
+        ours.or(global)
+    }
+
+    /// Name written in the log file
+    pub fn label(&self) -> &'static str {
+        match self {
+            Level::Off => "OFF",
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+
+    /// The level of a line of output, going by its prefix
+    pub fn of_line(line: &str) -> Option<Level> {
+        match line.split(':').next().unwrap_or_default() {
+            "ERROR" | "BROKEN" | "FAIL" => Some(Level::Error),
+            "WARNING" => Some(Level::Warn),
+            "INFO" | "FIXED" | "OK" => Some(Level::Info),
+            "DEBUG" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+}
+
+/// A log file shared by every worker, each line is written whole
+#[derive(Debug, Clone)]
+pub struct LogFile {
+    file: Arc<Mutex<File>>,
+}
+
+impl LogFile {
+    /// Open `path` for appending, creating it if needed
+    pub fn open(path: &Path) -> io::Result<LogFile> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(LogFile {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// Write `line` as `<timestamp> <LEVEL> <line>`, dropping the prefix that repeats the level
+    pub fn write(&self, level: Level, line: &str) {
+        let message = ["ERROR: ", "WARNING: ", "INFO: ", "DEBUG: "]
+            .iter()
+            .find_map(|prefix| line.strip_prefix(prefix))
+            .unwrap_or(line);
+        let entry = format!(
+            "{} {:<5} {message}\n",
+            DateTime::now().iso_string(),
+            level.label()
+        );
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        // a log that can't be written shouldn't stop the run
+        let _ = file.write_all(entry.as_bytes());
+    }
+}
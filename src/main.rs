@@ -7,144 +7,711 @@
 // Date: 10/15/2025
 
 use std::{
-    env, ffi, fs,
-    io::{self, BufWriter, Write},
+    env, fs,
+    io::{self, BufRead},
+    path::Path,
+    process::ExitCode,
+    sync::mpsc,
+    time::Duration,
 };
 
-/// Verify that a path exists and is valid
-fn verify_path(path: &str) -> Result<bool, io::Error> {
-    match fs::exists(path) {
-        Ok(d) => Ok(d),
-        Err(e) => Err(e),
+use m3ugen::{
+    ColorChoice, DirLock, GenError, GenOptions, Level, LibraryStats, ProcessReport, RED,
+    ScriptFormat, TuiEvent, dedupe_parent_dir, delete_orphans, diff_parent_dirs,
+    extensions_from_env, find_orphans, merge_dir, normalize_path, paint, paint_line,
+    process_parent_dir, render_script, stats_parent_dir, undo_transaction_log, unpack_parent_dir,
+    validate_parent_dir, verify_checksums, verify_dir, watch_parent_dir,
+};
+
+mod cli;
+mod config;
+mod interactive;
+mod signal;
+mod tui;
+
+/// Turn an error into a message that tells the user what to do about it
+fn describe_error(e: &GenError) -> String {
+    let hint = match e {
+        GenError::PathNotFound(_) => "check the path for typos",
+        GenError::NotADirectory(_) => "pass the directory that holds your game folders",
+        GenError::NotReadable(_) => "check that you have read access to it",
+        GenError::NotWritable(_) => "check that you have write access to it, or try --dry-run",
+        GenError::InvalidName(_) => "rename it so it only uses valid UTF-8 characters",
+        GenError::InvalidPlaylistName { .. } => "change the --m3u-filename template",
+        GenError::InvalidTransactionLog { .. } => "pass a log written by m3ugen",
+        GenError::InsufficientSpace { .. } => "free up some disk space and try again",
+        GenError::EntryTooLong { .. } => {
+            "shorten the dir or file names, or leave out --absolute-paths"
+        }
+        GenError::AlreadyLocked(_) => "wait for it to finish, or raise --lock-timeout-ms",
+        GenError::RenameDirFailed { source, .. }
+            if source.kind() == io::ErrorKind::AlreadyExists =>
+        {
+            "rename or remove the dir that has the new name"
+        }
+        GenError::BackupFailed { source, .. } if source.kind() == io::ErrorKind::NotFound => {
+            "check the --backup path"
+        }
+        GenError::MoveFileFailed { source, .. }
+        | GenError::RenameDirFailed { source, .. }
+        | GenError::BackupFailed { source, .. }
+        | GenError::CreateSubdirFailed { source, .. }
+        | GenError::CreateM3uFailed { source, .. }
+        | GenError::WriteEntryFailed { source, .. }
+        | GenError::WriteLogFailed { source, .. }
+        | GenError::UndoFailed { source, .. }
+        | GenError::WatchFailed { source, .. }
+        | GenError::LockFailed { source, .. }
+        | GenError::CreateOutputDirFailed { source, .. }
+        | GenError::CreateParentFailed { source, .. }
+        | GenError::ReadDirFailed { source, .. }
+        | GenError::AccessFailed { source, .. } => match source.kind() {
+            io::ErrorKind::PermissionDenied => "check that you have write access to it",
+            io::ErrorKind::NotFound => "it was removed while m3ugen was running",
+            io::ErrorKind::StorageFull => "free up some disk space and try again",
+            io::ErrorKind::ReadOnlyFilesystem => "the drive is mounted read-only, try --no-move",
+            _ => return e.to_string(),
+        },
+    };
+    format!("{e} ({hint})")
+}
+
+/// Lock the parent dir so a second run can't work on it at the same time. A dry run changes
+/// nothing so it doesn't need one, and a lock that can't be created is only warned about.
+fn lock_parent(parent: &Path, options: &GenOptions) -> Result<Option<DirLock>, GenError> {
+    if options.dry_run || !parent.is_dir() {
+        return Ok(None);
+    }
+    match DirLock::acquire(parent, Duration::from_millis(options.lock_timeout_ms)) {
+        Ok(lock) => Ok(Some(lock)),
+        Err(e @ GenError::AlreadyLocked(_)) => Err(e),
+        Err(e) => {
+            options.warn(format!("WARNING: {e}, continuing without a lock"));
+            Ok(None)
+        }
+    }
+}
+
+/// Check the playlists in `parent` and print the broken entries. Fails when any are left broken.
+fn run_validate(parent: &Path, options: &GenOptions, fix: bool, strip_prepend: bool) -> ExitCode {
+    let _lock = match fix {
+        true => match lock_parent(parent, options) {
+            Ok(lock) => lock,
+            Err(e) => {
+                options.warn(format!("ERROR: {}", describe_error(&e)));
+                return ExitCode::from(2);
+            }
+        },
+        false => None,
+    };
+    let report = match validate_parent_dir(parent, options, fix, strip_prepend) {
+        Ok(r) => r,
+        Err(e) => {
+            options.warn(format!("ERROR: {}", describe_error(&e)));
+            return ExitCode::FAILURE;
+        }
+    };
+    for broken in &report.broken {
+        match &broken.fixed {
+            Some(fixed) => options.say(format!(
+                "FIXED: {} -> {fixed} in {}",
+                broken.entry,
+                broken.playlist.display()
+            )),
+            None => options.say(format!(
+                "BROKEN: {} in {} does not exist",
+                broken.entry,
+                broken.playlist.display()
+            )),
+        }
+    }
+    for e in &report.errors {
+        options.warn(format!("ERROR: {}", describe_error(e)));
+    }
+    let still_broken = report.broken.iter().filter(|b| b.fixed.is_none()).count();
+    options.say(format!(
+        "INFO: checked {} entries in {} playlists, {still_broken} broken",
+        report.entries_checked, report.playlists_checked
+    ));
+    match report.is_valid() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+/// Check the files in `parent` against their checksum files. Fails when any don't match.
+fn run_verify(parent: &Path, options: &GenOptions, verbose: bool) -> ExitCode {
+    let report = match verify_checksums(parent, options) {
+        Ok(r) => r,
+        Err(e) => {
+            options.warn(format!("ERROR: {}", describe_error(&e)));
+            return ExitCode::FAILURE;
+        }
+    };
+    if verbose {
+        for result in &report.results {
+            match (&result.actual, result.matches()) {
+                (_, true) => options.say(format!("OK: {}", result.file.display())),
+                (Some(actual), false) => options.say(format!(
+                    "FAIL: {}: {} != {actual}",
+                    result.file.display(),
+                    result.expected
+                )),
+                (None, false) => options.say(format!(
+                    "FAIL: {}: {} != (unreadable)",
+                    result.file.display(),
+                    result.expected
+                )),
+            }
+        }
+    }
+    for e in &report.errors {
+        options.warn(format!("ERROR: {}", describe_error(e)));
+    }
+    options.say(format!(
+        "INFO: verified {} files from {} checksum files, {} failed",
+        report.results.len(),
+        report.sidecars_checked,
+        report.failures().count()
+    ));
+    match report.is_valid() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
     }
 }
 
-/// Get the name of the last chunk of a path
-fn get_path_dir_name(path: &str) -> String {
-    let dir: Vec<&str> = path
-        .split(match env::consts::OS {
-            "windows" => "\\",
-            _ => "/",
-        })
-        .collect();
-    dir.last().unwrap().to_string()
+/// Print what every child dir of the parents holds, as one table or as JSON
+fn run_stats(parents: &[(&Path, GenOptions)], options: &GenOptions, json: bool) -> ExitCode {
+    let mut stats = LibraryStats::default();
+    for (parent, parent_options) in parents {
+        match stats_parent_dir(parent, parent_options) {
+            Ok(s) => {
+                stats.dirs.extend(s.dirs);
+                stats.errors.extend(s.errors);
+            }
+            Err(e) => stats.errors.push(e),
+        }
+    }
+    match json {
+        true => println!("{}", stats.to_json().to_pretty()),
+        false => {
+            for e in &stats.errors {
+                options.warn(format!("ERROR: {}", describe_error(e)));
+            }
+            print!("{}", stats.table());
+        }
+    }
+    match stats.errors.is_empty() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
 }
 
-/// Combine the provided string into a valid path format
-fn build_path_from_parts(parts: &Vec<&str>) -> ffi::OsString {
-    let mut ret = ffi::OsString::new();
-    let ret_size = parts.iter().fold(0, |acc, s| acc + s.len());
-    ret.reserve(ret_size);
+/// Drop the duplicate entries from the playlists of one parent dir and report how many went
+fn run_dedupe(parent: &Path, options: &GenOptions, in_place: bool) -> ExitCode {
+    let _lock = match lock_parent(parent, options) {
+        Ok(lock) => lock,
+        Err(e) => {
+            options.warn(format!("ERROR: {}", describe_error(&e)));
+            return ExitCode::from(2);
+        }
+    };
+    let report = match dedupe_parent_dir(parent, options, in_place) {
+        Ok(r) => r,
+        Err(e) => {
+            options.warn(format!("ERROR: {}", describe_error(&e)));
+            return ExitCode::FAILURE;
+        }
+    };
+    for deduped in &report.deduped {
+        let target = match deduped.written == deduped.playlist {
+            true => String::new(),
+            false => format!(" into {}", deduped.written.display()),
+        };
+        options.say(match options.dry_run {
+            true => format!(
+                "WOULD DEDUPE: {} duplicate entries from {}{target}",
+                deduped.removed,
+                deduped.playlist.display()
+            ),
+            false => format!(
+                "FIXED: removed {} duplicate entries from {}{target}",
+                deduped.removed,
+                deduped.playlist.display()
+            ),
+        });
+    }
+    for e in &report.errors {
+        options.warn(format!("ERROR: {}", describe_error(e)));
+    }
+    options.say(format!(
+        "INFO: checked {} playlists, {} duplicate entries in {} of them",
+        report.playlists_checked,
+        report.removed(),
+        report.deduped.len()
+    ));
+    match report.errors.is_empty() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
 
-    let total = parts.len();
-    let mut count = 0;
-    parts.iter().for_each(|part| {
-        count += 1;
+/// List the files in the hidden sub-dirs that no playlist references, removing them with
+/// `delete` once the user agrees (or right away with `yes`)
+fn run_find_orphans(parent: &Path, options: &GenOptions, delete: bool, yes: bool) -> ExitCode {
+    let _lock = match delete {
+        true => match lock_parent(parent, options) {
+            Ok(lock) => lock,
+            Err(e) => {
+                options.warn(format!("ERROR: {}", describe_error(&e)));
+                return ExitCode::from(2);
+            }
+        },
+        false => None,
+    };
+    let report = match find_orphans(parent, options) {
+        Ok(r) => r,
+        Err(e) => {
+            options.warn(format!("ERROR: {}", describe_error(&e)));
+            return ExitCode::FAILURE;
+        }
+    };
+    for orphan in &report.orphans {
+        options.say(format!("ORPHAN: {}", orphan.display()));
+    }
+    let mut errors = report.errors;
+    options.say(format!(
+        "INFO: checked {} sub-dirs, {} files no playlist references",
+        report.sub_dirs_checked,
+        report.orphans.len()
+    ));
+    // like validate, orphans that are left in place fail the run
+    let mut left = !report.orphans.is_empty();
+    if delete && left {
+        match yes || options.dry_run || interactive::confirm_delete(report.orphans.len()) {
+            true => {
+                errors.extend(delete_orphans(&report.orphans, options));
+                left = options.dry_run;
+            }
+            false => options.say("INFO: left the orphaned files alone".to_string()),
+        }
+    }
+    for e in &errors {
+        options.warn(format!("ERROR: {}", describe_error(e)));
+    }
+    match errors.is_empty() && !left {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
 
-        ret.push(part);
+/// Print how the second parent differs from the first, as diff -u style text or as JSON
+fn run_diff(left: &Path, right: &Path, options: &GenOptions, json: bool) -> ExitCode {
+    let diff = match diff_parent_dirs(left, right, options) {
+        Ok(diff) => diff,
+        Err(e) => {
+            options.warn(format!("ERROR: {}", describe_error(&e)));
+            return ExitCode::FAILURE;
+        }
+    };
+    match json {
+        true => println!("{}", diff.to_json().to_pretty()),
+        false => {
+            for e in &diff.errors {
+                options.warn(format!("ERROR: {}", describe_error(e)));
+            }
+            print!("{}", diff.text(options.color.stdout()));
+        }
+    }
+    match diff.is_same() && diff.errors.is_empty() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
 
-        // never put a trailing / or \
-        if count != total {
-            ret.push(match env::consts::OS {
-                "windows" => "\\",
-                _ => "/",
-            });
+/// Run `run` on every parent, returning the exit code of the first one that failed
+fn run_each(
+    parents: &[(&Path, GenOptions)],
+    run: impl Fn(&Path, &GenOptions) -> ExitCode,
+) -> ExitCode {
+    let mut ret = ExitCode::SUCCESS;
+    for (parent, options) in parents {
+        let code = run(parent, options);
+        if ret == ExitCode::SUCCESS {
+            ret = code;
         }
-    });
+    }
     ret
 }
 
-// TODO skip directories that already have a sub directory containing a .m3u file OR only have one chd/set of bin/cue files
-// TODO create a log file that contains any directories that have both chd and bin/cue files
-// TODO integrate ratatui to create an optional interface (by passing --tui maybe?)
-fn main() -> io::Result<()> {
+/// Process (or unpack) one parent dir, with the full screen view when `use_tui` is set
+fn run_parent(
+    parent: &Path,
+    options: &GenOptions,
+    command: cli::Command,
+    use_tui: bool,
+    keep_originals: bool,
+) -> Result<ProcessReport, GenError> {
+    match command {
+        cli::Command::Unpack => unpack_parent_dir(parent, options),
+        cli::Command::Merge => merge_dir(parent, options, keep_originals),
+        _ if use_tui && tui::is_available() => tui::run(parent, options.clone()),
+        _ => {
+            if use_tui {
+                options.warn("WARNING: --tui needs a terminal, running without it".to_string());
+            }
+            process_parent_dir(parent, options)
+        }
+    }
+}
+
+/// Dry run every parent and write a script to `path` that makes the changes the run would have
+/// made. The changes are printed as well with --dry-run.
+fn run_script(
+    parents: &[(&Path, GenOptions)],
+    options: &GenOptions,
+    command: cli::Command,
+    keep_originals: bool,
+    path: &Path,
+    format: ScriptFormat,
+) -> ExitCode {
+    let mut lines: Vec<String> = Vec::new();
+    let mut failed = false;
+    for (parent, parent_options) in parents {
+        // absolute paths, so the script can be run from anywhere
+        let parent = std::path::absolute(parent).unwrap_or_else(|_| parent.to_path_buf());
+        let (sender, events) = mpsc::channel();
+        let script_options = GenOptions {
+            dry_run: true,
+            progress: false,
+            jobs: 1,
+            events: Some(sender),
+            log: None,
+            ..parent_options.clone()
+        };
+        let result = run_parent(&parent, &script_options, command, false, keep_originals);
+        drop(script_options);
+        lines.extend(events.into_iter().filter_map(|event| match event {
+            TuiEvent::Log(line) | TuiEvent::Warning(line) => Some(line),
+            _ => None,
+        }));
+        let errors = match result {
+            Ok(report) => report.errors,
+            Err(e) => vec![e],
+        };
+        for e in errors {
+            let line = format!("ERROR: {}", describe_error(&e));
+            options.warn(line.clone());
+            lines.push(line);
+            failed = true;
+        }
+    }
+    if options.dry_run {
+        for line in &lines {
+            options.say(line.clone());
+        }
+    }
+
+    let script = render_script(&lines, format, options.newline, options.digest_algorithm);
+    if let Err(e) = write_script(path, &script, format) {
+        options.warn(format!(
+            "ERROR: unable to write the script {}: {e}",
+            path.display()
+        ));
+        return ExitCode::FAILURE;
+    }
+    options.say(format!("INFO: wrote the script to {}", path.display()));
+    match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
+
+/// Write the script, executable when it's a sh script on a unix system
+fn write_script(path: &Path, script: &str, format: ScriptFormat) -> io::Result<()> {
+    fs::write(path, script)?;
+    #[cfg(unix)]
+    if format == ScriptFormat::Sh {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    #[cfg(not(unix))]
+    let _ = format;
+    Ok(())
+}
+
+/// Process every parent dir listed on stdin as it's read. A parent that fails is added to the
+/// report's errors and the next one is processed anyway.
+fn run_stdin(options: &GenOptions, command: cli::Command, keep_originals: bool) -> ProcessReport {
+    let mut report = ProcessReport::default();
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                options.warn(format!("ERROR: unable to read stdin: {e}"));
+                break;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parent = normalize_path(Path::new(line));
+        let parent = parent.as_path();
+        let mut parent_options = options.clone();
+        if let Err(e) = parent_options.load_ignore_file(parent) {
+            options.warn(format!("ERROR: invalid ignore file {e}"));
+            continue;
+        }
+        let processed = verify_dir(parent, false)
+            .and_then(|_| lock_parent(parent, &parent_options))
+            .and_then(|_lock| run_parent(parent, &parent_options, command, false, keep_originals));
+        match processed {
+            Ok(r) => report.merge(r),
+            Err(e) => report.errors.push(e),
+        }
+    }
+    report
+}
+
+/// Print an error from before the flags are parsed, so --color isn't known yet
+fn early_error(message: String) {
+    eprintln!("{}", paint_line(&message, ColorChoice::Auto.stderr()));
+}
+
+fn main() -> ExitCode {
     // step 1: get input from the user
     let args: Vec<String> = env::args().collect();
 
-    // step 2a: set the parent directory
-    let path_to_parent: &str = &args[1];
-
-    // step 3a: verify that the path is valid
-    let _ = verify_path(path_to_parent).or_else(|e| Err(e));
-
-    // step 3b: get a list of child dirs in the parent dir
-    let child_dirs = fs::read_dir(path_to_parent)?;
-
-    // step 5a: write the path (sub-dir/file_name) to a .m3u file and move the files into the
-    //         sub-dir
-    child_dirs.for_each(|dir| {
-        let curr = dir.unwrap();
-        let curr_name = get_path_dir_name(curr.path().to_str().unwrap());
-
-        // Create a hidden subdirectory string
-        let mut hidden_name: String = String::new();
-        hidden_name.push_str(".");
-        hidden_name.push_str(get_path_dir_name(curr.path().to_str().unwrap()).as_str());
-
-        // step 5b: make a path to the sub_directory
-        let sub_dir = build_path_from_parts(&vec![path_to_parent, &curr_name, &hidden_name]);
-
-        // step 5c: verify the parent dir still exists
-        let _ = verify_path(path_to_parent).or_else(|e| Err(e));
-
-        // step 5d: create a sub-dir for this file (if it doesn't already exist)
-        let _ = fs::create_dir(&sub_dir).or_else(|e| Err(e));
-
-        // Build path to the output .m3u file
-        let mut file_name = String::new();
-        file_name.push_str(curr_name.as_str());
-        file_name.push_str(".m3u");
-
-        // Create the output file
-        let outfile = fs::File::create(build_path_from_parts(&vec![
-            &path_to_parent,
-            curr_name.as_str(),
-            file_name.as_str(),
-        ]))
-        .unwrap();
-
-        // step 5f: move the .cue or .chd files to the sub_dir and write to our .m3u file
-        let _ = verify_path(sub_dir.to_str().unwrap()).or_else(|e| Err(e));
-        let files = fs::read_dir(&curr.path()).unwrap();
-
-        // loop through the files in the current directory
-        for file in files {
-            let curr_file = file.unwrap().path().to_str().unwrap().to_string();
-            let curr_file_name = get_path_dir_name(&curr_file);
-
-            // Skip the file if it's anything other than our data files
-            if !["chd", "cue", "bin"]
-                .iter()
-                .any(|ext| curr_file.ends_with(ext))
-            {
-                continue;
+    // step 2a: load the config file, --config has to exist but the default location doesn't.
+    // Extensions from the environment stand in for the defaults underneath both.
+    let mut options = GenOptions::default();
+    match extensions_from_env() {
+        Ok(Some(extensions)) => options.extensions = extensions,
+        Ok(None) => (),
+        Err(e) => {
+            early_error(format!("ERROR: {e}"));
+            return ExitCode::from(2);
+        }
+    }
+    let config_path = match cli::find_config_flag(&args[1..]) {
+        Ok(Some(path)) => Some(path),
+        Ok(None) => config::default_config_path().filter(|path| path.is_file()),
+        Err(e) => {
+            early_error(format!("ERROR: {e}"));
+            return ExitCode::from(2);
+        }
+    };
+    if let Some(path) = config_path {
+        match config::load_config(&path) {
+            Ok(config) => config.apply(&mut options),
+            Err(e) => {
+                early_error(format!("ERROR: invalid config file {e}"));
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    // step 2b: parse flags before touching the filesystem, they override the config file
+    let cli = match cli::parse_args(&args[1..], options) {
+        Ok(c) => c,
+        Err(e) => {
+            early_error(format!("ERROR: {e}"));
+            return ExitCode::from(2);
+        }
+    };
+    if cli.help {
+        print!("{}", cli::help_text());
+        return ExitCode::SUCCESS;
+    }
+    if cli.version {
+        println!("{}", cli::version_string());
+        return ExitCode::SUCCESS;
+    }
+    let mut options = cli.options;
+    options.log_level = Level::from_env().unwrap_or_default();
+    if let Err(e) = options.open_log_file() {
+        options.warn(format!("ERROR: unable to open the log file {e}"));
+        return ExitCode::from(2);
+    }
+    if let Err(e) = options.open_report_csv() {
+        options.warn(format!("ERROR: unable to create the CSV report {e}"));
+        return ExitCode::from(2);
+    }
+    if let Err(e) = options.load_template_m3u() {
+        options.warn(format!("ERROR: unable to read the template {e}"));
+        return ExitCode::from(2);
+    }
+    if cli.interactive {
+        options.confirm = Some(interactive::confirm);
+    }
+
+    // step 2c: check every parent before any of them is touched, and load the ignore file that
+    // applies to each
+    let mut parents: Vec<(&Path, GenOptions)> = Vec::new();
+    for parent in &cli.parents {
+        let missing = !parent.exists() && cli.command == cli::Command::Generate;
+        if missing && !options.create_parent && cli.undo.is_none() {
+            options.warn(format!(
+                "ERROR: parent dir '{}' does not exist, use --create-parent to create it",
+                parent.display()
+            ));
+            return ExitCode::FAILURE;
+        }
+        if cli.undo.is_none()
+            && !(missing && options.create_parent)
+            && let Err(e) = verify_dir(parent, false)
+        {
+            options.warn(format!("ERROR: {}", describe_error(&e)));
+            return ExitCode::FAILURE;
+        }
+        let mut parent_options = options.clone();
+        if let Err(e) = parent_options.load_ignore_file(parent) {
+            options.warn(format!("ERROR: invalid ignore file {e}"));
+            return ExitCode::from(2);
+        }
+        parents.push((parent.as_path(), parent_options));
+    }
+
+    // the validate subcommand only looks at the playlists (unless fixing them)
+    if cli.command == cli::Command::Validate {
+        return run_each(&parents, |parent, options| {
+            let validated = run_validate(parent, options, cli.fix, cli.strip_prepend);
+            match cli.verify_checksums {
+                true => match run_verify(parent, options, cli.verbose) {
+                    ExitCode::SUCCESS => validated,
+                    failed => failed,
+                },
+                false => validated,
             }
+        });
+    }
+    if cli.command == cli::Command::Stats {
+        return run_stats(&parents, &options, cli.json);
+    }
+    if cli.command == cli::Command::Dedupe {
+        let in_place = cli.in_place.unwrap_or(true);
+        return run_each(&parents, |parent, options| {
+            run_dedupe(parent, options, in_place)
+        });
+    }
+    if cli.command == cli::Command::FindOrphans {
+        return run_each(&parents, |parent, options| {
+            run_find_orphans(parent, options, cli.delete_orphans, cli.yes)
+        });
+    }
+    if let Some(path) = &cli.script_output {
+        return run_script(
+            &parents,
+            &options,
+            cli.command,
+            cli.keep_originals,
+            path,
+            cli.script_format,
+        );
+    }
+    if cli.command == cli::Command::Diff {
+        return run_diff(&cli.parents[0], &cli.parents[1], &options, cli.json);
+    }
+    if cli.verify_checksums {
+        return run_each(&parents, |parent, options| {
+            run_verify(parent, options, cli.verbose)
+        });
+    }
 
-            // build the path that we want to move our data files to
-            let new_file = build_path_from_parts(&vec![
-                path_to_parent,
-                curr_name.as_str(),
-                hidden_name.as_str(),
-                get_path_dir_name(curr_file.as_str()).as_str(),
-            ]);
-
-            // write to m3u_file
-            let file_m3u_line =
-                build_path_from_parts(&vec![hidden_name.as_str(), curr_file_name.as_str()]);
-            let mut buf = BufWriter::new(&outfile);
-            let _ = buf.write(file_m3u_line.to_str().unwrap().as_bytes());
-            let _ = buf.write(b"\n");
-
-            // move file
-            match fs::rename(&curr_file, &new_file) {
-                Ok(_) => (),
-                Err(e) => println!(
-                    "ERROR ({e}): Unable to move {} to {}",
-                    curr_file,
-                    new_file.to_str().unwrap()
-                ),
+    // step 2d: make sure nobody else is working on the parents, held until the run (and any
+    // watching) is done
+    let mut _locks: Vec<DirLock> = Vec::new();
+    if cli.undo.is_none() {
+        for (parent, parent_options) in &parents {
+            match lock_parent(parent, parent_options) {
+                Ok(lock) => _locks.extend(lock),
+                Err(e) => {
+                    options.warn(format!("ERROR: {}", describe_error(&e)));
+                    return ExitCode::from(2);
+                }
             }
         }
-    });
-    Ok(())
+    }
+
+    // step 2e: go through the parents one after the other, unless we're rolling back an earlier
+    // run
+    let result: Result<ProcessReport, GenError> = match &cli.undo {
+        Some(log) => undo_transaction_log(log, &options),
+        None if cli.stdin => Ok(run_stdin(&options, cli.command, cli.keep_originals)),
+        None => {
+            let mut report = ProcessReport::default();
+            let mut result = Ok(());
+            for (path_to_parent, parent_options) in &parents {
+                // step 3: process every child dir of the parent (or take it apart again)
+                match run_parent(
+                    path_to_parent,
+                    parent_options,
+                    cli.command,
+                    cli.tui,
+                    cli.keep_originals,
+                ) {
+                    Ok(r) => report.merge(r),
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                }
+            }
+            result.map(|_| report)
+        }
+    };
+    let report = match result {
+        Ok(r) => r,
+        Err(e) => {
+            options.warn(format!("ERROR: {}", describe_error(&e)));
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // step 4: report anything that went wrong
+    if let Some(format) = cli.summary {
+        let summary = match format {
+            cli::SummaryFormat::Text => report.summary(),
+            cli::SummaryFormat::Json => format!("{}\n", report.summary_json().to_pretty()),
+        };
+        match &cli.summary_file {
+            Some(path) => {
+                if let Err(e) = fs::write(path, summary) {
+                    options.warn(format!("WARNING: unable to write {}: {e}", path.display()));
+                }
+            }
+            None => print!("{summary}"),
+        }
+    }
+    let failed = !report.errors.is_empty();
+    if failed {
+        let header = format!("{} error(s) occurred:", report.errors.len());
+        match options.color.stderr() {
+            true => eprintln!("{}", paint(&header, RED)),
+            false => eprintln!("{header}"),
+        }
+        for e in &report.errors {
+            let message = describe_error(e);
+            eprintln!("  {message}");
+            if let Some(log) = &options.log {
+                log.write(Level::Error, &message);
+            }
+        }
+    }
+
+    // step 5: keep going with any child dirs added from now on
+    if options.watch {
+        let stop = signal::stop_flag();
+        let (parent, parent_options) = &parents[0];
+        if let Err(e) = watch_parent_dir(parent, parent_options, stop) {
+            options.warn(format!("ERROR: {}", describe_error(&e)));
+            return ExitCode::FAILURE;
+        }
+    }
+    match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
 }
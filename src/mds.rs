@@ -0,0 +1,209 @@
+// Description: Read the data file names out of Alcohol 120% .mds descriptors, so the .mdf
+//              images they describe get moved along with them.
+
+use std::{
+    error, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Every .mds starts with this
+const SIGNATURE: &[u8] = b"MEDIA DESCRIPTOR";
+
+/// Size of a session block and of a track block
+const SESSION_BLOCK_SIZE: usize = 24;
+const TRACK_BLOCK_SIZE: usize = 80;
+
+/// Why a .mds descriptor couldn't be read
+#[derive(Debug)]
+pub enum MdsParseError {
+    /// The file couldn't be read from disk
+    Io(io::Error),
+    /// The file doesn't start with the .mds signature
+    NotMds,
+    /// A block points past the end of the file
+    Truncated { offset: usize },
+}
+
+impl fmt::Display for MdsParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MdsParseError::Io(e) => write!(f, "{e}"),
+            MdsParseError::NotMds => write!(f, "not a media descriptor file"),
+            MdsParseError::Truncated { offset } => {
+                write!(f, "truncated descriptor (nothing at offset {offset:#x})")
+            }
+        }
+    }
+}
+
+impl error::Error for MdsParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            MdsParseError::Io(e) => Some(e),
+            MdsParseError::NotMds | MdsParseError::Truncated { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for MdsParseError {
+    fn from(e: io::Error) -> Self {
+        MdsParseError::Io(e)
+    }
+}
+
+/// Little endian reads that fail instead of panicking on a short file
+fn read_u8(data: &[u8], offset: usize) -> Result<u8, MdsParseError> {
+    data.get(offset)
+        .copied()
+        .ok_or(MdsParseError::Truncated { offset })
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, MdsParseError> {
+    match data.get(offset..offset + 2) {
+        Some(b) => Ok(u16::from_le_bytes([b[0], b[1]])),
+        None => Err(MdsParseError::Truncated { offset }),
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<usize, MdsParseError> {
+    match data.get(offset..offset + 4) {
+        Some(b) => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize),
+        None => Err(MdsParseError::Truncated { offset }),
+    }
+}
+
+/// Read the nul terminated file name at `offset`, UTF-16 when `wide` and ASCII otherwise
+fn read_name(data: &[u8], offset: usize, wide: bool) -> Result<String, MdsParseError> {
+    let rest = data
+        .get(offset..)
+        .ok_or(MdsParseError::Truncated { offset })?;
+    let name = match wide {
+        true => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                .take_while(|&u| u != 0)
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        false => {
+            let end = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+            String::from_utf8_lossy(&rest[..end]).to_string()
+        }
+    };
+    Ok(name)
+}
+
+/// Read the data files a .mds descriptor points at. Descriptors usually say `*.mdf`, which means
+/// the .mdf with the same name as the .mds.
+pub fn parse_mds(path: &Path) -> Result<Vec<PathBuf>, MdsParseError> {
+    let data = fs::read(path)?;
+    if !data.starts_with(SIGNATURE) {
+        return Err(MdsParseError::NotMds);
+    }
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut ret: Vec<PathBuf> = Vec::new();
+    let sessions = read_u16(&data, 0x14)? as usize;
+    let sessions_offset = read_u32(&data, 0x50)?;
+    for session in 0..sessions {
+        let session_block = sessions_offset + session * SESSION_BLOCK_SIZE;
+        let blocks = read_u8(&data, session_block + 0x0a)? as usize;
+        let tracks_offset = read_u32(&data, session_block + 0x14)?;
+        for block in 0..blocks {
+            let track_block = tracks_offset + block * TRACK_BLOCK_SIZE;
+            // points above 99 are lead-in entries, not tracks
+            let point = read_u8(&data, track_block + 0x04)?;
+            if !(1..=99).contains(&point) {
+                continue;
+            }
+            let names = read_u32(&data, track_block + 0x30)?;
+            let names_offset = read_u32(&data, track_block + 0x34)?;
+            for n in 0..names {
+                let name_block = names_offset + n * 16;
+                let name_offset = read_u32(&data, name_block)?;
+                let wide = read_u8(&data, name_block + 4)? != 0;
+                let name = read_name(&data, name_offset, wide)?.replace('*', &stem);
+                let name = PathBuf::from(name);
+                if !name.as_os_str().is_empty() && !ret.contains(&name) {
+                    ret.push(name);
+                }
+            }
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    /// A one session descriptor with a lead-in block and one track whose file name is `name`,
+    /// stored with the given widechar flag
+    fn descriptor(name: &[u8], widechar: u8) -> Vec<u8> {
+        let put_u32 = |data: &mut Vec<u8>, offset: usize, value: usize| {
+            data[offset..offset + 4].copy_from_slice(&(value as u32).to_le_bytes());
+        };
+        let sessions_offset = 0x58;
+        let tracks_offset = sessions_offset + SESSION_BLOCK_SIZE;
+        let names_offset = tracks_offset + 2 * TRACK_BLOCK_SIZE;
+        let name_offset = names_offset + 16;
+
+        let mut data = vec![0u8; name_offset];
+        data[..SIGNATURE.len()].copy_from_slice(SIGNATURE);
+        data[0x14] = 1;
+        put_u32(&mut data, 0x50, sessions_offset);
+        data[sessions_offset + 0x0a] = 2;
+        put_u32(&mut data, sessions_offset + 0x14, tracks_offset);
+        for (block, point) in [0xa0, 1].into_iter().enumerate() {
+            let track_block = tracks_offset + block * TRACK_BLOCK_SIZE;
+            data[track_block + 0x04] = point;
+            put_u32(&mut data, track_block + 0x30, 1);
+            put_u32(&mut data, track_block + 0x34, names_offset);
+        }
+        put_u32(&mut data, names_offset, name_offset);
+        data[names_offset + 4] = widechar;
+        data.extend_from_slice(name);
+        data
+    }
+
+    #[test]
+    fn reads_8_bit_names() {
+        let tmp = TempDir::new("mds-narrow");
+        let mds = tmp.write("Game.mds", &descriptor(b"*.mdf\0", 0));
+        assert_eq!(parse_mds(&mds).unwrap(), [PathBuf::from("Game.mdf")]);
+    }
+
+    #[test]
+    fn reads_wide_names() {
+        let tmp = TempDir::new("mds-wide");
+        let name: Vec<u8> = "Gamé (Track 1).mdf\0"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let mds = tmp.write("Game.mds", &descriptor(&name, 1));
+        assert_eq!(
+            parse_mds(&mds).unwrap(),
+            [PathBuf::from("Gamé (Track 1).mdf")]
+        );
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let tmp = TempDir::new("mds-invalid");
+        let other = tmp.write("Game.mds", b"FILE \"Game.bin\" BINARY\n");
+        assert!(matches!(parse_mds(&other), Err(MdsParseError::NotMds)));
+
+        let mut data = descriptor(b"*.mdf\0", 0);
+        data.truncate(0x60);
+        let short = tmp.write("Short.mds", &data);
+        assert!(matches!(
+            parse_mds(&short),
+            Err(MdsParseError::Truncated { .. })
+        ));
+    }
+}
@@ -0,0 +1,182 @@
+// Description: The merge subcommand: fold the playlists a user wrote by hand for one game dir into
+//              a single <dir name>.m3u, listing every entry once.
+
+use std::{
+    cmp::Ordering,
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crate::{
+    GenError, GenOptions, Newline, Operation, PlaylistEntry, PlaylistFormat, ProcessReport,
+    build_path_from_parts, default_transaction_log_path, disc_number, get_path_dir_name,
+    has_extension, natural_cmp, parse_playlist_entries, process::write_run_logs, render_playlist,
+    verify_dir, write_file_atomic,
+};
+
+/// Order the merged entries by disc when every one of them has an #EXTINF title, by path
+/// otherwise
+fn merge_order(entries: &mut [PlaylistEntry]) {
+    if entries.iter().all(|e| !e.title.is_empty()) {
+        entries.sort_by(|a, b| {
+            match (disc_number(&a.title), disc_number(&b.title)) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+            .then_with(|| natural_cmp(&a.path, &b.path))
+        });
+    } else {
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+}
+
+/// Merge the .m3u and .m3u8 playlists in `dir` into `<dir name>.m3u`, then remove the merged ones
+/// unless `keep_originals` is set. Entries are written the way they were, relative or absolute.
+pub fn merge_dir(
+    dir: &Path,
+    options: &GenOptions,
+    keep_originals: bool,
+) -> Result<ProcessReport, GenError> {
+    verify_dir(dir, !options.dry_run)?;
+    let started = Instant::now();
+    let mut report = ProcessReport {
+        dirs_scanned: 1,
+        ..ProcessReport::default()
+    };
+    let merged_path =
+        build_path_from_parts(&[dir, Path::new(&format!("{}.m3u", get_path_dir_name(dir)))]);
+
+    let files = fs::read_dir(dir).map_err(|source| GenError::ReadDirFailed {
+        path: dir.to_path_buf(),
+        source,
+    })?;
+    let mut playlists: Vec<PathBuf> = files
+        .filter_map(Result::ok)
+        .map(|f| f.path())
+        .filter(|f| f.is_file() && has_extension(f, &["m3u", "m3u8"]))
+        .collect();
+    playlists.sort();
+    if playlists.is_empty() || playlists == [merged_path.clone()] {
+        options.say(format!(
+            "INFO: nothing to merge in {}, it doesn't have more than its own playlist",
+            dir.display()
+        ));
+        return Ok(report);
+    }
+
+    // the first playlist to list a path wins, a later one can only add the title it was missing
+    let mut entries: Vec<PlaylistEntry> = Vec::new();
+    let mut originals: Vec<(PathBuf, String)> = Vec::new();
+    let mut newline = None;
+    for playlist in &playlists {
+        let contents = fs::read(playlist).map_err(|source| GenError::AccessFailed {
+            path: playlist.clone(),
+            source,
+        })?;
+        newline = newline.or_else(|| Newline::detect(&contents));
+        for entry in parse_playlist_entries(&contents, PlaylistFormat::M3u) {
+            match entries.iter_mut().find(|e| e.path == entry.path) {
+                Some(seen) if seen.title.is_empty() => seen.title = entry.title,
+                Some(_) => (),
+                None => entries.push(entry),
+            }
+        }
+        originals.push((
+            playlist.clone(),
+            String::from_utf8_lossy(&contents).to_string(),
+        ));
+    }
+    merge_order(&mut entries);
+
+    // titles are only worth keeping as #EXTINF lines if the playlists had them
+    let mut merge_options = options.clone();
+    merge_options.format = PlaylistFormat::M3u;
+    merge_options.extended = options.extended || entries.iter().any(|e| !e.title.is_empty());
+    if merge_options.extended {
+        for entry in entries.iter_mut().filter(|e| e.title.is_empty()) {
+            entry.title = Path::new(&entry.path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+        }
+    }
+    let contents = render_playlist(&entries, &merge_options, newline.unwrap_or(options.newline))
+        .map_err(|source| GenError::WriteEntryFailed {
+            path: merged_path.clone(),
+            source,
+        })?;
+    report.playlists.push(merged_path.clone());
+
+    let original = originals
+        .iter()
+        .find(|(path, _)| *path == merged_path)
+        .map(|(_, contents)| contents.clone());
+    if options.dry_run {
+        if original.is_none() {
+            options.say(format!("WOULD CREATE FILE: {}", merged_path.display()));
+        }
+        String::from_utf8_lossy(&contents).lines().for_each(|line| {
+            options.say(format!("WOULD WRITE: {line} >> {}", merged_path.display()))
+        });
+    } else {
+        write_file_atomic(&merged_path, &contents).map_err(|source| {
+            GenError::WriteEntryFailed {
+                path: merged_path.clone(),
+                source,
+            }
+        })?;
+        report.operations.push(match original {
+            Some(original) => Operation::Rewrite {
+                path: merged_path.clone(),
+                original,
+            },
+            None => Operation::CreateFile {
+                path: merged_path.clone(),
+                existed: false,
+            },
+        });
+    }
+
+    if !keep_originals {
+        for (playlist, contents) in originals {
+            if playlist == merged_path {
+                continue;
+            }
+            if options.dry_run {
+                options.say(format!("WOULD REMOVE FILE: {}", playlist.display()));
+                continue;
+            }
+            match fs::remove_file(&playlist) {
+                Ok(()) => report.operations.push(Operation::RemoveFile {
+                    path: playlist,
+                    contents,
+                }),
+                Err(source) => report.errors.push(GenError::AccessFailed {
+                    path: playlist,
+                    source,
+                }),
+            }
+        }
+    }
+    options.say(format!(
+        "INFO: merged {} playlists into {} ({} entries)",
+        playlists.len(),
+        merged_path.display(),
+        entries.len()
+    ));
+
+    report.dirs_processed = 1;
+    report.elapsed = started.elapsed();
+    if let Some(csv) = &options.csv {
+        csv.record(&report);
+    }
+    let log_path = match &options.transaction_log {
+        Some(path) => path.clone(),
+        None => default_transaction_log_path(dir),
+    };
+    write_run_logs(dir, options, &report, &log_path);
+    Ok(report)
+}
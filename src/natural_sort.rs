@@ -0,0 +1,126 @@
+// Description: Natural (human) ordering of names, where runs of digits are compared by value so
+//              `Disc 9` comes before `Disc 10`.
+
+use std::{cmp::Ordering, iter::Peekable, str::Chars};
+
+/// A name that orders naturally, for `sort_by_key` and friends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NaturalOrd<'a>(pub &'a str);
+
+impl Ord for NaturalOrd<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        natural_cmp(self.0, other.0)
+    }
+}
+
+impl PartialOrd for NaturalOrd<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Take the run of digits at the front of `chars`
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+/// Compare two strings split into runs of digits and non-digits: digit runs are compared as
+/// numbers and everything else character by character. Names that only differ in leading zeros
+/// (`Disc 01` and `Disc 1`) fall back to plain string order, so only equal strings are equal.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut x_chars = a.chars().peekable();
+    let mut y_chars = b.chars().peekable();
+    loop {
+        match (x_chars.peek().copied(), y_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut x_chars);
+                let y = take_number(&mut y_chars);
+
+                // compare by value without parsing, so long runs can't overflow
+                let x_trimmed = x.trim_start_matches('0');
+                let y_trimmed = y.trim_start_matches('0');
+                let ord = x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                x_chars.next();
+                y_chars.next();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Ordering::{Equal, Greater, Less};
+
+    #[test]
+    fn compares_naturally() {
+        let cases = [
+            // numbers by value
+            ("Disc 9", "Disc 10", Less),
+            ("Disc 10", "Disc 9", Greater),
+            ("Disc 2", "Disc 2", Equal),
+            ("Track 2 part 10", "Track 2 part 9", Greater),
+            ("a1b2", "a1b10", Less),
+            // leading zeros
+            ("Disc 01", "Disc 1", Less),
+            ("Disc 1", "Disc 01", Greater),
+            ("Disc 010", "Disc 9", Greater),
+            ("Disc 007", "Disc 7", Less),
+            ("Track 00", "Track 0", Greater),
+            // empty strings
+            ("", "", Equal),
+            ("", "a", Less),
+            ("1", "", Greater),
+            // all-numeric names
+            ("2", "10", Less),
+            ("100", "99", Greater),
+            ("12345678901234567890123", "12345678901234567890124", Less),
+            ("99999999999999999999999", "100000000000000000000000", Less),
+            // equal prefix
+            ("Game", "Game (Disc 1)", Less),
+            ("Game 1", "Game 1 (Rev 1)", Less),
+            ("Game (Disc 1)", "Game", Greater),
+            // digits and letters
+            ("1", "a", Less),
+            ("Disc A", "Disc 1", Greater),
+            ("Track01.bin", "Track1a.bin", Less),
+            // case is compared as is
+            ("Disc B", "Disc a", Less),
+            ("game", "Game", Greater),
+            // unicode
+            ("Pokémon 2", "Pokémon 10", Less),
+            ("Ōkami", "Okami", Greater),
+            ("ゲーム 9", "ゲーム 10", Less),
+            // non-ascii digits aren't numbers
+            ("Disc ٢", "Disc ١٠", Greater),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(natural_cmp(a, b), expected, "{a:?} vs {b:?}");
+        }
+    }
+
+    #[test]
+    fn sorts_with_natural_ord() {
+        let mut names = vec!["Disc 10", "Disc 1", "Disc 9", "Disc 01", "Disc 2"];
+        names.sort_by_key(|n| NaturalOrd(n));
+        assert_eq!(names, ["Disc 01", "Disc 1", "Disc 2", "Disc 9", "Disc 10"]);
+    }
+}
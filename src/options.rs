@@ -0,0 +1,599 @@
+// Description: Options that control how a parent dir is processed.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, mpsc::Sender},
+    thread,
+    time::SystemTime,
+};
+
+use crate::{
+    ColorChoice, CsvReport, DEFAULT_FORMAT_PRIORITY, DEFAULT_PBP_FIRST_DISC_NAME, Decision,
+    DigestAlgorithm, DirPlan, FormatPreference, IGNORE_FILE_NAME, IgnoreRules, Level, LogFile,
+    NameTemplate, Newline, OutputEncoding, PathSeparator, Pattern, PlaylistFormat, RenameStrategy,
+    SortOrder, SymlinkMode, TemplateVars, TuiEvent, build_path_from_parts, get_path_dir_name,
+    has_extension, paint_line, time::DateTime,
+};
+
+/// Extensions processed when the user does not ask for anything else
+pub const DEFAULT_EXTENSIONS: &[&str] = &["chd", "cue", "bin", "gdi", "pbp"];
+
+/// Prefix of the sub-dir name, which hides it on most systems
+pub const DEFAULT_SUBDIR_PREFIX: &str = ".";
+
+/// Name of every level below the sub-dir with --subdir-depth above 1
+pub const DEFAULT_SUBDIR_LEAF_NAME: &str = "files";
+
+/// Deepest --subdir-depth allowed
+pub const MAX_SUBDIR_DEPTH: usize = 5;
+
+/// Sidecar files copied along with the game by --include-nfo
+pub const DEFAULT_NFO_EXTENSIONS: &[&str] = &["nfo", "txt", "dat", "xml"];
+
+/// Flags that control how each child directory is processed
+#[derive(Debug, Clone)]
+pub struct GenOptions {
+    /// Print the planned operations instead of performing them
+    pub dry_run: bool,
+    /// Write the .m3u next to the existing files instead of moving them into the sub-dir
+    /// (--no-move or --flat), the sub-dir is never created
+    pub no_move: bool,
+    /// File extensions (without the dot) that are treated as game data
+    pub extensions: Vec<String>,
+    /// Write the #EXTM3U header and an #EXTINF line before every entry
+    pub extended: bool,
+    /// Kind of playlist file to write
+    pub format: PlaylistFormat,
+    /// Tweak the output for Windows players (a BOM at the start of .m3u8 files)
+    pub windows_compat: bool,
+    /// How many child dirs to process at the same time
+    pub jobs: usize,
+    /// Show a progress bar on stderr (only drawn when stderr is a terminal)
+    pub progress: bool,
+    /// Leave child dirs that already have a playlist or sub-dir alone
+    pub skip_existing: bool,
+    /// Add new files to child dirs that were already processed
+    pub update: bool,
+    /// Add files the playlist doesn't list yet to the end of it, leaving the rest alone
+    pub append: bool,
+    /// Process dirs with both a .chd and a .cue/.bin set instead of reporting them
+    pub force: bool,
+    /// Format kept when a conflicted dir is forced
+    pub prefer: FormatPreference,
+    /// Create the parent dir when it doesn't exist instead of failing
+    pub create_parent: bool,
+    /// Settle a conflicted dir by keeping its best format from `format_priority`
+    pub auto_detect_format: bool,
+    /// Extensions ranked by --auto-detect-format, the best first
+    pub format_priority: Vec<String>,
+    /// Move the files of the formats --auto-detect-format passed over into their own sub-dir
+    /// instead of leaving them where they are
+    pub archive_secondary_formats: bool,
+    /// Where to write the conflict report, defaults to conflicts.log in the parent dir
+    pub conflict_log: Option<PathBuf>,
+    /// Where to record the changes made, defaults to a timestamped file in the parent dir
+    pub transaction_log: Option<PathBuf>,
+    /// Keep running and process new child dirs as they are added
+    pub watch: bool,
+    /// How long a new child dir has to stay unchanged before it's processed in watch mode
+    pub debounce_ms: u64,
+    /// Watch by re-reading the parent dir instead of waiting for inotify events, for network
+    /// filesystems that don't send them
+    pub watch_poll: bool,
+    /// How often the parent dir is re-read with --watch-poll
+    pub watch_interval_ms: u64,
+    /// Look for game dirs in the whole tree below the parent instead of just its children
+    pub recursive: bool,
+    /// How many levels below the parent to look when recursing, unlimited when not set
+    pub max_depth: Option<usize>,
+    /// Copy the data files into the sub-dir instead of moving them
+    pub copy: bool,
+    /// Start the playlists of processed dirs over from what's in the sub-dir and the dir, and
+    /// replace files that already exist in the sub-dir when copying
+    pub overwrite: bool,
+    /// Order of the entries in the playlist
+    pub sort: SortOrder,
+    /// Extensions whose files are listed first, in this order, each group sorted by `sort`
+    pub extension_priority_order: Vec<String>,
+    /// Put in front of the dir name to name the sub-dir, `.` keeps it hidden
+    pub subdir_prefix: String,
+    /// Use this name for every sub-dir instead of the prefix and the dir name
+    pub subdir_name: Option<String>,
+    /// Levels of sub-dirs the files are moved into, 2 moves them into `.Game/files`
+    pub subdir_depth: usize,
+    /// Name of the levels below the first one
+    pub subdir_leaf_name: String,
+    /// Only child dirs whose name matches every one of these are processed
+    pub filters: Vec<Pattern>,
+    /// Child dirs whose name matches any of these are left alone
+    pub excludes: Vec<Pattern>,
+    /// Only child dirs whose name matches one of these are processed, before --exclude-dirs
+    pub include_dirs: Vec<Pattern>,
+    /// Child dirs (out of those --include-dirs picked) whose name matches one of these are
+    /// left alone
+    pub exclude_dirs: Vec<Pattern>,
+    /// Write every playlist to this dir instead of inside its game dir
+    pub output_dir: Option<PathBuf>,
+    /// Template for the playlist file name, `{dir}.{format extension}` when not set
+    pub m3u_filename: Option<NameTemplate>,
+    /// Template for the name of a PSP game's first .pbp, `EBOOT.PBP` when not set
+    pub pbp_first_disc_name: Option<NameTemplate>,
+    /// Start .m3u8 playlists with a UTF-8 BOM
+    pub bom: bool,
+    /// Write the playlists gzipped, as `Game.m3u.gz`
+    pub compress_m3u: bool,
+    /// How hard to try when compressing, from 1 (fastest) to 9 (smallest)
+    pub compression_level: u32,
+    /// Write a `<dir name>.sha256` (or .md5, .sha1) next to the playlist for the files moved into
+    /// the sub-dir
+    pub checksums: bool,
+    /// Hash written by --checksums, which also picks the extension of the sidecar file
+    pub digest_algorithm: DigestAlgorithm,
+    /// Comment lines (without the `#`) written at the top of every .m3u
+    pub m3u_comments: Vec<String>,
+    /// Start every .m3u with a comment saying which version of m3ugen wrote it
+    pub auto_comment: bool,
+    /// Add when it was written to the automatic comment. Off by default so a rerun that finds
+    /// nothing new leaves the playlist as it was.
+    pub auto_comment_date: bool,
+    /// File whose contents are written at the top of every .m3u, after the comments
+    pub template_m3u: Option<PathBuf>,
+    /// The contents of the --template-m3u, read once at startup
+    pub template: Option<Arc<str>>,
+    /// When to color what gets printed
+    pub color: ColorChoice,
+    /// Lines less important than this aren't printed, set from RUST_LOG
+    pub log_level: Level,
+    /// Also write every line (debug included) to this file
+    pub log_file: Option<PathBuf>,
+    /// The opened --log-file
+    pub log: Option<LogFile>,
+    /// Write a CSV row for every file operation to this file
+    pub report_csv: Option<PathBuf>,
+    /// The opened --report-csv
+    pub csv: Option<CsvReport>,
+    /// Where to send progress and output instead of printing it, set by the TUI
+    pub events: Option<Sender<TuiEvent>>,
+    /// Asked about every child dir before it's processed, set by --interactive
+    pub confirm: Option<fn(&DirPlan) -> Decision>,
+    /// Ignore file to use instead of the .m3ugenignore in the parent dir
+    pub ignore_file: Option<PathBuf>,
+    /// Dir and file names to leave alone, loaded from the ignore file
+    pub ignore: IgnoreRules,
+    /// How long to wait for another run to release the parent dir
+    pub lock_timeout_ms: u64,
+    /// Leave dirs with a single game file (or a single .cue and its tracks) alone
+    pub only_multi_file: bool,
+    /// Leave game dirs that hold other dirs (besides the sub-dir) alone
+    pub skip_dirs_with_subdirs: bool,
+    /// Number of child dirs (in name order) to skip before processing starts
+    pub offset: usize,
+    /// Stop after processing this many child dirs
+    pub limit: Option<usize>,
+    /// Skip hidden child dirs while scanning, defaults to on when the sub-dir prefix hides it
+    pub exclude_hidden: Option<bool>,
+    /// Write absolute paths to the data files instead of paths relative to the playlist
+    pub absolute_paths: bool,
+    /// Taken off the front of every playlist entry, e.g. `.` to drop the dot of the sub-dir
+    pub strip_prefix: Option<String>,
+    /// Put in front of every playlist entry, for frontends that resolve them from another root
+    pub prepend_path: Option<String>,
+    /// Separator written between the parts of an entry, `Windows` for frontends running on
+    /// Windows (set by --windows-paths too)
+    pub platform_separator: PathSeparator,
+    /// Give every game dir a canonical name before anything else is done to it
+    pub rename_dir: Option<RenameStrategy>,
+    /// Rename the game files after their dir before moving them
+    pub rename_files: bool,
+    /// Copy every game dir here as it was before anything is done to it
+    pub backup: Option<PathBuf>,
+    /// Also carry the extended attributes over when a file has to be copied
+    pub preserve_xattr: bool,
+    /// Encoding the playlists are written in
+    pub output_encoding: OutputEncoding,
+    /// Write the lines --output-encoding can't hold as UTF-8 instead of failing the playlist
+    pub encoding_fallback: bool,
+    /// Warn about playlist entries longer than this many characters
+    pub max_m3u_line_length: Option<usize>,
+    /// Leave entries over --max-m3u-line-length out of the playlist as an error instead
+    pub error_on_long_lines: bool,
+    /// List only the .cue (or .gdi, .mds) of each disc, not the tracks it references
+    pub one_per_disc: bool,
+    /// Spread the entries of a dir over more playlists once it has more than this many
+    pub max_files_per_m3u: Option<usize>,
+    /// Copy sidecar files (readmes, .nfo, ...) into the sub-dir, leaving the originals in place
+    pub include_nfo: bool,
+    /// Write a playlist per extension (Game_chd.m3u, Game_cue.m3u) instead of one for the dir
+    pub group_by_extension: bool,
+    /// Only process dirs whose own modification time is at or after this
+    pub since: Option<SystemTime>,
+    /// What to do with symlinked game dirs and files
+    pub symlinks: SymlinkMode,
+    /// How many more times to try a move that failed because the file was locked
+    pub retry_count: u32,
+    /// How long to wait between those tries, in milliseconds
+    pub retry_delay_ms: u64,
+    /// Extensions of the sidecar files copied by `include_nfo`
+    pub nfo_extensions: Vec<String>,
+    /// Line ending used in the playlists we write
+    pub newline: Newline,
+}
+
+impl Default for GenOptions {
+    fn default() -> Self {
+        GenOptions {
+            dry_run: false,
+            no_move: false,
+            extensions: DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+            extended: false,
+            format: PlaylistFormat::default(),
+            windows_compat: false,
+            jobs: thread::available_parallelism().map_or(1, |n| n.get()),
+            progress: true,
+            skip_existing: false,
+            update: false,
+            append: false,
+            force: false,
+            prefer: FormatPreference::default(),
+            create_parent: false,
+            extension_priority_order: Vec::new(),
+            auto_detect_format: false,
+            format_priority: DEFAULT_FORMAT_PRIORITY
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+            archive_secondary_formats: false,
+            conflict_log: None,
+            transaction_log: None,
+            watch: false,
+            debounce_ms: 500,
+            watch_poll: false,
+            watch_interval_ms: 5000,
+            recursive: false,
+            max_depth: None,
+            copy: false,
+            overwrite: false,
+            sort: SortOrder::default(),
+            subdir_prefix: DEFAULT_SUBDIR_PREFIX.to_string(),
+            subdir_name: None,
+            subdir_depth: 1,
+            subdir_leaf_name: DEFAULT_SUBDIR_LEAF_NAME.to_string(),
+            filters: Vec::new(),
+            excludes: Vec::new(),
+            include_dirs: Vec::new(),
+            exclude_dirs: Vec::new(),
+            output_dir: None,
+            m3u_filename: None,
+            pbp_first_disc_name: None,
+            bom: false,
+            compress_m3u: false,
+            compression_level: 6,
+            checksums: false,
+            digest_algorithm: DigestAlgorithm::default(),
+            m3u_comments: Vec::new(),
+            template_m3u: None,
+            template: None,
+            auto_comment: true,
+            auto_comment_date: false,
+            color: ColorChoice::default(),
+            log_level: Level::default(),
+            log_file: None,
+            report_csv: None,
+            csv: None,
+            log: None,
+            events: None,
+            confirm: None,
+            ignore_file: None,
+            ignore: IgnoreRules::default(),
+            lock_timeout_ms: 5000,
+            only_multi_file: false,
+            skip_dirs_with_subdirs: false,
+            offset: 0,
+            limit: None,
+            exclude_hidden: None,
+            absolute_paths: false,
+            strip_prefix: None,
+            prepend_path: None,
+            platform_separator: PathSeparator::default(),
+            output_encoding: OutputEncoding::default(),
+            encoding_fallback: false,
+            rename_dir: None,
+            rename_files: false,
+            backup: None,
+            preserve_xattr: false,
+            max_files_per_m3u: None,
+            one_per_disc: false,
+            max_m3u_line_length: None,
+            error_on_long_lines: false,
+            include_nfo: false,
+            group_by_extension: false,
+            since: None,
+            symlinks: SymlinkMode::Skip,
+            retry_count: 3,
+            retry_delay_ms: 500,
+            nfo_extensions: DEFAULT_NFO_EXTENSIONS
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+            newline: Newline::default(),
+        }
+    }
+}
+
+impl GenOptions {
+    /// Check if hidden dirs are left out of the scan. Our own sub-dirs are hidden when the prefix
+    /// is a dot, so scanning those would put playlists inside them.
+    pub fn skips_hidden(&self) -> bool {
+        self.exclude_hidden
+            .unwrap_or_else(|| self.sub_dir_name("").starts_with('.'))
+    }
+
+    /// Name of the sub-dir the files of the game dir `dir_name` are moved into
+    pub fn sub_dir_name(&self, dir_name: &str) -> String {
+        match &self.subdir_name {
+            Some(name) => name.clone(),
+            None => format!("{}{dir_name}", self.subdir_prefix),
+        }
+    }
+
+    /// Where the files of the game dir `dir_name` are moved, relative to the game dir. It's the
+    /// sub-dir itself unless --subdir-depth nests it, like `.Game/files`.
+    pub fn sub_dir_path(&self, dir_name: &str) -> PathBuf {
+        let mut ret = PathBuf::from(self.sub_dir_name(dir_name));
+        for _ in 1..self.subdir_depth {
+            ret = build_path_from_parts(&[&ret, Path::new(&self.subdir_leaf_name)]);
+        }
+        ret
+    }
+
+    /// What the first .pbp of the game dir `dir_name` is called, from --pbp-first-disc-name
+    pub fn first_pbp_name(&self, dir_name: &str) -> String {
+        match &self.pbp_first_disc_name {
+            Some(template) => template
+                .render(&TemplateVars {
+                    dir: dir_name,
+                    ext: "pbp",
+                    ..TemplateVars::default()
+                })
+                .unwrap_or_default(),
+            None => DEFAULT_PBP_FIRST_DISC_NAME.to_string(),
+        }
+    }
+
+    /// Check if a child dir makes it past --filter, --exclude, --include-dirs, --exclude-dirs and
+    /// the ignore file
+    pub fn wants_dir(&self, dir: &Path) -> bool {
+        let name = get_path_dir_name(dir);
+        self.filters.iter().all(|p| p.matches(&name))
+            && !self.excludes.iter().any(|p| p.matches(&name))
+            && (self.include_dirs.is_empty()
+                || self.include_dirs.iter().any(|p| p.matches_dir_name(&name)))
+            && !self.exclude_dirs.iter().any(|p| p.matches_dir_name(&name))
+            && !self.ignore.is_ignored(&name, true)
+    }
+
+    /// Check if a child dir was changed recently enough for --since. Only the dir's own time is
+    /// looked at, not the times of the files in it.
+    pub fn modified_since(&self, dir: &Path) -> bool {
+        let Some(since) = self.since else {
+            return true;
+        };
+        fs::metadata(dir)
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| modified >= since)
+    }
+
+    /// Check if a file is game data: it has one of our extensions and isn't ignored
+    pub fn wants_file(&self, file: &Path) -> bool {
+        has_extension(file, &self.extensions)
+            && !self.ignore.is_ignored(&get_path_dir_name(file), false)
+    }
+
+    /// Hand an event to whoever is listening, if anyone
+    pub fn emit(&self, event: TuiEvent) {
+        if let Some(events) = &self.events {
+            // the listener going away shouldn't stop the run
+            let _ = events.send(event);
+        }
+    }
+
+    /// Print a line of output, or send it along when something is listening for events
+    pub fn say(&self, line: String) {
+        let level = Level::of_line(&line).unwrap_or(Level::Info);
+        self.log(level, line, false);
+    }
+
+    /// Print a warning to stderr, or send it along when something is listening for events
+    pub fn warn(&self, line: String) {
+        let level = Level::of_line(&line).unwrap_or(Level::Warn);
+        self.log(level, line, true);
+    }
+
+    /// Print a line to stderr only when RUST_LOG asks for debug output
+    pub fn debug(&self, line: String) {
+        self.log(Level::Debug, format!("DEBUG: {line}"), true);
+    }
+
+    /// Write `line` to the log file, and to the terminal (or the listener) when `level` is
+    /// enabled
+    pub fn log(&self, level: Level, line: String, stderr: bool) {
+        if let Some(log) = &self.log {
+            log.write(level, &line);
+        }
+        if level > self.log_level {
+            return;
+        }
+        match (&self.events, stderr) {
+            (Some(_), true) if level <= Level::Warn => self.emit(TuiEvent::Warning(line)),
+            (Some(_), _) => self.emit(TuiEvent::Log(line)),
+            (None, true) => eprintln!("{}", paint_line(&line, self.color.stderr())),
+            (None, false) => println!("{}", paint_line(&line, self.color.stdout())),
+        }
+    }
+
+    /// Open the --log-file, if one was asked for
+    pub fn open_log_file(&mut self) -> Result<(), String> {
+        if let Some(path) = &self.log_file {
+            let log = LogFile::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+            self.log = Some(log);
+        }
+        Ok(())
+    }
+
+    /// Create the --report-csv, if there is one
+    pub fn open_report_csv(&mut self) -> Result<(), String> {
+        if let Some(path) = &self.report_csv {
+            let csv = CsvReport::create(path).map_err(|e| format!("{}: {e}", path.display()))?;
+            self.csv = Some(csv);
+        }
+        Ok(())
+    }
+
+    /// Read the --template-m3u, if there is one, so every playlist can start with it
+    pub fn load_template_m3u(&mut self) -> Result<(), String> {
+        if let Some(path) = &self.template_m3u {
+            let bytes = fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+            let text = String::from_utf8(bytes)
+                .map_err(|_| format!("{}: it isn't valid UTF-8", path.display()))?;
+            self.template = Some(text.into());
+        }
+        Ok(())
+    }
+
+    /// The comment lines to start a playlist with, the automatic one first
+    pub fn comment_lines(&self) -> Vec<String> {
+        let mut ret = Vec::new();
+        if self.auto_comment {
+            let mut comment = format!("Generated by m3ugen {}", env!("CARGO_PKG_VERSION"));
+            if self.auto_comment_date {
+                comment.push_str(&format!(" on {}", DateTime::now().iso_string()));
+            }
+            ret.push(comment);
+        }
+        ret.extend(self.m3u_comments.iter().cloned());
+        ret
+    }
+
+    /// Load the ignore file for `parent`, the --ignore-file one or else the .m3ugenignore in the
+    /// parent dir when there is one
+    pub fn load_ignore_file(&mut self, parent: &Path) -> Result<(), String> {
+        let path = match &self.ignore_file {
+            Some(path) => path.clone(),
+            None => {
+                let path = build_path_from_parts(&[parent, Path::new(IGNORE_FILE_NAME)]);
+                if !path.is_file() {
+                    return Ok(());
+                }
+                path
+            }
+        };
+        self.ignore = IgnoreRules::load(&path)?;
+        Ok(())
+    }
+}
+
+/// Parse the date given to --since
+pub fn parse_since(s: &str) -> Result<SystemTime, String> {
+    DateTime::parse_iso(s)
+        .map(|date| date.to_system_time())
+        .ok_or_else(|| {
+            format!("'{s}' is not a valid date (expected YYYY-MM-DD or YYYY-MM-DDTHH:MM:SSZ)")
+        })
+}
+
+/// Environment variable with the extensions to use instead of the built-in defaults
+pub const EXTENSIONS_ENV: &str = "M3UGEN_DEFAULT_EXTENSIONS";
+
+/// The extensions in M3UGEN_DEFAULT_EXTENSIONS, if it's set to anything
+pub fn extensions_from_env() -> Result<Option<Vec<String>>, String> {
+    let Some(value) = env::var_os(EXTENSIONS_ENV) else {
+        return Ok(None);
+    };
+    let value = value
+        .into_string()
+        .map_err(|_| format!("{EXTENSIONS_ENV} isn't valid UTF-8"))?;
+    let list = parse_extension_list(&value).map_err(|e| format!("{EXTENSIONS_ENV}: {e}"))?;
+    Ok((!list.is_empty()).then_some(list))
+}
+
+/// Split a comma separated list like `iso,.PBP` into lowercase extensions without the dot
+pub fn parse_extension_list(list: &str) -> Result<Vec<String>, String> {
+    let mut ret = Vec::new();
+    for ext in list.split(',') {
+        let ext = ext.trim().trim_start_matches('.').to_lowercase();
+        if ext.is_empty() {
+            continue;
+        }
+        if ext.contains(['/', '\\', '\0', ' ']) {
+            return Err(format!("'{ext}' is not a valid file extension"));
+        }
+        ret.push(ext);
+    }
+    Ok(ret)
+}
+
+/// Check that a fixed sub-dir name is a single path component
+pub fn validate_subdir_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(format!("'{name}' can't be used as a sub-dir name"));
+    }
+    if name.contains(['/', '\\', '\0']) {
+        return Err(format!(
+            "'{name}' can't be used as a sub-dir name, it contains a path separator"
+        ));
+    }
+    Ok(())
+}
+
+/// Check that a sub-dir prefix can't turn the sub-dir name into a path
+pub fn validate_subdir_prefix(prefix: &str) -> Result<(), String> {
+    if prefix.contains(['/', '\\', '\0']) {
+        return Err(format!(
+            "'{prefix}' can't be used as a prefix, it contains a path separator"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_comment_has_no_date_by_default() {
+        let options = GenOptions {
+            m3u_comments: vec!["Kanto".to_string()],
+            ..GenOptions::default()
+        };
+        let version = env!("CARGO_PKG_VERSION");
+        assert_eq!(
+            options.comment_lines(),
+            [
+                format!("Generated by m3ugen {version}"),
+                "Kanto".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn auto_comment_date_is_opt_in() {
+        let options = GenOptions {
+            auto_comment_date: true,
+            ..GenOptions::default()
+        };
+        let before = DateTime::now().iso_string();
+        let lines = options.comment_lines();
+        let after = DateTime::now().iso_string();
+        let prefix = format!("Generated by m3ugen {} on ", env!("CARGO_PKG_VERSION"));
+        let date = lines[0].strip_prefix(&prefix).unwrap();
+        // ISO timestamps sort the same as the times they name
+        assert!(before.as_str() <= date && date <= after.as_str(), "{date}");
+
+        let options = GenOptions {
+            auto_comment: false,
+            ..GenOptions::default()
+        };
+        assert!(options.comment_lines().is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+// Description: The find-orphans subcommand: spot the files in the hidden sub-dirs that no
+//              playlist points at any more, e.g. after entries were deleted from a playlist by
+//              hand, and remove them with --delete-orphans.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    GenError, GenOptions, PlaylistFormat, build_path_from_parts, get_path_dir_name, has_extension,
+    is_playlist_file, parse_playlist_entries, process::referenced_tracks, read_playlist_file,
+    strip_gz, validate::child_dirs, verify_dir,
+};
+
+/// What a find-orphans run found
+#[derive(Debug, Default)]
+pub struct OrphanReport {
+    pub sub_dirs_checked: usize,
+    /// Files in a hidden sub-dir that no playlist of its game dir references
+    pub orphans: Vec<PathBuf>,
+    pub errors: Vec<GenError>,
+}
+
+/// Compare paths the way the filesystem sees them, so `.G/a.bin` and `/roms/G/.G/a.bin` match
+fn same_file_key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Every file under `dir`, in sorted order
+fn files_under(dir: &Path, ret: &mut Vec<PathBuf>) -> Result<(), GenError> {
+    let files = fs::read_dir(dir).map_err(|source| GenError::ReadDirFailed {
+        path: dir.to_path_buf(),
+        source,
+    })?;
+    let mut paths: Vec<PathBuf> = files.filter_map(Result::ok).map(|f| f.path()).collect();
+    paths.sort();
+    for path in paths {
+        match path.is_dir() {
+            true => files_under(&path, ret)?,
+            false => ret.push(path),
+        }
+    }
+    Ok(())
+}
+
+/// The files the playlists of `child` point at, along with the tracks of any sheet among them
+fn referenced_files(child: &Path, options: &GenOptions) -> Result<Vec<PathBuf>, GenError> {
+    let files = fs::read_dir(child).map_err(|source| GenError::ReadDirFailed {
+        path: child.to_path_buf(),
+        source,
+    })?;
+    let mut playlists: Vec<PathBuf> = files
+        .filter_map(Result::ok)
+        .map(|f| f.path())
+        .filter(|f| f.is_file() && is_playlist_file(f))
+        .collect();
+    playlists.sort();
+    // entries written with --prepend-path only resolve once it's taken off
+    let prefix = options
+        .prepend_path
+        .as_deref()
+        .filter(|p| !p.is_empty())
+        .map(|p| p.trim_end_matches(['/', '\\']));
+    let mut ret = Vec::new();
+    for playlist in playlists {
+        let contents = read_playlist_file(&playlist).map_err(|source| GenError::AccessFailed {
+            path: playlist.clone(),
+            source,
+        })?;
+        let format = match has_extension(&strip_gz(&playlist), &["pls"]) {
+            true => PlaylistFormat::Pls,
+            false => PlaylistFormat::M3u,
+        };
+        for entry in parse_playlist_entries(&contents, format) {
+            let path = match prefix.and_then(|p| entry.path.strip_prefix(p)) {
+                Some(rest) => rest.trim_start_matches(['/', '\\']),
+                None => entry.path.as_str(),
+            };
+            ret.push(build_path_from_parts(&[child, Path::new(path)]));
+        }
+    }
+    // a listed .cue needs its .bin tracks even when they have no entries of their own
+    let tracks = referenced_tracks(&ret);
+    ret.extend(tracks);
+    Ok(ret)
+}
+
+/// Find the orphans in the hidden sub-dir of one game dir
+fn find_dir_orphans(child: &Path, options: &GenOptions, report: &mut OrphanReport) {
+    let sub_dir = build_path_from_parts(&[child, &options.sub_dir_path(&get_path_dir_name(child))]);
+    if !sub_dir.is_dir() {
+        return;
+    }
+    let mut files = Vec::new();
+    if let Err(e) = files_under(&sub_dir, &mut files) {
+        report.errors.push(e);
+        return;
+    }
+    let referenced: Vec<PathBuf> = match referenced_files(child, options) {
+        Ok(r) => r.iter().map(|p| same_file_key(p)).collect(),
+        Err(e) => {
+            report.errors.push(e);
+            return;
+        }
+    };
+    report.sub_dirs_checked += 1;
+    report.orphans.extend(
+        files
+            .into_iter()
+            .filter(|f| !referenced.contains(&same_file_key(f))),
+    );
+}
+
+/// Find the files in the hidden sub-dirs of the child dirs of `parent` that none of the
+/// playlists in that child dir reference. Nothing is changed.
+pub fn find_orphans(parent: &Path, options: &GenOptions) -> Result<OrphanReport, GenError> {
+    verify_dir(parent, false)?;
+    let mut report = OrphanReport::default();
+    for child in child_dirs(parent, options)? {
+        find_dir_orphans(&child, options, &mut report);
+    }
+    Ok(report)
+}
+
+/// Delete the `orphans` a find-orphans run found, returning the ones that couldn't be removed
+pub fn delete_orphans(orphans: &[PathBuf], options: &GenOptions) -> Vec<GenError> {
+    let mut ret = Vec::new();
+    for orphan in orphans {
+        if options.dry_run {
+            options.say(format!("WOULD REMOVE FILE: {}", orphan.display()));
+            continue;
+        }
+        match fs::remove_file(orphan) {
+            Ok(()) => options.say(format!("REMOVED: {}", orphan.display())),
+            Err(source) => ret.push(GenError::AccessFailed {
+                path: orphan.clone(),
+                source,
+            }),
+        }
+    }
+    ret
+}
@@ -0,0 +1,83 @@
+// Description: Work out what processing a child dir would do before doing it, so the user can
+//              approve it first (--interactive).
+
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use crate::{DirOutcome, GenOptions, TuiEvent, process::process_child_report};
+
+/// What to do with a child dir after seeing its plan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Go ahead and process it
+    Process,
+    /// Leave it alone and move on to the next one
+    Skip,
+    /// Stop the run, nothing else gets processed
+    Quit,
+}
+
+/// The changes processing a child dir would make, taken from a dry run of it
+#[derive(Debug, Clone)]
+pub struct DirPlan {
+    /// The child dir
+    pub dir: PathBuf,
+    /// What the dry run ended with, `None` when it failed
+    pub outcome: Option<DirOutcome>,
+    /// Every line the dry run printed (with full paths), errors included
+    pub lines: Vec<String>,
+}
+
+impl DirPlan {
+    /// The files that would be moved or copied, as `src -> dst`
+    pub fn moves(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| {
+                l.strip_prefix("WOULD MOVE: ")
+                    .or_else(|| l.strip_prefix("WOULD COPY: "))
+            })
+            .collect()
+    }
+
+    /// The lines that would be written to the playlist
+    pub fn playlist(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| l.strip_prefix("WOULD WRITE: "))
+            .map(|l| l.rsplit_once(" >> ").map_or(l, |(line, _)| line))
+            .collect()
+    }
+}
+
+/// Dry run a child dir of `parent` and collect what it would do
+pub fn plan_child_dir(parent: &Path, child: &Path, options: &GenOptions) -> DirPlan {
+    let (sender, events) = mpsc::channel();
+    let dry_run = GenOptions {
+        dry_run: true,
+        progress: false,
+        events: Some(sender),
+        confirm: None,
+        // the real run is what belongs in the log file
+        log: None,
+        ..options.clone()
+    };
+    let mut report = process_child_report(parent, child, &dry_run);
+    drop(dry_run);
+
+    let mut lines: Vec<String> = events
+        .into_iter()
+        .filter_map(|event| match event {
+            TuiEvent::Log(line) | TuiEvent::Warning(line) => Some(line),
+            _ => None,
+        })
+        .collect();
+    lines.extend(report.errors.iter().map(|e| format!("ERROR: {e}")));
+    DirPlan {
+        dir: child.to_path_buf(),
+        outcome: report.directories.pop().and_then(|d| d.outcome),
+        lines,
+    }
+}
@@ -0,0 +1,489 @@
+// Description: Playlist formats that can be written for a game dir.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{GenOptions, gunzip, has_extension, strip_gz};
+
+/// The UTF-8 byte order mark some Windows players need to detect the encoding
+pub const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Which kind of playlist file to write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaylistFormat {
+    #[default]
+    M3u,
+    M3u8,
+    Pls,
+}
+
+impl PlaylistFormat {
+    /// Parse the value given to --output-format
+    pub fn parse(name: &str) -> Option<PlaylistFormat> {
+        match name.to_lowercase().trim_start_matches('.') {
+            "m3u" => Some(PlaylistFormat::M3u),
+            "m3u8" => Some(PlaylistFormat::M3u8),
+            "pls" => Some(PlaylistFormat::Pls),
+            _ => None,
+        }
+    }
+
+    /// File extension (without the dot) for this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u => "m3u",
+            PlaylistFormat::M3u8 => "m3u8",
+            PlaylistFormat::Pls => "pls",
+        }
+    }
+}
+
+/// Line ending written between playlist lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Newline {
+    #[default]
+    Lf,
+    Crlf,
+    /// CRLF on Windows, LF everywhere else
+    Platform,
+}
+
+impl Newline {
+    /// Parse the value given to --newline
+    pub fn parse(name: &str) -> Option<Newline> {
+        match name.to_lowercase().as_str() {
+            "lf" => Some(Newline::Lf),
+            "crlf" => Some(Newline::Crlf),
+            "platform" => Some(Newline::Platform),
+            _ => None,
+        }
+    }
+
+    /// The bytes to write
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Newline::Lf => "\n",
+            Newline::Crlf => "\r\n",
+            Newline::Platform if cfg!(windows) => "\r\n",
+            Newline::Platform => "\n",
+        }
+    }
+
+    /// Line ending used by an existing file, if it has any line breaks at all
+    pub fn detect(contents: &[u8]) -> Option<Newline> {
+        let lf = contents.iter().position(|&b| b == b'\n')?;
+        match lf > 0 && contents[lf - 1] == b'\r' {
+            true => Some(Newline::Crlf),
+            false => Some(Newline::Lf),
+        }
+    }
+}
+
+/// Separator written between the parts of a playlist entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathSeparator {
+    /// The separator of the platform m3ugen was built for
+    #[default]
+    Auto,
+    /// `/`, whatever the build platform
+    Linux,
+    /// `\`, whatever the build platform
+    Windows,
+}
+
+impl PathSeparator {
+    /// Parse the value given to --platform-separator
+    pub fn parse(name: &str) -> Option<PathSeparator> {
+        match name.to_lowercase().as_str() {
+            "auto" => Some(PathSeparator::Auto),
+            "linux" => Some(PathSeparator::Linux),
+            "windows" => Some(PathSeparator::Windows),
+            _ => None,
+        }
+    }
+
+    /// Switch the separators of an entry built from native paths. Only `/` separates parts on
+    /// unix, so a `\` in a file name there is left alone.
+    pub fn apply(&self, entry: String) -> String {
+        match self {
+            PathSeparator::Windows => entry.replace('/', "\\"),
+            PathSeparator::Auto if cfg!(windows) => entry.replace('/', "\\"),
+            PathSeparator::Linux if cfg!(windows) => entry.replace('\\', "/"),
+            PathSeparator::Auto | PathSeparator::Linux => entry,
+        }
+    }
+}
+
+/// One file listed in a playlist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaylistEntry {
+    /// Path written to the playlist, relative to the playlist file
+    pub path: String,
+    /// Human readable name, e.g. `Final Fantasy VII (Disc 2)`
+    pub title: String,
+}
+
+/// Something that can write a list of entries out in a playlist format
+pub trait PlaylistWriter {
+    fn write_playlist(&mut self, entries: &[PlaylistEntry]) -> io::Result<()>;
+}
+
+/// Writes .m3u and .m3u8 playlists
+pub struct M3uWriter<W: Write> {
+    out: W,
+    /// Write #EXTM3U and #EXTINF lines
+    pub extended: bool,
+    /// Start with the #EXTM3U line when extended, off when adding to an existing playlist
+    pub header: bool,
+    /// Start the file with a UTF-8 BOM
+    pub bom: bool,
+    /// Comment lines written at the top (without the `#`), part of the header
+    pub comments: Vec<String>,
+    /// Text written as it is after the header, ended with a line break if it doesn't have one
+    pub template: Option<Arc<str>>,
+    /// Line ending after every line
+    pub newline: Newline,
+}
+
+impl<W: Write> M3uWriter<W> {
+    pub fn new(out: W) -> Self {
+        M3uWriter {
+            out,
+            extended: false,
+            header: true,
+            bom: false,
+            comments: Vec::new(),
+            template: None,
+            newline: Newline::default(),
+        }
+    }
+}
+
+impl<W: Write> PlaylistWriter for M3uWriter<W> {
+    fn write_playlist(&mut self, entries: &[PlaylistEntry]) -> io::Result<()> {
+        if self.bom {
+            self.out.write_all(UTF8_BOM)?;
+        }
+        let nl = self.newline.as_str();
+        if self.header {
+            for comment in &self.comments {
+                // a line break would end the comment and turn the rest into an entry
+                let comment = comment.replace(['\r', '\n'], " ");
+                write!(self.out, "# {comment}{nl}")?;
+            }
+        }
+        if self.extended && self.header {
+            write!(self.out, "#EXTM3U{nl}")?;
+        }
+        if let Some(template) = self.template.as_deref().filter(|_| self.header) {
+            self.out.write_all(template.as_bytes())?;
+            if !template.is_empty() && !template.ends_with('\n') {
+                self.out.write_all(nl.as_bytes())?;
+            }
+        }
+        for entry in entries {
+            if self.extended {
+                write!(self.out, "#EXTINF:-1,{}{nl}", entry.title)?;
+            }
+            write!(self.out, "{}{nl}", entry.path)?;
+        }
+        self.out.flush()
+    }
+}
+
+/// Writes INI style .pls playlists
+pub struct PlsWriter<W: Write> {
+    out: W,
+    /// Line ending after every line
+    pub newline: Newline,
+}
+
+impl<W: Write> PlsWriter<W> {
+    pub fn new(out: W) -> Self {
+        PlsWriter {
+            out,
+            newline: Newline::default(),
+        }
+    }
+}
+
+impl<W: Write> PlaylistWriter for PlsWriter<W> {
+    fn write_playlist(&mut self, entries: &[PlaylistEntry]) -> io::Result<()> {
+        let nl = self.newline.as_str();
+        write!(self.out, "[playlist]{nl}")?;
+        for (i, entry) in entries.iter().enumerate() {
+            let num = i + 1;
+            write!(self.out, "File{num}={}{nl}", entry.path)?;
+            write!(self.out, "Title{num}={}{nl}", entry.title)?;
+            write!(self.out, "Length{num}=-1{nl}")?;
+        }
+        write!(self.out, "NumberOfEntries={}{nl}", entries.len())?;
+        write!(self.out, "Version=2{nl}")?;
+        self.out.flush()
+    }
+}
+
+/// Write `entries` in the format chosen in `options` into a buffer, ending lines with `newline`
+pub fn render_playlist(
+    entries: &[PlaylistEntry],
+    options: &GenOptions,
+    newline: Newline,
+) -> io::Result<Vec<u8>> {
+    let mut out: Vec<u8> = Vec::new();
+    match options.format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => {
+            let mut writer = M3uWriter::new(&mut out);
+            writer.extended = options.extended;
+            writer.newline = newline;
+            writer.comments = options.comment_lines();
+            writer.template = options.template.clone();
+            writer.bom =
+                options.format == PlaylistFormat::M3u8 && (options.bom || options.windows_compat);
+            writer.write_playlist(entries)?;
+        }
+        PlaylistFormat::Pls => {
+            let mut writer = PlsWriter::new(&mut out);
+            writer.newline = newline;
+            writer.write_playlist(entries)?;
+        }
+    }
+    Ok(out)
+}
+
+/// Add `entries` to the end of an existing playlist, keeping what is already there byte for byte.
+/// A .pls numbers its entries and ends with their count, so it is written again in full.
+pub fn append_playlist(
+    existing: &[u8],
+    entries: &[PlaylistEntry],
+    options: &GenOptions,
+    newline: Newline,
+) -> io::Result<Vec<u8>> {
+    if options.format == PlaylistFormat::Pls {
+        let mut all = parse_playlist_entries(existing, options.format);
+        all.extend(entries.iter().cloned());
+        return render_playlist(&all, options, newline);
+    }
+    let mut out = existing.to_vec();
+    if !out.is_empty() && !out.ends_with(b"\n") {
+        out.extend_from_slice(newline.as_str().as_bytes());
+    }
+    let mut writer = M3uWriter::new(&mut out);
+    writer.extended = options.extended;
+    writer.header = existing.is_empty();
+    writer.comments = options.comment_lines();
+    writer.template = options.template.clone();
+    writer.newline = newline;
+    writer.write_playlist(entries)?;
+    Ok(out)
+}
+
+/// Read the entries back out of a playlist written in `format`
+pub fn read_playlist_entries(
+    path: &Path,
+    format: PlaylistFormat,
+) -> io::Result<Vec<PlaylistEntry>> {
+    Ok(parse_playlist_entries(&read_playlist_file(path)?, format))
+}
+
+/// Check if a file is a playlist: a .m3u, .m3u8 or .pls, gzipped or not
+pub fn is_playlist_file(path: &Path) -> bool {
+    has_extension(&strip_gz(path), &["m3u", "m3u8", "pls"])
+}
+
+/// Read a playlist from disk, decompressing it first when it's a .gz
+pub fn read_playlist_file(path: &Path) -> io::Result<Vec<u8>> {
+    let bytes = fs::read(path)?;
+    match has_extension(path, &["gz"]) {
+        true => gunzip(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        false => Ok(bytes),
+    }
+}
+
+/// Get the entries out of the contents of a playlist written in `format`, with either line
+/// ending
+pub fn parse_playlist_entries(bytes: &[u8], format: PlaylistFormat) -> Vec<PlaylistEntry> {
+    let contents = String::from_utf8_lossy(bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes));
+    let mut ret = Vec::new();
+    match format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => {
+            let mut title: Option<String> = None;
+            for line in contents.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                if let Some(info) = line.strip_prefix("#EXTINF:") {
+                    title = info.split_once(',').map(|(_, t)| t.to_string());
+                } else if !line.starts_with('#') {
+                    ret.push(PlaylistEntry {
+                        path: line.to_string(),
+                        title: title.take().unwrap_or_default(),
+                    });
+                }
+            }
+        }
+        PlaylistFormat::Pls => {
+            // FileN and TitleN can come in any order, so collect them by number
+            let mut numbered: Vec<(usize, PlaylistEntry)> = Vec::new();
+            for line in contents.lines().map(str::trim) {
+                let Some((key, value)) = line.split_once('=') else {
+                    continue;
+                };
+                let (is_file, num) = if let Some(num) = key.strip_prefix("File") {
+                    (true, num)
+                } else if let Some(num) = key.strip_prefix("Title") {
+                    (false, num)
+                } else {
+                    continue;
+                };
+                let Ok(num) = num.parse::<usize>() else {
+                    continue;
+                };
+                let pos = match numbered.iter().position(|(n, _)| *n == num) {
+                    Some(pos) => pos,
+                    None => {
+                        numbered.push((
+                            num,
+                            PlaylistEntry {
+                                path: String::new(),
+                                title: String::new(),
+                            },
+                        ));
+                        numbered.len() - 1
+                    }
+                };
+                if is_file {
+                    numbered[pos].1.path = value.to_string();
+                } else {
+                    numbered[pos].1.title = value.to_string();
+                }
+            }
+            numbered.sort_by_key(|(n, _)| *n);
+            ret.extend(
+                numbered
+                    .into_iter()
+                    .map(|(_, e)| e)
+                    .filter(|e| !e.path.is_empty()),
+            );
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_separator_everywhere() {
+        let entry = ".Tekken 3/Tekken 3.chd".to_string();
+        assert_eq!(
+            PathSeparator::Windows.apply(entry),
+            r".Tekken 3\Tekken 3.chd"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_separators() {
+        let entry = ".Tekken 3/Tekken 3.chd".to_string();
+        assert_eq!(PathSeparator::Auto.apply(entry.clone()), entry);
+        assert_eq!(PathSeparator::Linux.apply(entry.clone()), entry);
+        // a backslash is part of the file name on unix
+        let entry = r".Game/AC\DC.chd".to_string();
+        assert_eq!(PathSeparator::Linux.apply(entry.clone()), entry);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_separators() {
+        let entry = r".Tekken 3/sub\Tekken 3.chd".to_string();
+        assert_eq!(
+            PathSeparator::Auto.apply(entry.clone()),
+            r".Tekken 3\sub\Tekken 3.chd"
+        );
+        assert_eq!(
+            PathSeparator::Linux.apply(entry),
+            ".Tekken 3/sub/Tekken 3.chd"
+        );
+    }
+
+    #[test]
+    fn parses_separator_names() {
+        assert_eq!(
+            PathSeparator::parse("Windows"),
+            Some(PathSeparator::Windows)
+        );
+        assert_eq!(PathSeparator::parse("linux"), Some(PathSeparator::Linux));
+        assert_eq!(PathSeparator::parse("auto"), Some(PathSeparator::Auto));
+        assert_eq!(PathSeparator::parse("mac"), None);
+    }
+
+    fn entries() -> Vec<PlaylistEntry> {
+        ["Pokémon (Disc 1)", "Pokémon (Disc 2)"]
+            .iter()
+            .map(|title| PlaylistEntry {
+                path: format!(".Pokémon/{title}.chd"),
+                title: title.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bom_comes_first() {
+        let options = GenOptions {
+            format: PlaylistFormat::M3u8,
+            bom: true,
+            extended: true,
+            auto_comment: false,
+            m3u_comments: vec!["Kanto".to_string()],
+            ..GenOptions::default()
+        };
+        let out = render_playlist(&entries(), &options, Newline::Lf).unwrap();
+        let mut expected = vec![0xef, 0xbb, 0xbf];
+        expected.extend_from_slice(
+            "# Kanto\n#EXTM3U\n\
+             #EXTINF:-1,Pokémon (Disc 1)\n.Pokémon/Pokémon (Disc 1).chd\n\
+             #EXTINF:-1,Pokémon (Disc 2)\n.Pokémon/Pokémon (Disc 2).chd\n"
+                .as_bytes(),
+        );
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn no_bom_unless_asked() {
+        let options = GenOptions {
+            format: PlaylistFormat::M3u8,
+            auto_comment: false,
+            ..GenOptions::default()
+        };
+        let out = render_playlist(&entries(), &options, Newline::Lf).unwrap();
+        assert_eq!(
+            out,
+            ".Pokémon/Pokémon (Disc 1).chd\n.Pokémon/Pokémon (Disc 2).chd\n".as_bytes()
+        );
+
+        // a plain .m3u never gets one
+        let options = GenOptions {
+            format: PlaylistFormat::M3u,
+            bom: true,
+            auto_comment: false,
+            ..GenOptions::default()
+        };
+        let out = render_playlist(&entries(), &options, Newline::Lf).unwrap();
+        assert!(!out.starts_with(UTF8_BOM));
+    }
+
+    #[test]
+    fn no_bom_when_appending() {
+        let options = GenOptions {
+            format: PlaylistFormat::M3u8,
+            bom: true,
+            ..GenOptions::default()
+        };
+        let existing = render_playlist(&entries()[..1], &options, Newline::Lf).unwrap();
+        let out = append_playlist(&existing, &entries()[1..], &options, Newline::Lf).unwrap();
+        assert!(out.starts_with(UTF8_BOM));
+        assert_eq!(out.windows(3).filter(|w| *w == UTF8_BOM).count(), 1);
+    }
+}
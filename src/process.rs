@@ -0,0 +1,2011 @@
+// Description: Walk the child dirs of a parent dir, writing a .m3u for each one and moving its
+//              game files into the hidden sub-dir.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{self, Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    Conflict, DEFAULT_CONFLICT_LOG, Decision, DirOutcome, DirectoryResult, FsOp, GenError,
+    GenOptions, MoveKind, Newline, Operation, OutputEncoding, PathStatus, PlaylistEntry,
+    ProcessReport, Progress, RenameStrategy, SortOrder, SymlinkMode, TemplateVars, TuiEvent,
+    append_playlist, apply_preference, available_space, build_path_from_parts,
+    check_simulated_error, check_space, compute_digest, copy_tree, copy_with_metadata, copy_xattrs,
+    default_transaction_log_path, detect_discs, file_disc_number, find_conflict, format_size,
+    get_path_dir_name, gz_path, gzip, has_extension, is_hidden, is_retriable, is_symlink,
+    move_file_with_fallback, move_symlink, parse_checksums, parse_cue_file, parse_gdi, parse_mds,
+    parse_playlist_entries, pick_best_format, plan_child_dir, read_playlist_entries,
+    read_playlist_file, relative_path, render_checksums, render_playlist, rewrite_cue_references,
+    rewrite_gdi_references, same_filesystem, sort_by_extension_priority, sort_files,
+    time::DateTime, verify_dir, verify_path, write_conflict_log, write_file_atomic,
+    write_transaction_log,
+};
+
+/// Files that reference track files which have to move along with them
+const SHEET_EXTENSIONS: &[&str] = &["cue", "gdi", "mds"];
+
+/// Sheets that name their tracks with a path, which has to be fixed up once they're moved. A
+/// .mds only names its .mdf, which moves right along with it.
+const REWRITTEN_SHEETS: &[&str] = &["cue", "gdi"];
+
+/// Wrap a failed `fs::read_dir` with the dir it was reading
+fn read_dir_err(path: &Path, source: io::Error) -> GenError {
+    GenError::ReadDirFailed {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// Process every child dir of `parent`. Only problems with `parent` itself are returned as an
+/// error; failures inside a child dir are collected in the report so the rest still get done.
+pub fn process_parent_dir(parent: &Path, options: &GenOptions) -> Result<ProcessReport, GenError> {
+    // with --create-parent a missing parent is made first, a new one has no child dirs yet. Its
+    // transaction log goes next to the dirs that were made, so --undo can remove them again.
+    let mut report = ProcessReport::default();
+    let mut log_dir = parent.to_path_buf();
+    if options.create_parent && !parent.exists() {
+        if options.dry_run {
+            options.say(format!("WOULD CREATE DIR: {}", parent.display()));
+            return Ok(report);
+        }
+        create_missing_dirs(parent, &mut report).map_err(|source| {
+            GenError::CreateParentFailed {
+                path: parent.to_path_buf(),
+                source,
+            }
+        })?;
+        if let Some(Operation::CreateDir { path }) = report.operations.first()
+            && let Some(above) = path.parent()
+        {
+            log_dir = above.to_path_buf();
+        }
+    }
+
+    // verify that the path is valid, the parent itself is only written to for the logs
+    verify_dir(parent, false)?;
+
+    // get a list of child dirs in the parent dir
+    let started = Instant::now();
+    let mut children = collect_child_dirs(parent, 1, options, &mut report)?;
+
+    // read_dir order changes between filesystems and runs, so sort before taking a slice
+    children.sort();
+    let end = match options.limit {
+        Some(limit) => options.offset.saturating_add(limit).min(children.len()),
+        None => children.len(),
+    };
+    let children = children.get(options.offset..end).unwrap_or_default();
+    options.debug(format!(
+        "found {} child dir(s) to process in {}",
+        children.len(),
+        parent.display()
+    ));
+    options.emit(TuiEvent::DirsFound(children.to_vec()));
+    create_output_dir(options, &mut report)?;
+    if let Some(csv) = &options.csv {
+        csv.record(&report);
+    }
+
+    // write the path (sub-dir/file_name) to a .m3u file and move the files into the sub-dir.
+    // Each worker grabs the next unclaimed child dir, every child dir writes its own playlist so
+    // the workers never touch the same file.
+    let next = AtomicUsize::new(0);
+    let quit = AtomicBool::new(false);
+    let shared = Mutex::new(report);
+
+    // a dry run is all about its output, so keep the bar from drawing over it (or over the
+    // questions when asking about every dir)
+    let progress = if options.progress && !options.dry_run && options.confirm.is_none() {
+        Progress::new(children.len())
+    } else {
+        Progress::hidden()
+    };
+    // only one question can be asked at a time
+    let jobs = match options.confirm {
+        Some(_) => 1,
+        None => options.jobs.clamp(1, children.len().max(1)),
+    };
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| {
+                while let Some(curr) = children.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    // when recursing the game dir can be a few levels below the parent
+                    let curr_parent = curr.parent().unwrap_or(parent);
+                    let decision = match options.confirm {
+                        Some(confirm) => confirm(&plan_child_dir(curr_parent, curr, options)),
+                        None => Decision::Process,
+                    };
+                    let mut local = match decision {
+                        Decision::Process => {
+                            let name = get_path_dir_name(curr);
+                            progress.start_dir(&name);
+                            options.emit(TuiEvent::DirStarted(curr.clone()));
+                            let local = process_child_report(curr_parent, curr, options);
+                            progress.finish_dir(&name, local.files_moved);
+                            local
+                        }
+                        Decision::Skip => declined_report(parent, curr),
+                        Decision::Quit => {
+                            quit.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                    };
+                    // when recursing the dir's own parent isn't the one it was found in
+                    for result in &mut local.directories {
+                        result.parent = parent.to_path_buf();
+                    }
+                    if let Some(csv) = &options.csv {
+                        csv.record(&local);
+                    }
+                    if let Some(result) = local.directories.first() {
+                        options.emit(TuiEvent::DirFinished(result.clone()));
+                    }
+
+                    let mut report = shared.lock().unwrap_or_else(|e| e.into_inner());
+                    report.merge(local);
+                }
+            });
+        }
+    });
+    progress.finish();
+    let mut report = shared.into_inner().unwrap_or_else(|e| e.into_inner());
+    report.elapsed = started.elapsed();
+    if quit.load(Ordering::Relaxed) {
+        options.say("INFO: stopped, the remaining dirs were left alone".to_string());
+    }
+
+    let log_path = match &options.transaction_log {
+        Some(path) => path.clone(),
+        None => default_transaction_log_path(&log_dir),
+    };
+    write_run_logs(parent, options, &report, &log_path);
+    options.emit(TuiEvent::Done);
+    Ok(report)
+}
+
+/// Create the --output-dir (and any missing parents) before the first playlist goes in it
+pub(crate) fn create_output_dir(
+    options: &GenOptions,
+    report: &mut ProcessReport,
+) -> Result<(), GenError> {
+    let Some(output_dir) = &options.output_dir else {
+        return Ok(());
+    };
+    if output_dir.is_dir() {
+        return Ok(());
+    }
+    if options.dry_run {
+        options.say(format!("WOULD CREATE DIR: {}", output_dir.display()));
+        return Ok(());
+    }
+
+    create_missing_dirs(output_dir, report).map_err(|source| GenError::CreateOutputDirFailed {
+        path: output_dir.clone(),
+        source,
+    })
+}
+
+/// Create `dir` along with any missing dirs above it
+fn create_missing_dirs(dir: &Path, report: &mut ProcessReport) -> io::Result<()> {
+    // remember every dir create_dir_all makes so --undo can take them all away again
+    let mut missing: Vec<PathBuf> = dir
+        .ancestors()
+        .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    missing.reverse();
+    check_simulated_error(FsOp::CreateDir).and_then(|_| fs::create_dir_all(dir))?;
+    report.operations.extend(
+        missing
+            .into_iter()
+            .map(|path| Operation::CreateDir { path }),
+    );
+    Ok(())
+}
+
+/// The line written to the playlist for a data file that ends up at `location`, which is
+/// `in_dir` relative to the game dir. Playlists in an --output-dir need a path from there.
+fn entry_path(location: &Path, in_dir: &Path, options: &GenOptions) -> String {
+    if options.absolute_paths {
+        return absolute_location(location).to_string_lossy().to_string();
+    }
+    let path = match &options.output_dir {
+        Some(output_dir) => match (path::absolute(output_dir), path::absolute(location)) {
+            (Ok(from), Ok(to)) => relative_path(&from, &to),
+            _ => location.to_path_buf(),
+        },
+        None => in_dir.to_path_buf(),
+    };
+    path.to_string_lossy().to_string()
+}
+
+/// Take the --strip-prefix off the front of an entry. Also returns false when there is a prefix
+/// to strip but the entry doesn't start with it.
+fn strip_entry_prefix(path: String, options: &GenOptions) -> (String, bool) {
+    match &options.strip_prefix {
+        Some(prefix) if !prefix.is_empty() => match path.strip_prefix(prefix.as_str()) {
+            Some(rest) => (rest.to_string(), true),
+            None => (path, false),
+        },
+        _ => (path, true),
+    }
+}
+
+/// Put `prefix` in front of an entry with exactly one `/` between them
+pub(crate) fn prepend_entry_path(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches(['/', '\\']);
+    let path = path.trim_start_matches(['/', '\\']);
+    match (prefix.is_empty(), path.is_empty()) {
+        (true, _) => path.to_string(),
+        (false, true) => prefix.to_string(),
+        (false, false) => format!("{prefix}/{path}"),
+    }
+}
+
+/// Add the --prepend-path in front of an entry and switch it to the --platform-separator. Only
+/// the text written to the playlist changes, never a real path.
+fn finish_entry(path: String, options: &GenOptions) -> String {
+    let path = match &options.prepend_path {
+        Some(prefix) if !prefix.is_empty() => prepend_entry_path(prefix, &path),
+        _ => path,
+    };
+    options.platform_separator.apply(path)
+}
+
+/// Resolve where a data file ends up to an absolute path. The file usually hasn't been moved yet
+/// when its entry is built, so fall back to resolving its dir, and then to joining the parts onto
+/// the current dir.
+pub(crate) fn absolute_location(location: &Path) -> PathBuf {
+    if let Ok(path) = location.canonicalize() {
+        return path;
+    }
+    if let (Some(dir), Some(name)) = (location.parent(), location.file_name())
+        && let Ok(dir) = dir.canonicalize()
+    {
+        return dir.join(name);
+    }
+    path::absolute(location).unwrap_or_else(|_| location.to_path_buf())
+}
+
+/// Collect the child dirs of `dir` (which is `depth` levels below the parent) that should get a
+/// playlist. When recursing only dirs holding data files count, dirs that just group other dirs
+/// are walked instead. Failing to read a dir below the parent is only recorded in the report.
+fn collect_child_dirs(
+    dir: &Path,
+    depth: usize,
+    options: &GenOptions,
+    report: &mut ProcessReport,
+) -> Result<Vec<PathBuf>, GenError> {
+    let entries = fs::read_dir(dir).map_err(|source| read_dir_err(dir, source))?;
+    let mut ret: Vec<PathBuf> = Vec::new();
+    for entry in entries {
+        let curr = match entry {
+            Ok(d) => d.path(),
+            Err(e) => {
+                report.errors.push(read_dir_err(dir, e));
+                continue;
+            }
+        };
+
+        // loose files in the parent dir are not games
+        if !curr.is_dir() || (options.skips_hidden() && is_hidden(&curr)) {
+            continue;
+        }
+        if options.symlinks != SymlinkMode::Follow && is_symlink(&curr) {
+            options.warn(format!(
+                "WARNING: skipping {} (it's a symlink, see --follow-symlinks)",
+                curr.display()
+            ));
+            report.symlinks_skipped.push(curr);
+            continue;
+        }
+        if options.ignore.is_ignored(&get_path_dir_name(&curr), true) {
+            continue;
+        }
+        // a --backup dir inside the parent holds copies, not games
+        if let Some(backup) = &options.backup
+            && fs::canonicalize(&curr).ok() == fs::canonicalize(backup).ok()
+        {
+            continue;
+        }
+        if !options.recursive || is_game_dir(&curr, options) {
+            if options.wants_dir(&curr) && options.modified_since(&curr) {
+                ret.push(curr);
+            }
+            continue;
+        }
+
+        // don't follow links while walking, they can point back up the tree
+        if !is_symlink(&curr) && options.max_depth.is_none_or(|max| depth < max) {
+            match collect_child_dirs(&curr, depth + 1, options, report) {
+                Ok(dirs) => ret.extend(dirs),
+                Err(e) => report.errors.push(e),
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Check if a dir holds a game: it has data files of its own, or an earlier run already moved
+/// them into its hidden sub-dir
+fn is_game_dir(dir: &Path, options: &GenOptions) -> bool {
+    let name = get_path_dir_name(dir);
+    let sub_dir_name = options.sub_dir_name(&name);
+    if build_path_from_parts(&[dir, Path::new(&sub_dir_name)]).is_dir() {
+        return true;
+    }
+    fs::read_dir(dir).is_ok_and(|files| {
+        files
+            .filter_map(Result::ok)
+            .map(|f| f.path())
+            .any(|f| f.is_file() && options.wants_file(&f))
+    })
+}
+
+/// Write the transaction log and conflict log for a run. Neither is worth failing the run over,
+/// so problems are only warned about.
+pub(crate) fn write_run_logs(
+    parent: &Path,
+    options: &GenOptions,
+    report: &ProcessReport,
+    transaction_log: &Path,
+) {
+    // record what changed so the run can be rolled back with --undo
+    if !options.dry_run
+        && !report.operations.is_empty()
+        && let Err(e) = write_transaction_log(transaction_log, &report.operations)
+    {
+        options.warn(format!("WARNING: {e}, this run can't be undone"));
+    }
+
+    // let the user sort out the dirs that hold the same game twice
+    if !report.conflicts.is_empty() {
+        let log_path = match &options.conflict_log {
+            Some(path) => path.clone(),
+            None => build_path_from_parts(&[parent, Path::new(DEFAULT_CONFLICT_LOG)]),
+        };
+        if options.dry_run {
+            options.say(format!(
+                "WOULD CREATE FILE: {} ({} conflicted dirs)",
+                log_path.display(),
+                report.conflicts.len()
+            ));
+        } else {
+            match write_conflict_log(&log_path, &report.conflicts) {
+                Ok(()) => options.warn(format!(
+                    "WARNING: {} dirs have both .chd and .cue/.bin files, see {}",
+                    report.conflicts.len(),
+                    log_path.display()
+                )),
+                Err(e) => options.warn(format!(
+                    "WARNING: unable to write {}: {e}",
+                    log_path.display()
+                )),
+            }
+        }
+    }
+}
+
+/// Check if a child dir was already processed: it has a playlist or the hidden sub-dir
+fn is_managed(child: &Path, sub_dir: &Path, m3u_path: &Path) -> Result<bool, GenError> {
+    if sub_dir.is_dir() || m3u_path.is_file() || gz_path(m3u_path).is_file() {
+        return Ok(true);
+    }
+    let files = fs::read_dir(child).map_err(|source| read_dir_err(child, source))?;
+    Ok(files
+        .filter_map(Result::ok)
+        .any(|f| has_extension(&f.path(), &["m3u", "m3u8"])))
+}
+
+/// The first dir in `child` that isn't the sub-dir `hidden_name` (or its top level when
+/// --subdir-depth nests it)
+fn other_sub_dir(child: &Path, hidden_name: &str) -> Result<Option<PathBuf>, GenError> {
+    let ours = Path::new(hidden_name).components().next();
+    let files = fs::read_dir(child).map_err(|source| read_dir_err(child, source))?;
+    Ok(files
+        .filter_map(Result::ok)
+        .filter(|f| f.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|f| f.path())
+        .find(|p| p.file_name() != ours.map(|c| c.as_os_str())))
+}
+
+/// Process a single child dir into a report of its own, with the dir's `DirectoryResult` and
+/// any dir-level error recorded in it
+pub(crate) fn process_child_report(
+    parent: &Path,
+    child: &Path,
+    options: &GenOptions,
+) -> ProcessReport {
+    let mut local = ProcessReport {
+        dirs_scanned: 1,
+        ..ProcessReport::default()
+    };
+    let outcome = match process_child_dir(parent, child, options, &mut local) {
+        Ok(outcome) => Some(outcome),
+        Err(e) => {
+            local.errors.push(e);
+            None
+        }
+    };
+    if matches!(outcome, Some(DirOutcome::Processed | DirOutcome::Updated)) {
+        local.dirs_processed += 1;
+    }
+    local.directories.push(DirectoryResult {
+        path: child.to_path_buf(),
+        parent: parent.to_path_buf(),
+        outcome,
+        files_moved: local.files_moved,
+        m3u_path: local.playlists.first().cloned(),
+        skipped: matches!(outcome, Some(DirOutcome::Skipped)),
+        errors: local.errors.iter().map(|e| e.to_string()).collect(),
+    });
+    local
+}
+
+/// The report for a child dir the user chose not to process
+fn declined_report(parent: &Path, child: &Path) -> ProcessReport {
+    ProcessReport {
+        dirs_scanned: 1,
+        skipped_count: 1,
+        directories: vec![DirectoryResult {
+            path: child.to_path_buf(),
+            parent: parent.to_path_buf(),
+            outcome: Some(DirOutcome::Skipped),
+            files_moved: 0,
+            m3u_path: None,
+            skipped: true,
+            errors: Vec::new(),
+        }],
+        ..ProcessReport::default()
+    }
+}
+
+/// Copy a child dir to `dest/<dir name>` for --backup. The sub-dir of an earlier run isn't part
+/// of the original files, so it's left out.
+fn backup_child_dir(
+    child: &Path,
+    curr_name: &str,
+    dest: &Path,
+    options: &GenOptions,
+) -> Result<(), GenError> {
+    let backup = build_path_from_parts(&[dest, Path::new(curr_name)]);
+    if options.dry_run {
+        options.say(format!(
+            "WOULD BACK UP: {} -> {}",
+            child.display(),
+            backup.display()
+        ));
+        return Ok(());
+    }
+    let sub_dir_name = options.sub_dir_name(curr_name);
+    copy_tree(child, &backup, &[&sub_dir_name]).map_err(|source| GenError::BackupFailed {
+        src: child.to_path_buf(),
+        dst: backup.clone(),
+        source,
+    })?;
+    options.debug(format!(
+        "backed up {} to {}",
+        child.display(),
+        backup.display()
+    ));
+    Ok(())
+}
+
+/// Rename a child dir for --rename-dir, returning the dir to work on and its new name. A dry run
+/// only says what it would do and keeps working on the dir where it is.
+fn rename_child_dir(
+    child: &Path,
+    curr_name: &str,
+    strategy: &RenameStrategy,
+    options: &GenOptions,
+    report: &mut ProcessReport,
+) -> Result<(PathBuf, String), GenError> {
+    let ext = match strategy.uses_ext() {
+        true => dominant_extension(&[child], options),
+        false => String::new(),
+    };
+    let new_name = strategy
+        .apply(curr_name, &ext)
+        .map_err(|reason| GenError::RenameDirFailed {
+            src: child.to_path_buf(),
+            dst: child.to_path_buf(),
+            source: io::Error::new(io::ErrorKind::InvalidInput, reason),
+        })?;
+    if new_name == curr_name {
+        return Ok((child.to_path_buf(), new_name));
+    }
+    let new_path = child.with_file_name(&new_name);
+    if options.dry_run {
+        options.say(format!(
+            "WOULD RENAME: {} -> {}",
+            child.display(),
+            new_path.display()
+        ));
+        return Ok((child.to_path_buf(), new_name));
+    }
+
+    // only a change of case may land on a path that already exists (the same dir, on a
+    // filesystem that ignores case)
+    if new_path.exists() && !new_name.eq_ignore_ascii_case(curr_name) {
+        return Err(GenError::RenameDirFailed {
+            src: child.to_path_buf(),
+            dst: new_path,
+            source: io::Error::new(io::ErrorKind::AlreadyExists, "the new name is taken"),
+        });
+    }
+    check_simulated_error(FsOp::Rename)
+        .and_then(|_| fs::rename(child, &new_path))
+        .map_err(|source| GenError::RenameDirFailed {
+            src: child.to_path_buf(),
+            dst: new_path.clone(),
+            source,
+        })?;
+    options.say(format!(
+        "INFO: renamed {} to {}",
+        child.display(),
+        new_path.display()
+    ));
+    report.operations.push(Operation::Rename {
+        from: child.to_path_buf(),
+        to: new_path.clone(),
+    });
+    Ok((new_path, new_name))
+}
+
+/// Rename the game files in `child` after the dir (`dir_name`) for --rename-files, numbering them
+/// `<dir name> (Disc N).<ext>` when there's more than one. The disc comes from the old name when
+/// it has a marker and from the playlist order otherwise. Tracks referenced by a sheet keep
+/// their names so the sheet still finds them. Returns the files under their new names.
+fn rename_data_files(
+    child: &Path,
+    dir_name: &str,
+    data_files: Vec<PathBuf>,
+    options: &GenOptions,
+    report: &mut ProcessReport,
+) -> Vec<PathBuf> {
+    let tracks: Vec<PathBuf> = data_files
+        .iter()
+        .filter(|f| has_extension(f, SHEET_EXTENSIONS))
+        .flat_map(|sheet| sheet_tracks(sheet).unwrap_or_default())
+        .map(|track| build_path_from_parts(&[child, &track]))
+        .collect();
+    let units = data_files.iter().filter(|f| !tracks.contains(f)).count();
+
+    let mut ret: Vec<PathBuf> = Vec::new();
+    let mut position = 0;
+    for file in data_files {
+        if tracks.contains(&file) {
+            ret.push(file);
+            continue;
+        }
+        position += 1;
+        let ext = match file.extension() {
+            Some(ext) => format!(".{}", ext.to_string_lossy()),
+            None => String::new(),
+        };
+        let new_name = match units {
+            1 => format!("{dir_name}{ext}"),
+            _ => {
+                let first_pbp = options.first_pbp_name(dir_name);
+                let disc = file_disc_number(&get_path_dir_name(&file), &first_pbp)
+                    .unwrap_or(position as u32);
+                format!("{dir_name} (Disc {disc}){ext}")
+            }
+        };
+        let new_path = file.with_file_name(&new_name);
+        if new_path == file {
+            ret.push(file);
+            continue;
+        }
+        if new_path.exists() || ret.contains(&new_path) {
+            options.warn(format!(
+                "WARNING: not renaming {}, {} is in the way",
+                file.display(),
+                new_path.display()
+            ));
+            ret.push(file);
+            continue;
+        }
+        if options.dry_run {
+            options.say(format!(
+                "WOULD RENAME: {} -> {}",
+                file.display(),
+                new_path.display()
+            ));
+            // a sheet has to be read later on to find its tracks, which only works where it is
+            match has_extension(&file, SHEET_EXTENSIONS) {
+                true => ret.push(file),
+                false => ret.push(new_path),
+            }
+            continue;
+        }
+        match check_simulated_error(FsOp::Rename).and_then(|_| fs::rename(&file, &new_path)) {
+            Ok(()) => {
+                options.debug(format!(
+                    "renamed {} to {}",
+                    file.display(),
+                    new_path.display()
+                ));
+                report.operations.push(Operation::Rename {
+                    from: file,
+                    to: new_path.clone(),
+                });
+                ret.push(new_path);
+            }
+            Err(source) => {
+                report.errors.push(GenError::MoveFileFailed {
+                    src: file.clone(),
+                    dst: new_path,
+                    source,
+                });
+                ret.push(file);
+            }
+        }
+    }
+    ret
+}
+
+/// Create the .m3u file for a single child dir and move its data files into the sub-dir.
+/// Errors that only affect one file are pushed onto the report so the rest of the dir still
+/// gets processed; anything that prevents the dir from being processed at all is returned.
+pub fn process_child_dir(
+    path_to_parent: &Path,
+    child: &Path,
+    options: &GenOptions,
+    report: &mut ProcessReport,
+) -> Result<DirOutcome, GenError> {
+    let curr_name = match child.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name.to_string(),
+        None => return Err(GenError::InvalidName(child.to_path_buf())),
+    };
+
+    // the backup is taken before anything is renamed or moved, a dir without one is left alone
+    if let Some(dest) = &options.backup {
+        backup_child_dir(child, &curr_name, dest, options)?;
+    }
+
+    // renaming comes first so the playlist and the sub-dir get the new name
+    let (renamed, curr_name) = match &options.rename_dir {
+        Some(strategy) => rename_child_dir(child, &curr_name, strategy, options, report)?,
+        None => (child.to_path_buf(), curr_name),
+    };
+    let child = renamed.as_path();
+
+    // Create a hidden subdirectory string, `.Game/files` with --subdir-depth 2
+    let hidden_name = options
+        .sub_dir_path(&curr_name)
+        .to_string_lossy()
+        .to_string();
+
+    // make a path to the sub_directory
+    let sub_dir = build_path_from_parts(&[
+        path_to_parent,
+        Path::new(&curr_name),
+        Path::new(&hidden_name),
+    ]);
+
+    // Build path to the output playlist file
+    let file_name = match &options.m3u_filename {
+        Some(template) => {
+            let ext = match template.uses_ext() {
+                true => dominant_extension(&[child, &sub_dir], options),
+                false => String::new(),
+            };
+            let vars = TemplateVars {
+                dir: &curr_name,
+                ext: &ext,
+                date: &DateTime::now().date_string(),
+            };
+            template
+                .render(&vars)
+                .map_err(|reason| GenError::InvalidPlaylistName {
+                    path: child.to_path_buf(),
+                    reason,
+                })?
+        }
+        None => {
+            let mut file_name = String::new();
+            file_name.push_str(curr_name.as_str());
+            file_name.push('.');
+            file_name.push_str(options.format.extension());
+            file_name
+        }
+    };
+    let m3u_path = match &options.output_dir {
+        Some(output_dir) => build_path_from_parts(&[output_dir, Path::new(&file_name)]),
+        None => {
+            build_path_from_parts(&[path_to_parent, Path::new(&curr_name), Path::new(&file_name)])
+        }
+    };
+
+    options.debug(format!(
+        "processing {} into {}",
+        child.display(),
+        m3u_path.display()
+    ));
+
+    // verify the parent dir still exists
+    match verify_path(path_to_parent)? {
+        PathStatus::ExistsAsDirectory => (),
+        _ => return Err(GenError::PathNotFound(path_to_parent.to_path_buf())),
+    }
+
+    // the playlist and the sub-dir go in the child dir, so it has to let us change it
+    let writes_child = options.output_dir.is_none() || !options.no_move;
+    verify_dir(child, writes_child && !options.dry_run)?;
+
+    // a dir the user keeps other dirs in may not be a plain game dir
+    if options.skip_dirs_with_subdirs
+        && let Some(other) = other_sub_dir(child, &hidden_name)?
+    {
+        options.warn(format!(
+            "WARNING: skipping {} (it holds the dir {})",
+            child.display(),
+            get_path_dir_name(&other)
+        ));
+        report.skipped_count += 1;
+        return Ok(DirOutcome::Skipped);
+    }
+
+    // leave already processed dirs alone, add to them when updating or appending, or start
+    // their playlist over when overwriting
+    let managed = (options.skip_existing || options.update || options.append || options.overwrite)
+        && is_managed(child, &sub_dir, &m3u_path)?;
+    if managed && options.skip_existing {
+        options.say(format!(
+            "INFO: skipping {} (it already has a playlist)",
+            child.display()
+        ));
+        report.skipped_count += 1;
+        return Ok(DirOutcome::Skipped);
+    }
+    let updating = managed && options.update;
+    let rebuilding = managed && options.overwrite && !options.no_move;
+
+    // when appending, the playlist is kept as it is and only files it doesn't list yet are added
+    let existing = match managed && options.append && m3u_path.is_file() {
+        true => Some(
+            fs::read(&m3u_path).map_err(|source| GenError::AccessFailed {
+                path: m3u_path.clone(),
+                source,
+            })?,
+        ),
+        false => None,
+    };
+
+    // collect the data files in the current directory
+    let files = fs::read_dir(child).map_err(|source| read_dir_err(child, source))?;
+    let mut data_files: Vec<PathBuf> = Vec::new();
+    for file in files {
+        let curr_file = match file {
+            Ok(f) => f.path(),
+            Err(e) => {
+                report.errors.push(read_dir_err(child, e));
+                continue;
+            }
+        };
+
+        // Skip the file if it's anything other than our data files
+        if curr_file.is_file()
+            && options.wants_file(&curr_file)
+            && options.symlinks == SymlinkMode::Skip
+            && is_symlink(&curr_file)
+        {
+            options.warn(format!(
+                "WARNING: skipping {} (it's a symlink, see --follow-symlinks)",
+                curr_file.display()
+            ));
+            report.symlinks_skipped.push(curr_file);
+        } else if curr_file.is_file() && options.wants_file(&curr_file) {
+            options.debug(format!("picked up {}", curr_file.display()));
+            data_files.push(curr_file);
+        } else {
+            options.debug(format!("leaving {} alone", curr_file.display()));
+        }
+    }
+
+    // a dir with both formats is most likely the same game twice, so don't guess which copy
+    // the user wants unless they told us (or asked for a playlist of each, or for both in one
+    // playlist in a given order)
+    let mut secondary: Vec<PathBuf> = Vec::new();
+    if !options.group_by_extension
+        && options.extension_priority_order.is_empty()
+        && let Some(files) = find_conflict(&data_files)
+    {
+        let mut conflict = Conflict {
+            dir: child.to_path_buf(),
+            files,
+            forced: options.force,
+            kept: None,
+        };
+        if options.auto_detect_format
+            && let Some((best, kept, passed_over)) =
+                pick_best_format(data_files.clone(), &options.format_priority)
+        {
+            // the tracks of a kept .cue go with it, they just don't get entries of their own
+            let tracks = referenced_tracks(&kept);
+            secondary = passed_over
+                .into_iter()
+                .filter(|f| !tracks.contains(f))
+                .collect();
+            data_files = kept;
+            if !secondary.is_empty() && !options.archive_secondary_formats {
+                options.warn(format!(
+                    "WARNING: leaving {} files in {} alone, --auto-detect-format kept the .{best} \
+                     files",
+                    secondary.len(),
+                    child.display()
+                ));
+            }
+            conflict.kept = Some(best);
+        } else if !options.force {
+            report.conflicts.push(conflict);
+            return Ok(DirOutcome::Conflicted);
+        } else {
+            apply_preference(&mut data_files, options.prefer);
+        }
+        report.conflicts.push(conflict);
+    }
+
+    // a single game file can be loaded directly, it doesn't need a playlist
+    if options.only_multi_file && !managed && count_units(child, &data_files) == 1 {
+        options.say(format!(
+            "INFO: skipping {} (it only has one game file)",
+            child.display()
+        ));
+        report.skipped_count += 1;
+        return Ok(DirOutcome::Skipped);
+    }
+
+    let mut data_files = playlist_order(data_files, &curr_name, options);
+    if options.rename_files && !options.no_move {
+        let renamed = rename_data_files(child, &curr_name, data_files, options, report);
+        data_files = playlist_order(renamed, &curr_name, options);
+    }
+    if let Some(existing) = &existing {
+        let listed = listed_files(existing, &m3u_path, options);
+        data_files.retain(|f| {
+            let location = match options.no_move {
+                true => f.clone(),
+                false => build_path_from_parts(&[&sub_dir, Path::new(&get_path_dir_name(f))]),
+            };
+            !listed.contains(&absolute_location(&location))
+        });
+        if data_files.is_empty() {
+            options.say(format!(
+                "INFO: skipping {} (nothing new to add to its playlist)",
+                child.display()
+            ));
+            report.skipped_count += 1;
+            return Ok(DirOutcome::Skipped);
+        }
+    }
+
+    // a rebuilt playlist lists what an earlier run moved into the sub-dir along with the new
+    // files, only the new ones get moved
+    let listed_files = match rebuilding {
+        true => {
+            let mut listed = data_files.clone();
+            listed.extend(sub_dir_data_files(&sub_dir, options));
+            playlist_order(listed, &curr_name, options)
+        }
+        false => data_files.clone(),
+    };
+
+    // tracks referenced by a .cue or .gdi have to travel with it even if their extension isn't
+    // one of ours, but they don't get their own m3u entry. Nothing moves in a flat layout so they
+    // can stay where they are.
+    let track_files = match options.no_move {
+        true => Vec::new(),
+        false => find_cue_tracks(child, &data_files, options),
+    };
+
+    // moving within the filesystem is a rename, but a copy needs room for all of it. Check up
+    // front so a full disk can't leave the dir half moved.
+    if !options.no_move {
+        let to_copy: Vec<PathBuf> = data_files
+            .iter()
+            .chain(&track_files)
+            .filter(|f| options.copy || !same_filesystem(f, &sub_dir))
+            .cloned()
+            .collect();
+        check_space(&to_copy, &sub_dir)?;
+        if options.dry_run && !to_copy.is_empty() {
+            let required: u64 = to_copy
+                .iter()
+                .filter_map(|f| fs::metadata(f).ok())
+                .map(|m| m.len())
+                .sum();
+            if let Ok(available) = available_space(&sub_dir) {
+                options.say(format!(
+                    "INFO: {} needs {} and has {} free",
+                    sub_dir.display(),
+                    format_size(required),
+                    format_size(available)
+                ));
+            }
+        }
+    }
+
+    // create a sub-dir for this file (if it doesn't already exist)
+    if options.no_move {
+        // files stay where they are, so there is nothing to create
+    } else if options.dry_run {
+        options.say(format!("WOULD CREATE DIR: {}", sub_dir.display()));
+    } else {
+        // a nested sub-dir is created one level at a time, so undo removes each of them
+        let mut level = child.to_path_buf();
+        for part in Path::new(&hidden_name).components() {
+            level = build_path_from_parts(&[&level, part.as_ref()]);
+            if level.is_dir() {
+                continue;
+            }
+            check_simulated_error(FsOp::CreateDir)
+                .and_then(|_| fs::create_dir(&level))
+                .map_err(|source| GenError::CreateSubdirFailed {
+                    path: level.clone(),
+                    source,
+                })?;
+            report.operations.push(Operation::CreateDir {
+                path: level.clone(),
+            });
+        }
+    }
+
+    // keep whatever an earlier run already listed, or rebuild it from the sub-dir if the
+    // playlist went missing
+    let mut entries: Vec<PlaylistEntry> = Vec::new();
+    if updating {
+        entries = existing_entries(&m3u_path, &sub_dir, &hidden_name, &curr_name, options);
+    }
+
+    // with --one-per-disc a sheet stands in for the tracks it references
+    let referenced: Vec<PathBuf> = match options.one_per_disc {
+        true => referenced_tracks(&listed_files),
+        false => Vec::new(),
+    };
+
+    // write the data files to our playlist and move them to the sub_dir
+    let mut unstripped = 0;
+    for curr_file in listed_files.iter().filter(|f| !referenced.contains(f)) {
+        let curr_file_name = get_path_dir_name(curr_file);
+        let (location, file_m3u_line) = if options.no_move {
+            (curr_file.clone(), PathBuf::from(&curr_file_name))
+        } else {
+            (
+                build_path_from_parts(&[&sub_dir, Path::new(&curr_file_name)]),
+                build_path_from_parts(&[Path::new(&hidden_name), Path::new(&curr_file_name)]),
+            )
+        };
+        let (path, stripped) =
+            strip_entry_prefix(entry_path(&location, &file_m3u_line, options), options);
+        if !stripped {
+            unstripped += 1;
+        }
+        let entry = PlaylistEntry {
+            path: finish_entry(path, options),
+            title: entry_title(&curr_name, &curr_file_name, options),
+        };
+        if !entry_fits(&entry, &m3u_path, options, report) {
+            continue;
+        }
+        if !entries.iter().any(|e| e.path == entry.path) {
+            entries.push(entry);
+        }
+    }
+    if unstripped > 0
+        && let Some(prefix) = &options.strip_prefix
+    {
+        options.warn(format!(
+            "WARNING: --strip-prefix '{prefix}' doesn't match {unstripped} entries for {}",
+            m3u_path.display()
+        ));
+    }
+    // an updated playlist keeps the line endings it was written with
+    let existing_newline = match (&existing, updating) {
+        (Some(existing), _) => Newline::detect(existing),
+        (None, true) => read_playlist_file(&written_path(&m3u_path, options))
+            .ok()
+            .and_then(|b| Newline::detect(&b)),
+        (None, false) => None,
+    };
+    let newline = existing_newline.unwrap_or(options.newline);
+
+    // --group-by-extension writes Game_chd.m3u, Game_cue.m3u, ... instead of one playlist
+    let groups = match options.group_by_extension && existing.is_none() {
+        true => group_by_extension(&m3u_path, child, &listed_files, entries),
+        false => vec![(m3u_path.clone(), entries)],
+    };
+
+    // too many entries for one playlist get spread over Game.m3u, Game_2.m3u, ... in playlist
+    // order. Appending only ever adds to the playlist that's there.
+    let mut playlists: Vec<(PathBuf, &[PlaylistEntry])> = Vec::new();
+    for (group_path, entries) in &groups {
+        let chunks: Vec<&[PlaylistEntry]> = match options.max_files_per_m3u {
+            Some(max) if existing.is_none() && entries.len() > max => {
+                let chunks: Vec<&[PlaylistEntry]> = entries.chunks(max).collect();
+                options.warn(format!(
+                    "WARNING: splitting the {} entries for {} over {} playlists",
+                    entries.len(),
+                    group_path.display(),
+                    chunks.len()
+                ));
+                chunks
+            }
+            _ => vec![&entries[..]],
+        };
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            playlists.push((split_playlist_path(group_path, i + 1), chunk));
+        }
+    }
+    for (playlist_path, chunk) in playlists {
+        let contents = match &existing {
+            Some(existing) => append_playlist(existing, chunk, options, newline),
+            None => render_playlist(chunk, options, newline),
+        }
+        .map_err(|source| GenError::WriteEntryFailed {
+            path: playlist_path.clone(),
+            source,
+        })?;
+        let encoded = encode_playlist(&contents, &playlist_path, options)?;
+        // --compress-m3u writes Game.m3u.gz, with Game.m3u as the name inside it
+        let plain_name = get_path_dir_name(&playlist_path);
+        let playlist_path = written_path(&playlist_path, options);
+        report.playlists.push(playlist_path.clone());
+        if options.dry_run {
+            // only show what gets added to a playlist we append to
+            let added = match &existing {
+                Some(existing) if contents.starts_with(existing) => &contents[existing.len()..],
+                Some(_) => &contents[..],
+                None => {
+                    options.say(format!("WOULD CREATE FILE: {}", playlist_path.display()));
+                    &contents[..]
+                }
+            };
+            String::from_utf8_lossy(added).lines().for_each(|line| {
+                options.say(format!(
+                    "WOULD WRITE: {line} >> {}",
+                    playlist_path.display()
+                ))
+            });
+        } else {
+            let existed = playlist_path.exists();
+            // keep what a rebuilt playlist said before so --undo can put it back
+            let original = match &existing {
+                Some(existing) => Some(String::from_utf8_lossy(existing).to_string()),
+                None if rebuilding => fs::read_to_string(&playlist_path).ok(),
+                None => None,
+            };
+            let contents = match options.compress_m3u {
+                true => gzip(&encoded, &plain_name, options.compression_level),
+                false => encoded,
+            };
+            write_playlist_file(&playlist_path, &contents, options)?;
+            report.operations.push(match original {
+                Some(original) => Operation::Rewrite {
+                    path: playlist_path.clone(),
+                    original,
+                },
+                None => Operation::CreateFile {
+                    path: playlist_path.clone(),
+                    existed,
+                },
+            });
+        }
+    }
+
+    // move file
+    let outcome = match updating || rebuilding || existing.is_some() {
+        true => {
+            report.updated_count += 1;
+            DirOutcome::Updated
+        }
+        false => DirOutcome::Processed,
+    };
+    if options.no_move {
+        return Ok(outcome);
+    }
+    // a sheet is no use without its tracks, so the tracks go first and a sheet stays put when
+    // any of them couldn't be moved
+    let (sheets, others): (Vec<&PathBuf>, Vec<&PathBuf>) = data_files
+        .iter()
+        .chain(&track_files)
+        .partition(|f| has_extension(f, SHEET_EXTENSIONS));
+    let mut failed: Vec<&PathBuf> = Vec::new();
+    let mut moved: Vec<PathBuf> = Vec::new();
+    let mut moved_cues: Vec<PathBuf> = Vec::new();
+    for curr_file in others.into_iter().chain(sheets) {
+        if has_extension(curr_file, SHEET_EXTENSIONS)
+            && let Some(track) = sheet_tracks(curr_file)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|t| build_path_from_parts(&[child, &t]))
+                .find(|t| failed.contains(&t))
+        {
+            options.warn(format!(
+                "WARNING: not moving {} since {} couldn't be moved",
+                curr_file.display(),
+                track.display()
+            ));
+            continue;
+        }
+        let new_file = build_path_from_parts(&[&sub_dir, Path::new(&get_path_dir_name(curr_file))]);
+        if !move_file(curr_file, &new_file, options, report) {
+            failed.push(curr_file);
+            continue;
+        }
+        if !options.dry_run && has_extension(&new_file, REWRITTEN_SHEETS) {
+            moved_cues.push(new_file.clone());
+        }
+        moved.push(new_file);
+    }
+
+    // the sheets now live one level deeper, so fix up any references that relied on their old
+    // location (once all the tracks have been moved)
+    rewrite_moved_cues(moved_cues, child, &sub_dir, options, report);
+
+    if options.include_nfo {
+        copy_sidecars(child, &sub_dir, &track_files, options, report);
+    }
+
+    if options.archive_secondary_formats && !secondary.is_empty() {
+        archive_secondary_formats(child, &curr_name, &secondary, options, report)?;
+    }
+
+    // hash after the sheets were rewritten so the sums match what's on disk
+    if options.checksums && !moved.is_empty() {
+        write_checksums(child, &curr_name, &hidden_name, &moved, options, report);
+    }
+    Ok(outcome)
+}
+
+/// Move the files of the formats --auto-detect-format passed over into `.Game.secondary`, out of
+/// the way of the frontend but still around if the kept copy turns out to be bad
+fn archive_secondary_formats(
+    child: &Path,
+    curr_name: &str,
+    secondary: &[PathBuf],
+    options: &GenOptions,
+    report: &mut ProcessReport,
+) -> Result<(), GenError> {
+    let archive = build_path_from_parts(&[
+        child,
+        Path::new(&format!("{}.secondary", options.sub_dir_name(curr_name))),
+    ]);
+    if options.dry_run {
+        options.say(format!("WOULD CREATE DIR: {}", archive.display()));
+    } else if !archive.is_dir() {
+        check_simulated_error(FsOp::CreateDir)
+            .and_then(|_| fs::create_dir(&archive))
+            .map_err(|source| GenError::CreateSubdirFailed {
+                path: archive.clone(),
+                source,
+            })?;
+        report.operations.push(Operation::CreateDir {
+            path: archive.clone(),
+        });
+    }
+    for curr_file in secondary {
+        let new_file = build_path_from_parts(&[&archive, Path::new(&get_path_dir_name(curr_file))]);
+        move_file(curr_file, &new_file, options, report);
+    }
+    Ok(())
+}
+
+/// Copy the sidecar files of `child` (anything with an --nfo-extensions extension that isn't game
+/// data) into the sub-dir. The originals stay where they are and copies from an earlier run are
+/// kept.
+fn copy_sidecars(
+    child: &Path,
+    sub_dir: &Path,
+    track_files: &[PathBuf],
+    options: &GenOptions,
+    report: &mut ProcessReport,
+) {
+    let files = match fs::read_dir(child) {
+        Ok(files) => files,
+        Err(e) => {
+            report.errors.push(read_dir_err(child, e));
+            return;
+        }
+    };
+    let mut sidecars: Vec<PathBuf> = files
+        .filter_map(Result::ok)
+        .map(|f| f.path())
+        .filter(|f| {
+            f.is_file()
+                && has_extension(f, &options.nfo_extensions)
+                && !options.wants_file(f)
+                && !track_files.contains(f)
+        })
+        .collect();
+    sidecars.sort();
+    for src in sidecars {
+        let dst = build_path_from_parts(&[sub_dir, Path::new(&get_path_dir_name(&src))]);
+        if dst.exists() {
+            continue;
+        }
+        if options.dry_run {
+            options.say(format!(
+                "WOULD COPY: {} -> {}",
+                src.display(),
+                dst.display()
+            ));
+            continue;
+        }
+        match copy_with_metadata(&src, &dst) {
+            Ok(()) => report.operations.push(Operation::CreateFile {
+                path: dst,
+                existed: false,
+            }),
+            Err(source) => report
+                .errors
+                .push(GenError::MoveFileFailed { src, dst, source }),
+        }
+    }
+}
+
+/// Write `<dir name>.sha256` (or the extension of the --digest-algorithm) in `child` with the hash
+/// of every file moved into the sub-dir, keeping the lines of an earlier run for files that weren't
+/// moved this time
+fn write_checksums(
+    child: &Path,
+    dir_name: &str,
+    sub_dir_name: &str,
+    moved: &[PathBuf],
+    options: &GenOptions,
+    report: &mut ProcessReport,
+) {
+    let algorithm = options.digest_algorithm;
+    let path = build_path_from_parts(&[
+        child,
+        Path::new(&format!("{dir_name}.{}", algorithm.extension())),
+    ]);
+    let entry_name = |file: &Path| format!("{sub_dir_name}/{}", get_path_dir_name(file));
+    if options.dry_run {
+        options.say(format!("WOULD CREATE FILE: {}", path.display()));
+        for file in moved {
+            options.say(format!(
+                "WOULD HASH: {} >> {}",
+                entry_name(file),
+                path.display()
+            ));
+        }
+        return;
+    }
+
+    let original = fs::read_to_string(&path).ok();
+    let mut entries = original.as_deref().map(parse_checksums).unwrap_or_default();
+    for file in moved {
+        let hash = match compute_digest(file, algorithm) {
+            Ok(hash) => hash,
+            Err(source) => {
+                report.errors.push(GenError::AccessFailed {
+                    path: file.clone(),
+                    source,
+                });
+                continue;
+            }
+        };
+        let name = entry_name(file);
+        entries.retain(|(_, listed)| *listed != name);
+        entries.push((hash, name));
+    }
+    if let Err(source) = write_file_atomic(&path, render_checksums(&entries).as_bytes()) {
+        report
+            .errors
+            .push(GenError::WriteEntryFailed { path, source });
+        return;
+    }
+    report.operations.push(match original {
+        Some(original) => Operation::Rewrite { path, original },
+        None => Operation::CreateFile {
+            path,
+            existed: false,
+        },
+    });
+}
+
+/// Fix up the FILE lines of sheets moved from `old_base` to `new_base`, recording a rewrite for
+/// each one that changed
+pub(crate) fn rewrite_moved_cues(
+    cues: Vec<PathBuf>,
+    old_base: &Path,
+    new_base: &Path,
+    options: &GenOptions,
+    report: &mut ProcessReport,
+) {
+    for cue in cues {
+        let original = fs::read_to_string(&cue).ok();
+        let rewritten = match has_extension(&cue, &["gdi"]) {
+            true => rewrite_gdi_references(&cue, old_base, new_base),
+            false => rewrite_cue_references(&cue, old_base, new_base),
+        };
+        if let Err(e) = rewritten {
+            options.warn(format!("WARNING: unable to update {}: {e}", cue.display()));
+            continue;
+        }
+        if let Some(original) = original
+            && fs::read_to_string(&cue).is_ok_and(|now| now != original)
+        {
+            report.operations.push(Operation::Rewrite {
+                path: cue,
+                original,
+            });
+        }
+    }
+}
+
+/// Write a playlist through a temp file so a crash can't leave half of it behind. When the temp
+/// file can't be made (e.g. the dir only lets us replace existing files) it's written in place.
+fn write_playlist_file(
+    m3u_path: &Path,
+    contents: &[u8],
+    options: &GenOptions,
+) -> Result<(), GenError> {
+    let Err(e) = write_file_atomic(m3u_path, contents) else {
+        return Ok(());
+    };
+    options.warn(format!(
+        "WARNING: unable to replace {} atomically ({e}), writing it in place",
+        m3u_path.display()
+    ));
+    let mut outfile = fs::File::create(m3u_path).map_err(|source| GenError::CreateM3uFailed {
+        path: m3u_path.to_path_buf(),
+        source,
+    })?;
+    check_simulated_error(FsOp::Write)
+        .and_then(|_| outfile.write_all(contents))
+        .and_then(|_| outfile.sync_all())
+        .map_err(|source| GenError::WriteEntryFailed {
+            path: m3u_path.to_path_buf(),
+            source,
+        })
+}
+
+/// Most common extension among the data files in `dirs`, for the {ext} template variable. Ties
+/// go to the extension listed first in the options.
+fn dominant_extension(dirs: &[&Path], options: &GenOptions) -> String {
+    let mut counts = vec![0usize; options.extensions.len()];
+    for dir in dirs {
+        let Ok(files) = fs::read_dir(dir) else {
+            continue;
+        };
+        for file in files.filter_map(Result::ok).map(|f| f.path()) {
+            if !file.is_file() {
+                continue;
+            }
+            if let Some(i) = options
+                .extensions
+                .iter()
+                .position(|ext| has_extension(&file, &[ext]))
+            {
+                counts[i] += 1;
+            }
+        }
+    }
+    // max_by_key keeps the last maximum, so walk backwards to favour the first
+    counts
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, count)| **count > 0)
+        .max_by_key(|(_, count)| **count)
+        .map(|(i, _)| options.extensions[i].clone())
+        .unwrap_or_default()
+}
+
+/// Title used for a file's playlist entry, e.g. `Final Fantasy VII (Disc 2)`
+fn entry_title(dir_name: &str, file_name: &str, options: &GenOptions) -> String {
+    let disc = file_disc_number(file_name, &options.first_pbp_name(dir_name)).unwrap_or(1);
+    format!("{dir_name} (Disc {disc})")
+}
+
+/// Entries of a playlist written by an earlier run. When the playlist is gone the entries are
+/// rebuilt from the data files already in the sub-dir.
+fn existing_entries(
+    m3u_path: &Path,
+    sub_dir: &Path,
+    hidden_name: &str,
+    dir_name: &str,
+    options: &GenOptions,
+) -> Vec<PlaylistEntry> {
+    let written = written_path(m3u_path, options);
+    if written.is_file() {
+        match read_playlist_entries(&written, options.format) {
+            Ok(mut entries) => {
+                // the rest of a playlist that was split by --max-files-per-m3u
+                if options.max_files_per_m3u.is_some() {
+                    let parts =
+                        (2..).map(|n| written_path(&split_playlist_path(m3u_path, n), options));
+                    for part in parts.take_while(|p| p.is_file()) {
+                        entries.extend(
+                            read_playlist_entries(&part, options.format).unwrap_or_default(),
+                        );
+                    }
+                }
+                return entries;
+            }
+            Err(e) => options.warn(format!(
+                "WARNING: unable to read {}: {e}",
+                m3u_path.display()
+            )),
+        }
+    }
+    let Ok(files) = fs::read_dir(sub_dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = files
+        .filter_map(Result::ok)
+        .map(|f| f.path())
+        .filter(|f| f.is_file() && has_extension(f, &options.extensions))
+        .map(|f| get_path_dir_name(&f))
+        .collect();
+    names.sort();
+    names
+        .iter()
+        .map(|name| PlaylistEntry {
+            path: finish_entry(
+                strip_entry_prefix(
+                    entry_path(
+                        &build_path_from_parts(&[sub_dir, Path::new(name)]),
+                        &build_path_from_parts(&[Path::new(hidden_name), Path::new(name)]),
+                        options,
+                    ),
+                    options,
+                )
+                .0,
+                options,
+            ),
+            title: entry_title(dir_name, name, options),
+        })
+        .collect()
+}
+
+/// Put the files in playlist order. read_dir order depends on the filesystem, so they're sorted
+/// first, and since frontends boot the first entry multi-disc games are put in disc order on top
+/// of that. --extension-priority-order groups them by extension last.
+fn playlist_order(mut files: Vec<PathBuf>, dir_name: &str, options: &GenOptions) -> Vec<PathBuf> {
+    sort_files(&mut files, options.sort);
+    let mut files: Vec<PathBuf> = match options.sort {
+        SortOrder::None => files,
+        _ => detect_discs(&files, &options.first_pbp_name(dir_name))
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect(),
+    };
+    if !options.extension_priority_order.is_empty() {
+        sort_by_extension_priority(&mut files, &options.extension_priority_order);
+    }
+    files
+}
+
+/// The data files an earlier run moved into `sub_dir`
+fn sub_dir_data_files(sub_dir: &Path, options: &GenOptions) -> Vec<PathBuf> {
+    let Ok(files) = fs::read_dir(sub_dir) else {
+        return Vec::new();
+    };
+    files
+        .filter_map(Result::ok)
+        .map(|f| f.path())
+        .filter(|f| f.is_file() && options.wants_file(f))
+        .collect()
+}
+
+/// Encode a rendered playlist in the --output-encoding a line at a time. A line with a character
+/// the encoding doesn't have is warned about, then kept as UTF-8 with --encoding-fallback or
+/// fails the playlist without it.
+fn encode_playlist(
+    contents: &[u8],
+    m3u_path: &Path,
+    options: &GenOptions,
+) -> Result<Vec<u8>, GenError> {
+    let encoding = options.output_encoding;
+    if encoding == OutputEncoding::Utf8 {
+        return Ok(contents.to_vec());
+    }
+    let mut ret = Vec::with_capacity(contents.len());
+    for line in String::from_utf8_lossy(contents).split_inclusive('\n') {
+        match encoding.encode(line) {
+            Ok(bytes) => ret.extend(bytes),
+            Err(c) => {
+                let entry = line.trim_end_matches(['\r', '\n']);
+                if !options.encoding_fallback {
+                    return Err(GenError::WriteEntryFailed {
+                        path: m3u_path.to_path_buf(),
+                        source: io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("'{c}' in {entry} can't be written in {}", encoding.name()),
+                        ),
+                    });
+                }
+                options.warn(format!(
+                    "WARNING: '{c}' in {entry} can't be written in {}, writing the line as UTF-8",
+                    encoding.name()
+                ));
+                ret.extend_from_slice(line.as_bytes());
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Check an entry against --max-m3u-line-length. A longer one is warned about, or with
+/// --error-on-long-lines recorded as an error and left out of the playlist.
+fn entry_fits(
+    entry: &PlaylistEntry,
+    m3u_path: &Path,
+    options: &GenOptions,
+    report: &mut ProcessReport,
+) -> bool {
+    let Some(max) = options.max_m3u_line_length else {
+        return true;
+    };
+    let length = entry.path.chars().count();
+    if length <= max {
+        return true;
+    }
+    let e = GenError::EntryTooLong {
+        path: m3u_path.to_path_buf(),
+        entry: entry.path.clone(),
+        length,
+        max,
+    };
+    if options.error_on_long_lines {
+        report.errors.push(e);
+        return false;
+    }
+    options.warn(format!("WARNING: {e}"));
+    true
+}
+
+/// Where the playlist `m3u_path` ends up on disk, which is `m3u_path.gz` with --compress-m3u
+fn written_path(m3u_path: &Path, options: &GenOptions) -> PathBuf {
+    match options.compress_m3u {
+        true => gz_path(m3u_path),
+        false => m3u_path.to_path_buf(),
+    }
+}
+
+/// Split the entries into one playlist per extension, named `<name>_<ext>.<format>`. The tracks
+/// of a .cue or .gdi in the dir are left out since they're loaded through the sheet.
+fn group_by_extension(
+    m3u_path: &Path,
+    child: &Path,
+    data_files: &[PathBuf],
+    entries: Vec<PlaylistEntry>,
+) -> Vec<(PathBuf, Vec<PlaylistEntry>)> {
+    let tracks: Vec<String> = data_files
+        .iter()
+        .filter(|f| has_extension(f, SHEET_EXTENSIONS))
+        .flat_map(|sheet| sheet_tracks(sheet).unwrap_or_default())
+        .map(|track| get_path_dir_name(&build_path_from_parts(&[child, &track])))
+        .collect();
+    let stem = m3u_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let format_ext = m3u_path
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut ret: Vec<(PathBuf, Vec<PlaylistEntry>)> = Vec::new();
+    for entry in entries {
+        // the entry may use backslashes (--windows-paths), so take the name off the end by hand
+        let name = entry.path.rsplit(['/', '\\']).next().unwrap_or_default();
+        if tracks.iter().any(|t| t == name) {
+            continue;
+        }
+        let ext = Path::new(name)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let path = m3u_path.with_file_name(format!("{stem}_{ext}.{format_ext}"));
+        match ret.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, group)) => group.push(entry),
+            None => ret.push((path, vec![entry])),
+        }
+    }
+    ret
+}
+
+/// The path of part `n` of a split playlist: the playlist itself for the first part and
+/// `<name>_<n>.<ext>` after that
+fn split_playlist_path(m3u_path: &Path, n: usize) -> PathBuf {
+    if n == 1 {
+        return m3u_path.to_path_buf();
+    }
+    let stem = m3u_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match m3u_path.extension() {
+        Some(ext) => format!("{stem}_{n}.{}", ext.to_string_lossy()),
+        None => format!("{stem}_{n}"),
+    };
+    m3u_path.with_file_name(name)
+}
+
+/// Where the entries of an existing playlist point, resolved the same way as --absolute-paths
+/// so relative and absolute entries can be compared
+fn listed_files(contents: &[u8], m3u_path: &Path, options: &GenOptions) -> Vec<PathBuf> {
+    let m3u_dir = m3u_path.parent().unwrap_or(Path::new(""));
+    parse_playlist_entries(contents, options.format)
+        .iter()
+        .map(|entry| absolute_location(&build_path_from_parts(&[m3u_dir, Path::new(&entry.path)])))
+        .collect()
+}
+
+/// Read the tracks a .cue sheet, .gdi file or .mds descriptor references
+fn sheet_tracks(sheet: &Path) -> Result<Vec<PathBuf>, String> {
+    if has_extension(sheet, &["gdi"]) {
+        parse_gdi(sheet).map_err(|e| e.to_string())
+    } else if has_extension(sheet, &["mds"]) {
+        parse_mds(sheet).map_err(|e| e.to_string())
+    } else {
+        parse_cue_file(sheet).map_err(|e| e.to_string())
+    }
+}
+
+/// The tracks referenced by the sheets among `files`, next to the sheet that references them
+pub(crate) fn referenced_tracks(files: &[PathBuf]) -> Vec<PathBuf> {
+    files
+        .iter()
+        .filter(|f| has_extension(f, SHEET_EXTENSIONS))
+        .flat_map(|sheet| {
+            let dir = sheet.parent().unwrap_or(Path::new(""));
+            sheet_tracks(sheet)
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |track| build_path_from_parts(&[dir, &track]))
+        })
+        .collect()
+}
+
+/// Count the game files in a dir, where a .cue (or .gdi) and the tracks it references count as
+/// one
+fn count_units(child: &Path, data_files: &[PathBuf]) -> usize {
+    let tracks: Vec<PathBuf> = data_files
+        .iter()
+        .filter(|f| has_extension(f, SHEET_EXTENSIONS))
+        .filter_map(|sheet| sheet_tracks(sheet).ok())
+        .flatten()
+        .map(|track| build_path_from_parts(&[child, &track]))
+        .collect();
+    data_files.iter().filter(|f| !tracks.contains(f)).count()
+}
+
+/// Find the files referenced by the .cue sheets and .gdi files in `data_files` that aren't
+/// already in it. Missing tracks are warned about since the sheet won't play without them.
+pub(crate) fn find_cue_tracks(
+    child: &Path,
+    data_files: &[PathBuf],
+    options: &GenOptions,
+) -> Vec<PathBuf> {
+    let mut ret: Vec<PathBuf> = Vec::new();
+    for cue in data_files
+        .iter()
+        .filter(|f| has_extension(f, SHEET_EXTENSIONS))
+    {
+        let tracks = match sheet_tracks(cue) {
+            Ok(t) => t,
+            Err(e) => {
+                options.warn(format!("WARNING: unable to read {}: {e}", cue.display()));
+                continue;
+            }
+        };
+        for track in tracks {
+            let track_path = build_path_from_parts(&[child, &track]);
+            if !track_path.is_file() {
+                options.warn(format!(
+                    "WARNING: {} references {} which does not exist",
+                    cue.display(),
+                    track.display()
+                ));
+                continue;
+            }
+
+            // only pick up tracks that live right next to the sheet
+            if track_path.parent() != Some(child)
+                || data_files.contains(&track_path)
+                || ret.contains(&track_path)
+            {
+                continue;
+            }
+            ret.push(track_path);
+        }
+    }
+    ret
+}
+
+/// Move (or with --copy, copy) a single data file, recording the outcome in the report.
+/// Returns whether the file is now in the sub-dir.
+pub(crate) fn move_file(
+    src: &Path,
+    dst: &Path,
+    options: &GenOptions,
+    report: &mut ProcessReport,
+) -> bool {
+    if options.copy {
+        return copy_file(src, dst, options, report);
+    }
+    if options.symlinks == SymlinkMode::Skip && is_symlink(src) {
+        options.warn(format!(
+            "WARNING: not moving {} (it's a symlink, see --follow-symlinks)",
+            src.display()
+        ));
+        report.symlinks_skipped.push(src.to_path_buf());
+        return false;
+    }
+    let size = fs::metadata(src).map(|m| m.len()).unwrap_or(0);
+    if options.dry_run {
+        options.say(format!(
+            "WOULD MOVE: {} -> {}",
+            src.display(),
+            dst.display()
+        ));
+        report.record_move(src, size);
+        return true;
+    }
+    // a moved link would point somewhere else from the sub-dir
+    if is_symlink(src) {
+        return match move_symlink(src, dst, options.symlinks) {
+            Ok(()) => {
+                report.record_move(src, size);
+                report.operations.push(Operation::Rename {
+                    from: src.to_path_buf(),
+                    to: dst.to_path_buf(),
+                });
+                true
+            }
+            Err(source) => {
+                report.errors.push(GenError::MoveFileFailed {
+                    src: src.to_path_buf(),
+                    dst: dst.to_path_buf(),
+                    source,
+                });
+                false
+            }
+        };
+    }
+    let mut attempt = 0;
+    let moved = loop {
+        match move_file_with_fallback(src, dst, options.preserve_xattr) {
+            Err(e) if attempt < options.retry_count && is_retriable(&e) => {
+                attempt += 1;
+                options.warn(format!(
+                    "WARNING: unable to move {} ({e}), retrying ({attempt}/{})",
+                    src.display(),
+                    options.retry_count
+                ));
+                thread::sleep(Duration::from_millis(options.retry_delay_ms));
+            }
+            result => break result,
+        }
+    };
+    match moved {
+        Ok(kind) => {
+            if kind == MoveKind::Copied {
+                options.say(format!(
+                    "INFO: {} is on a different filesystem, copied it to {} and removed the \
+                     original",
+                    src.display(),
+                    dst.display()
+                ));
+            }
+            report.record_move(src, size);
+            report.operations.push(Operation::Rename {
+                from: src.to_path_buf(),
+                to: dst.to_path_buf(),
+            });
+            true
+        }
+        Err(source) => {
+            report.errors.push(GenError::MoveFileFailed {
+                src: src.to_path_buf(),
+                dst: dst.to_path_buf(),
+                source,
+            });
+            false
+        }
+    }
+}
+
+/// Copy a single data file into the sub-dir, leaving the original alone. Copies left by an
+/// earlier run are kept unless --overwrite is given.
+fn copy_file(src: &Path, dst: &Path, options: &GenOptions, report: &mut ProcessReport) -> bool {
+    let existed = dst.exists();
+    if existed && !options.overwrite {
+        options.say(format!(
+            "INFO: {} already exists, not copying it again",
+            dst.display()
+        ));
+        return true;
+    }
+    let size = fs::metadata(src).map(|m| m.len()).unwrap_or(0);
+    if options.dry_run {
+        options.say(format!(
+            "WOULD COPY: {} -> {}",
+            src.display(),
+            dst.display()
+        ));
+        report.record_move(src, size);
+        return true;
+    }
+    let copied = copy_with_metadata(src, dst).and_then(|_| match options.preserve_xattr {
+        true => copy_xattrs(src, dst),
+        false => Ok(()),
+    });
+    match copied {
+        Ok(()) => {
+            report.record_move(src, size);
+            report.operations.push(Operation::CreateFile {
+                path: dst.to_path_buf(),
+                existed,
+            });
+            true
+        }
+        Err(source) => {
+            report.errors.push(GenError::MoveFileFailed {
+                src: src.to_path_buf(),
+                dst: dst.to_path_buf(),
+                source,
+            });
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PathSeparator, PlaylistFormat, testing::TempDir};
+
+    #[test]
+    fn prepends_with_one_separator() {
+        let cases = [
+            ("/mnt/roms", ".Game/Game.chd", "/mnt/roms/.Game/Game.chd"),
+            ("/mnt/roms/", ".Game/Game.chd", "/mnt/roms/.Game/Game.chd"),
+            ("/mnt/roms//", "/.Game/Game.chd", "/mnt/roms/.Game/Game.chd"),
+            (r"D:\roms\", ".Game/Game.chd", r"D:\roms/.Game/Game.chd"),
+            ("roms", "Game.chd", "roms/Game.chd"),
+            ("", ".Game/Game.chd", ".Game/Game.chd"),
+            ("/mnt/roms", "", "/mnt/roms"),
+        ];
+        for (prefix, path, expected) in cases {
+            assert_eq!(
+                prepend_entry_path(prefix, path),
+                expected,
+                "{prefix:?} + {path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn prepend_path_then_separator() {
+        let options = GenOptions {
+            prepend_path: Some(r"D:\roms\".to_string()),
+            platform_separator: PathSeparator::Windows,
+            ..GenOptions::default()
+        };
+        let entry = finish_entry(".Game/Game.chd".to_string(), &options);
+        assert_eq!(entry, r"D:\roms\.Game\Game.chd");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn prepend_path_keeps_slashes_on_unix() {
+        let options = GenOptions {
+            prepend_path: Some("/mnt/roms/".to_string()),
+            ..GenOptions::default()
+        };
+        let entry = finish_entry(".Game/Game.chd".to_string(), &options);
+        assert_eq!(entry, "/mnt/roms/.Game/Game.chd");
+    }
+
+    #[test]
+    fn no_move_only_writes_the_playlist() {
+        let tmp = TempDir::new("no-move");
+        tmp.write("Game/Game (Disc 1).chd", b"one");
+        tmp.write("Game/Game (Disc 2).chd", b"two");
+        let options = GenOptions {
+            no_move: true,
+            auto_comment: false,
+            ..GenOptions::default()
+        };
+        let mut report = ProcessReport::default();
+        let child = tmp.path().join("Game");
+        process_child_dir(tmp.path(), &child, &options, &mut report).unwrap();
+
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(
+            tmp.tree(),
+            [
+                "Game",
+                "Game/Game (Disc 1).chd",
+                "Game/Game (Disc 2).chd",
+                "Game/Game.m3u"
+            ]
+            .map(PathBuf::from)
+        );
+        let playlist = fs::read_to_string(child.join("Game.m3u")).unwrap();
+        assert_eq!(playlist, "Game (Disc 1).chd\nGame (Disc 2).chd\n");
+        assert_eq!(fs::read(child.join("Game (Disc 1).chd")).unwrap(), b"one");
+        // the playlist is the only change
+        assert_eq!(
+            report.operations,
+            [Operation::CreateFile {
+                path: child.join("Game.m3u"),
+                existed: false
+            }]
+        );
+    }
+
+    #[test]
+    fn bom_starts_the_written_file() {
+        let tmp = TempDir::new("bom");
+        tmp.write("Game/Game.chd", b"");
+        let options = GenOptions {
+            format: PlaylistFormat::M3u8,
+            bom: true,
+            auto_comment: false,
+            ..GenOptions::default()
+        };
+        let mut report = ProcessReport::default();
+        let child = tmp.path().join("Game");
+        process_child_dir(tmp.path(), &child, &options, &mut report).unwrap();
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(
+            fs::read(child.join("Game.m3u8")).unwrap(),
+            b"\xEF\xBB\xBF.Game/Game.chd\n"
+        );
+    }
+
+    #[test]
+    fn gdi_tracks_move_with_the_gdi() {
+        let tmp = TempDir::new("gdi-move");
+        tmp.write(
+            "Crazy Taxi/Crazy Taxi.gdi",
+            b"3\n\
+              1 0 4 2352 track01.bin 0\n\
+              2 756 0 2352 track02.raw 0\n\
+              3 45000 4 2352 track03.bin 0\n",
+        );
+        for track in ["track01.bin", "track02.raw", "track03.bin"] {
+            tmp.write(&format!("Crazy Taxi/{track}"), b"");
+        }
+        let options = GenOptions {
+            auto_comment: false,
+            ..GenOptions::default()
+        };
+        let mut report = ProcessReport::default();
+        let child = tmp.path().join("Crazy Taxi");
+        process_child_dir(tmp.path(), &child, &options, &mut report).unwrap();
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+
+        let sub_dir = child.join(".Crazy Taxi");
+        for file in [
+            "Crazy Taxi.gdi",
+            "track01.bin",
+            "track02.raw",
+            "track03.bin",
+        ] {
+            assert!(sub_dir.join(file).is_file(), "{file} wasn't moved");
+        }
+        // the .raw track isn't a data file of its own, so only the .bin tracks get listed too
+        let playlist = fs::read_to_string(child.join("Crazy Taxi.m3u")).unwrap();
+        assert_eq!(
+            playlist,
+            ".Crazy Taxi/Crazy Taxi.gdi\n.Crazy Taxi/track01.bin\n.Crazy Taxi/track03.bin\n"
+        );
+    }
+}
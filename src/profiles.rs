@@ -0,0 +1,25 @@
+// Description: Extension presets for common retro systems, selected with --profile.
+
+/// Names accepted by `profile_extensions`
+pub const PROFILE_NAMES: &[&str] = &["ps1", "ps2", "saturn", "dreamcast", "psp", "3ds", "wii"];
+
+/// Get the extensions used by a system's disc images
+pub fn profile_extensions(name: &str) -> Option<&'static [&'static str]> {
+    match name.to_lowercase().as_str() {
+        "ps1" | "psx" => Some(&["cue", "bin", "chd"]),
+        "ps2" => Some(&["iso", "chd", "cso"]),
+        "saturn" => Some(&["cue", "bin", "chd", "mds", "mdf"]),
+        "dreamcast" | "dc" => Some(&["gdi", "cdi", "chd"]),
+        "psp" => Some(&["iso", "cso", "pbp"]),
+        "3ds" => Some(&["3ds", "cia", "cci"]),
+        "wii" => Some(&["iso", "wbfs", "rvz", "wia"]),
+        _ => None,
+    }
+}
+
+/// Extension list for a profile with any extra extensions added on the end
+pub fn profile_with_extras(profile: &[&str], extra: Vec<String>) -> Vec<String> {
+    let mut ret: Vec<String> = profile.iter().map(|e| e.to_string()).collect();
+    ret.extend(extra.into_iter().filter(|e| !profile.contains(&e.as_str())));
+    ret
+}
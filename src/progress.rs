@@ -0,0 +1,125 @@
+// Description: Progress display for long runs: a bar counting finished child dirs and a spinner
+//              line naming the dirs being worked on.
+
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::Mutex,
+};
+
+/// Frames of the spinner shown in front of the current dir names
+const SPINNER: &[char] = &['|', '/', '-', '\\'];
+
+/// Width of the bar, in characters
+const BAR_WIDTH: usize = 30;
+
+#[derive(Debug, Default)]
+struct State {
+    total: usize,
+    done: usize,
+    files_moved: usize,
+    active: Vec<String>,
+    frame: usize,
+    drawn: bool,
+}
+
+/// Progress bar drawn on stderr. Every update redraws under a lock so several workers can
+/// report at once without garbling the output.
+#[derive(Debug)]
+pub struct Progress {
+    state: Mutex<State>,
+    hidden: bool,
+}
+
+impl Progress {
+    /// A progress display for `total` child dirs, hidden when stderr isn't a terminal
+    pub fn new(total: usize) -> Self {
+        Progress {
+            state: Mutex::new(State {
+                total,
+                ..State::default()
+            }),
+            hidden: !io::stderr().is_terminal(),
+        }
+    }
+
+    /// A progress display that never draws anything
+    pub fn hidden() -> Self {
+        Progress {
+            state: Mutex::new(State::default()),
+            hidden: true,
+        }
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// A worker started on the child dir `name`
+    pub fn start_dir(&self, name: &str) {
+        self.update(|state| state.active.push(name.to_string()));
+    }
+
+    /// A worker finished the child dir `name`, having moved `files_moved` files
+    pub fn finish_dir(&self, name: &str, files_moved: usize) {
+        self.update(|state| {
+            if let Some(pos) = state.active.iter().position(|n| n == name) {
+                state.active.remove(pos);
+            }
+            state.done += 1;
+            state.files_moved += files_moved;
+        });
+    }
+
+    /// Remove the progress display from the terminal
+    pub fn finish(&self) {
+        if self.hidden {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.drawn {
+            let mut err = io::stderr().lock();
+            let _ = write!(err, "\r\x1b[2K\x1b[1A\x1b[2K");
+            let _ = err.flush();
+            state.drawn = false;
+        }
+    }
+
+    fn update<F: FnOnce(&mut State)>(&self, f: F) {
+        if self.hidden {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut state);
+        state.frame = (state.frame + 1) % SPINNER.len();
+
+        let filled = match state.total {
+            0 => BAR_WIDTH,
+            total => state.done * BAR_WIDTH / total,
+        };
+        let bar = format!(
+            "[{}{}] {}/{} dirs",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            state.done,
+            state.total
+        );
+        let current = match state.active.len() {
+            0 => "waiting".to_string(),
+            1..=3 => state.active.join(", "),
+            n => format!("{} and {} more", state.active[..3].join(", "), n - 3),
+        };
+        let spinner = format!(
+            "{} {current} ({} files moved)",
+            SPINNER[state.frame], state.files_moved
+        );
+
+        // go back over the two lines drawn last time
+        let mut err = io::stderr().lock();
+        if state.drawn {
+            let _ = write!(err, "\r\x1b[2K\x1b[1A\x1b[2K");
+        }
+        let _ = write!(err, "{bar}\n{spinner}");
+        let _ = err.flush();
+        state.drawn = true;
+    }
+}
@@ -0,0 +1,95 @@
+// Description: Naming strategies for --rename-dir, which give inconsistently named game dirs
+//              (`tekken_3`, `tekken-3`) one canonical name before anything else is done to them.
+
+use crate::{NameTemplate, TemplateVars, time::DateTime};
+
+/// Articles No-Intro moves to the end of the title
+const ARTICLES: [&str; 3] = ["The", "A", "An"];
+
+/// How to rename a game dir
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameStrategy {
+    /// `tekken_3` and `tekken-3` become `Tekken 3`
+    SnakeToTitle,
+    /// `The Legend of Zelda(USA)` becomes `Legend of Zelda, The (USA)`
+    NoIntro,
+    /// A name template with the same variables as --m3u-filename
+    Custom(NameTemplate),
+}
+
+impl RenameStrategy {
+    /// Parse the strategy given to --rename-dir: `snake-to-title`, `nointro` or
+    /// `custom:TEMPLATE`
+    pub fn parse(s: &str) -> Result<RenameStrategy, String> {
+        if let Some(template) = s.strip_prefix("custom:") {
+            return NameTemplate::parse(template).map(RenameStrategy::Custom);
+        }
+        match s.to_lowercase().as_str() {
+            "snake-to-title" => Ok(RenameStrategy::SnakeToTitle),
+            "nointro" | "no-intro" => Ok(RenameStrategy::NoIntro),
+            _ => Err(format!(
+                "unknown rename strategy '{s}' (expected snake-to-title, nointro or \
+                 custom:TEMPLATE)"
+            )),
+        }
+    }
+
+    /// Check if the strategy needs the dominant extension, which takes a look at the files
+    pub fn uses_ext(&self) -> bool {
+        matches!(self, RenameStrategy::Custom(template) if template.uses_ext())
+    }
+
+    /// The new name for a dir called `name`, `ext` being its most common data file extension
+    pub fn apply(&self, name: &str, ext: &str) -> Result<String, String> {
+        match self {
+            RenameStrategy::SnakeToTitle => Ok(snake_to_title(name)),
+            RenameStrategy::NoIntro => Ok(no_intro(name)),
+            RenameStrategy::Custom(template) => template.render(&TemplateVars {
+                dir: name,
+                ext,
+                date: &DateTime::now().date_string(),
+            }),
+        }
+    }
+}
+
+/// Upper case the first letter of a word, leaving the rest as it is so `III` or `DX` survive
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Turn underscores and hyphens into spaces and capitalize every word
+fn snake_to_title(name: &str) -> String {
+    name.split(['_', '-', ' '])
+        .filter(|word| !word.is_empty())
+        .map(capitalize)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Tidy a name up the way No-Intro names are written: single spaces, a space before every
+/// `(...)`/`[...]` tag and a leading article moved to the end of the title
+fn no_intro(name: &str) -> String {
+    let spaced = name.replace('_', " ").replace('(', " (").replace('[', " [");
+    let spaced = spaced.split_whitespace().collect::<Vec<_>>().join(" ");
+    let tags_at = [" (", " ["]
+        .iter()
+        .filter_map(|tag| spaced.find(tag))
+        .min()
+        .unwrap_or(spaced.len());
+    let (title, tags) = spaced.split_at(tags_at);
+
+    let title = match title.split_once(' ') {
+        Some((article, rest))
+            if ARTICLES.iter().any(|a| a.eq_ignore_ascii_case(article)) && !rest.is_empty() =>
+        {
+            format!("{rest}, {}", capitalize(&article.to_lowercase()))
+        }
+        _ => title.to_string(),
+    };
+    format!("{title}{tags}")
+}
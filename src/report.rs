@@ -0,0 +1,247 @@
+// Description: Summary of what a run did.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{Conflict, GenError, Json, Operation};
+
+/// Totals collected while processing a parent dir
+#[derive(Debug, Default)]
+pub struct ProcessReport {
+    /// Child dirs found in the parent dir (after filtering)
+    pub dirs_scanned: usize,
+    /// Child dirs that were processed (or updated) without a dir-level failure
+    pub dirs_processed: usize,
+    /// Data files moved (or copied) into a sub-dir, or that would have been in a dry run
+    pub files_moved: usize,
+    /// Files moved, by lowercase extension
+    pub files_by_extension: BTreeMap<String, usize>,
+    /// Total size of the files moved
+    pub bytes_moved: u64,
+    /// How long the run took
+    pub elapsed: Duration,
+    /// Child dirs left alone because they already had a playlist (--skip-existing)
+    pub skipped_count: usize,
+    /// Already processed child dirs that had new files added (--update)
+    pub updated_count: usize,
+    /// Child dirs that hold both a .chd and a .cue/.bin set
+    pub conflicts: Vec<Conflict>,
+    /// Symlinked dirs and files that were left alone
+    pub symlinks_skipped: Vec<PathBuf>,
+    /// Playlists written (or that would have been)
+    pub playlists: Vec<PathBuf>,
+    /// What happened to each child dir, in the order they finished
+    pub directories: Vec<DirectoryResult>,
+    /// Every change made to the filesystem, in the order it was made
+    pub operations: Vec<Operation>,
+    /// Every error encountered, in the order they happened
+    pub errors: Vec<GenError>,
+}
+
+/// What happened to a single child dir
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirOutcome {
+    /// A new playlist was written
+    Processed,
+    /// The dir was left alone
+    Skipped,
+    /// An existing playlist had new files added to it
+    Updated,
+    /// The dir has both a .chd and a .cue/.bin set and was left alone
+    Conflicted,
+}
+
+impl DirOutcome {
+    /// Name used in reports
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DirOutcome::Processed => "processed",
+            DirOutcome::Skipped => "skipped",
+            DirOutcome::Updated => "updated",
+            DirOutcome::Conflicted => "conflicted",
+        }
+    }
+}
+
+/// The result for one child dir, for callers that want more than the totals
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryResult {
+    /// The child dir
+    pub path: PathBuf,
+    /// The parent dir it was found in
+    pub parent: PathBuf,
+    /// What happened to it, `None` when it failed
+    pub outcome: Option<DirOutcome>,
+    /// Data files moved (or copied) into its sub-dir
+    pub files_moved: usize,
+    /// The playlist written for it
+    pub m3u_path: Option<PathBuf>,
+    /// Left alone because it was already processed
+    pub skipped: bool,
+    /// Everything that went wrong in it
+    pub errors: Vec<String>,
+}
+
+impl DirectoryResult {
+    /// The result as JSON
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("path", self.path.to_string_lossy().as_ref().into()),
+            ("parent", self.parent.to_string_lossy().as_ref().into()),
+            ("outcome", self.outcome.map(|o| o.as_str()).into()),
+            ("files_moved", self.files_moved.into()),
+            (
+                "m3u_path",
+                self.m3u_path
+                    .as_ref()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .into(),
+            ),
+            ("skipped", self.skipped.into()),
+            (
+                "errors",
+                Json::Array(self.errors.iter().map(|e| e.as_str().into()).collect()),
+            ),
+        ])
+    }
+}
+
+impl ProcessReport {
+    /// Add the totals from another report (e.g. one filled in by a worker thread, or the report
+    /// of another parent dir) to this one
+    pub fn merge(&mut self, other: ProcessReport) {
+        self.dirs_scanned += other.dirs_scanned;
+        self.dirs_processed += other.dirs_processed;
+        self.files_moved += other.files_moved;
+        for (ext, count) in other.files_by_extension {
+            *self.files_by_extension.entry(ext).or_default() += count;
+        }
+        self.bytes_moved += other.bytes_moved;
+        self.elapsed += other.elapsed;
+        self.skipped_count += other.skipped_count;
+        self.updated_count += other.updated_count;
+        self.conflicts.extend(other.conflicts);
+        self.symlinks_skipped.extend(other.symlinks_skipped);
+        self.playlists.extend(other.playlists);
+        self.directories.extend(other.directories);
+        self.operations.extend(other.operations);
+        self.errors.extend(other.errors);
+    }
+
+    /// Count a file of `size` bytes that was moved (or copied) into a sub-dir
+    pub fn record_move(&mut self, file: &Path, size: u64) {
+        let ext = file
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        self.files_moved += 1;
+        *self.files_by_extension.entry(ext).or_default() += 1;
+        self.bytes_moved += size;
+    }
+
+    /// Human readable summary of the run
+    pub fn summary(&self) -> String {
+        let by_ext: Vec<String> = self
+            .files_by_extension
+            .iter()
+            .map(|(ext, count)| format!("{ext}: {count}"))
+            .collect();
+        let mut ret = String::from("Summary:\n");
+        ret.push_str(&format!("  dirs scanned:   {}\n", self.dirs_scanned));
+        ret.push_str(&format!("  dirs processed: {}\n", self.dirs_processed));
+        ret.push_str(&format!("  dirs skipped:   {}\n", self.skipped_count));
+        ret.push_str(&format!("  dirs updated:   {}\n", self.updated_count));
+        ret.push_str(&format!("  conflicts:      {}\n", self.conflicts.len()));
+        if !self.symlinks_skipped.is_empty() {
+            ret.push_str(&format!(
+                "  links skipped:  {} (see --follow-symlinks)\n",
+                self.symlinks_skipped.len()
+            ));
+        }
+        match by_ext.is_empty() {
+            true => ret.push_str(&format!("  files moved:    {}\n", self.files_moved)),
+            false => ret.push_str(&format!(
+                "  files moved:    {} ({})\n",
+                self.files_moved,
+                by_ext.join(", ")
+            )),
+        }
+        ret.push_str(&format!(
+            "  bytes moved:    {} ({} bytes)\n",
+            format_size(self.bytes_moved),
+            self.bytes_moved
+        ));
+        ret.push_str(&format!(
+            "  elapsed:        {:.2}s\n",
+            self.elapsed.as_secs_f64()
+        ));
+        ret.push_str(&format!("  errors:         {}\n", self.errors.len()));
+        for e in &self.errors {
+            ret.push_str(&format!("    - {e}\n"));
+        }
+        ret
+    }
+
+    /// Summary of the run for scripts
+    pub fn summary_json(&self) -> Json {
+        Json::object(vec![
+            ("dirs_scanned", self.dirs_scanned.into()),
+            ("dirs_processed", self.dirs_processed.into()),
+            ("dirs_skipped", self.skipped_count.into()),
+            ("dirs_updated", self.updated_count.into()),
+            ("conflicts", self.conflicts.len().into()),
+            (
+                "symlinks_skipped",
+                Json::Array(
+                    self.symlinks_skipped
+                        .iter()
+                        .map(|p| p.to_string_lossy().to_string().into())
+                        .collect(),
+                ),
+            ),
+            ("files_moved", self.files_moved.into()),
+            (
+                "files_by_extension",
+                Json::Object(
+                    self.files_by_extension
+                        .iter()
+                        .map(|(ext, count)| (ext.clone(), (*count).into()))
+                        .collect(),
+                ),
+            ),
+            ("bytes_moved", self.bytes_moved.into()),
+            ("elapsed_seconds", self.elapsed.as_secs_f64().into()),
+            (
+                "errors",
+                Json::Array(self.errors.iter().map(|e| e.to_string().into()).collect()),
+            ),
+            (
+                "directories",
+                Json::Array(
+                    self.directories
+                        .iter()
+                        .map(DirectoryResult::to_json)
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+}
+
+/// Format a byte count with a binary unit, e.g. `1.50 GiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.2} {}", UNITS[unit]),
+    }
+}
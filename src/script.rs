@@ -0,0 +1,300 @@
+// Description: Turn the plan of a dry run into a shell script that makes the same changes, for
+//              --script-output. The script can be read over and then run by hand.
+
+use crate::{DigestAlgorithm, Newline, time::DateTime};
+
+/// Language of the script written by --script-output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScriptFormat {
+    /// POSIX sh
+    #[default]
+    Sh,
+    /// PowerShell
+    Ps1,
+}
+
+impl ScriptFormat {
+    /// Parse the value given to --script-output-format
+    pub fn parse(name: &str) -> Option<ScriptFormat> {
+        match name.to_lowercase().as_str() {
+            "sh" => Some(ScriptFormat::Sh),
+            "ps1" | "powershell" => Some(ScriptFormat::Ps1),
+            _ => None,
+        }
+    }
+}
+
+/// One change the dry run would have made
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    /// A line of the run's output that isn't a change, kept to explain the script
+    Comment(String),
+    CreateDir(String),
+    Move {
+        from: String,
+        to: String,
+    },
+    Copy {
+        from: String,
+        to: String,
+    },
+    /// A copy of the whole game dir
+    Backup {
+        from: String,
+        to: String,
+    },
+    /// A playlist and everything that goes in it
+    Write {
+        path: String,
+        lines: Vec<String>,
+    },
+    /// A checksum file and the files it lists, relative to the dir it's in
+    Hash {
+        path: String,
+        files: Vec<String>,
+    },
+    RemoveFile(String),
+    RemoveDir(String),
+}
+
+/// Split `src -> dst`
+fn split_move(rest: &str) -> (String, String) {
+    match rest.split_once(" -> ") {
+        Some((from, to)) => (from.to_string(), to.to_string()),
+        None => (rest.to_string(), String::new()),
+    }
+}
+
+/// Get the steps out of the lines a dry run printed. The lines written to a file are gathered
+/// into a single step where the first of them was.
+fn parse_steps(lines: &[String]) -> Vec<Step> {
+    let mut ret: Vec<Step> = Vec::new();
+    for line in lines {
+        let Some((tag, rest)) = line
+            .split_once(": ")
+            .filter(|(t, _)| t.starts_with("WOULD "))
+        else {
+            ret.push(Step::Comment(line.clone()));
+            continue;
+        };
+        let step = match tag {
+            "WOULD CREATE DIR" => Step::CreateDir(rest.to_string()),
+            "WOULD MOVE" | "WOULD RENAME" => {
+                let (from, to) = split_move(rest);
+                Step::Move { from, to }
+            }
+            "WOULD COPY" => {
+                let (from, to) = split_move(rest);
+                Step::Copy { from, to }
+            }
+            "WOULD BACK UP" => {
+                let (from, to) = split_move(rest);
+                Step::Backup { from, to }
+            }
+            "WOULD WRITE" | "WOULD HASH" => {
+                let (text, path) = rest.rsplit_once(" >> ").unwrap_or((rest, ""));
+                let existing = ret.iter_mut().find_map(|s| match s {
+                    Step::Write { path: p, lines }
+                    | Step::Hash {
+                        path: p,
+                        files: lines,
+                    } if p == path => Some(lines),
+                    _ => None,
+                });
+                match existing {
+                    Some(lines) => lines.push(text.to_string()),
+                    None if tag == "WOULD WRITE" => ret.push(Step::Write {
+                        path: path.to_string(),
+                        lines: vec![text.to_string()],
+                    }),
+                    None => ret.push(Step::Hash {
+                        path: path.to_string(),
+                        files: vec![text.to_string()],
+                    }),
+                }
+                continue;
+            }
+            // the playlist or checksum file is written by its WRITE or HASH lines
+            "WOULD CREATE FILE" if !rest.ends_with(" conflicted dirs)") => continue,
+            "WOULD CREATE FILE" => {
+                Step::Comment(format!("the conflict log isn't part of the script: {rest}"))
+            }
+            "WOULD REMOVE FILE" => Step::RemoveFile(rest.to_string()),
+            "WOULD REMOVE DIR" => Step::RemoveDir(rest.to_string()),
+            _ => Step::Comment(format!("not scripted: {line}")),
+        };
+        ret.push(step);
+    }
+    ret
+}
+
+/// `text` in single quotes for sh
+fn sh_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// `text` in single quotes for PowerShell, which also ends a string at curly quotes
+fn ps_quote(text: &str) -> String {
+    let mut ret = String::from("'");
+    for c in text.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') {
+            ret.push(c);
+        }
+        ret.push(c);
+    }
+    ret.push('\'');
+    ret
+}
+
+/// The dir a checksum file is in, which the files it lists are relative to
+fn parent_of(path: &str) -> &str {
+    match path.rfind(['/', '\\']) {
+        Some(0) => "/",
+        Some(i) => &path[..i],
+        None => ".",
+    }
+}
+
+fn sh_step(step: &Step, newline: &str, algorithm: DigestAlgorithm) -> String {
+    let q = sh_quote;
+    match step {
+        Step::Comment(text) => format!("# {}\n", text.replace('\n', "\n# ")),
+        Step::CreateDir(path) => format!("# create the dir\nmkdir -p {}\n", q(path)),
+        Step::Move { from, to } => format!(
+            "# move a file, unless that was done already\nif [ -e {0} ] && [ ! -e {1} ]; then \
+             mv {0} {1}; fi\n",
+            q(from),
+            q(to)
+        ),
+        Step::Copy { from, to } => format!(
+            "# copy a file, unless that was done already\nif [ -e {0} ] && [ ! -e {1} ]; then \
+             cp -p {0} {1}; fi\n",
+            q(from),
+            q(to)
+        ),
+        Step::Backup { from, to } => format!(
+            "# back up the game dir\nif [ ! -e {1} ]; then cp -Rp {0} {1}; fi\n",
+            q(from),
+            q(to)
+        ),
+        Step::Write { path, lines } => {
+            let newline = newline.replace('\r', "\\r").replace('\n', "\\n");
+            let mut ret = format!("# write the playlist\nprintf '%s{newline}'");
+            for line in lines {
+                ret.push_str(&format!(" \\\n    {}", q(line)));
+            }
+            ret.push_str(&format!(" > {}\n", q(path)));
+            ret
+        }
+        Step::Hash { path, files } => {
+            let files: Vec<String> = files.iter().map(|f| q(f)).collect();
+            format!(
+                "# hash the moved files\n(cd {} && {}sum -- {}) > {}\n",
+                q(parent_of(path)),
+                algorithm.extension(),
+                files.join(" "),
+                q(path)
+            )
+        }
+        Step::RemoveFile(path) => format!("# remove the file\nrm -f {}\n", q(path)),
+        Step::RemoveDir(path) => format!(
+            "# remove the empty dir\nif [ -d {0} ]; then rmdir {0}; fi\n",
+            q(path)
+        ),
+    }
+}
+
+fn ps1_step(step: &Step, newline: &str, algorithm: DigestAlgorithm) -> String {
+    let q = ps_quote;
+    match step {
+        Step::Comment(text) => format!("# {}\n", text.replace('\n', "\n# ")),
+        Step::CreateDir(path) => format!(
+            "# create the dir\n[IO.Directory]::CreateDirectory({}) | Out-Null\n",
+            q(path)
+        ),
+        Step::Move { from, to } => format!(
+            "# move a file, unless that was done already\nif ((Test-Path -LiteralPath {0}) -and \
+             -not (Test-Path -LiteralPath {1})) {{ Move-Item -LiteralPath {0} -Destination {1} \
+             }}\n",
+            q(from),
+            q(to)
+        ),
+        Step::Copy { from, to } => format!(
+            "# copy a file, unless that was done already\nif ((Test-Path -LiteralPath {0}) -and \
+             -not (Test-Path -LiteralPath {1})) {{ Copy-Item -LiteralPath {0} -Destination {1} \
+             }}\n",
+            q(from),
+            q(to)
+        ),
+        Step::Backup { from, to } => format!(
+            "# back up the game dir\nif (-not (Test-Path -LiteralPath {1})) {{ Copy-Item \
+             -LiteralPath {0} -Destination {1} -Recurse }}\n",
+            q(from),
+            q(to)
+        ),
+        Step::Write { path, lines } => {
+            let newline = newline.replace('\r', "`r").replace('\n', "`n");
+            let lines: Vec<String> = lines.iter().map(|l| format!("    {}", q(l))).collect();
+            format!(
+                "# write the playlist\n[IO.File]::WriteAllText({}, ((@(\n{}\n) -join \
+                 \"{newline}\") + \"{newline}\"))\n",
+                q(path),
+                lines.join(",\n")
+            )
+        }
+        Step::Hash { path, files } => {
+            let files: Vec<String> = files.iter().map(|f| q(f)).collect();
+            format!(
+                "# hash the moved files\nSet-Content -LiteralPath {} -Value @(foreach ($f in \
+                 @({})) {{ '{{0}}  {{1}}' -f (Get-FileHash -Algorithm {} -LiteralPath \
+                 (Join-Path {} $f)).Hash.ToLower(), $f }})\n",
+                q(path),
+                files.join(", "),
+                algorithm.extension().to_uppercase(),
+                q(parent_of(path))
+            )
+        }
+        Step::RemoveFile(path) => format!(
+            "# remove the file\nif (Test-Path -LiteralPath {0}) {{ Remove-Item -LiteralPath {0} \
+             }}\n",
+            q(path)
+        ),
+        Step::RemoveDir(path) => format!(
+            "# remove the empty dir\nif (Test-Path -LiteralPath {0}) {{ Remove-Item -LiteralPath \
+             {0} }}\n",
+            q(path)
+        ),
+    }
+}
+
+/// A script making the changes `lines` (the output of a dry run) describe. Every step checks
+/// whether it was done already, so running the script twice is harmless. Playlists are written
+/// with `newline` and checksum files hashed with `algorithm`.
+pub fn render_script(
+    lines: &[String],
+    format: ScriptFormat,
+    newline: Newline,
+    algorithm: DigestAlgorithm,
+) -> String {
+    let mut ret = match format {
+        ScriptFormat::Sh => String::from("#!/bin/sh\n"),
+        ScriptFormat::Ps1 => String::new(),
+    };
+    ret.push_str(&format!(
+        "# Generated by m3ugen {} on {}. Review it, then run it to make the changes m3ugen\n# \
+         would have made. Unlike a real run it keeps no transaction log for --undo.\n",
+        env!("CARGO_PKG_VERSION"),
+        DateTime::now().iso_string()
+    ));
+    ret.push_str(match format {
+        ScriptFormat::Sh => "set -e\n\n",
+        ScriptFormat::Ps1 => "$ErrorActionPreference = 'Stop'\n\n",
+    });
+    for step in parse_steps(lines) {
+        ret.push_str(&match format {
+            ScriptFormat::Sh => sh_step(&step, newline.as_str(), algorithm),
+            ScriptFormat::Ps1 => ps1_step(&step, newline.as_str(), algorithm),
+        });
+    }
+    ret
+}
@@ -0,0 +1,31 @@
+// Description: Turn Ctrl+C (and SIGTERM) into a flag the watch loop checks, so the dir being
+//              processed gets finished instead of being cut off halfway through a move.
+
+use std::{
+    ffi::c_int,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+const SIGINT: c_int = 2;
+const SIGTERM: c_int = 15;
+
+unsafe extern "C" {
+    fn signal(signum: c_int, handler: usize) -> usize;
+}
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_signal(_signum: c_int) {
+    STOP.store(true, Ordering::SeqCst);
+}
+
+/// Install the handlers and hand back the flag they set
+pub fn stop_flag() -> &'static AtomicBool {
+    let handler = on_signal as extern "C" fn(c_int) as usize;
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        signal(SIGINT, handler);
+        signal(SIGTERM, handler);
+    }
+    &STOP
+}
@@ -0,0 +1,137 @@
+// Description: Failures injected on purpose with --simulate-errors, so the error handling
+//              (retries, the report, --undo) can be exercised without a failing disk. Only
+//              debug builds and builds with the simulate-errors feature can turn it on.
+
+use std::io;
+
+/// The filesystem calls a failure can be injected into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsOp {
+    Rename,
+    CreateDir,
+    Write,
+}
+
+impl FsOp {
+    #[cfg(any(debug_assertions, feature = "simulate-errors"))]
+    fn error(self) -> io::Error {
+        match self {
+            FsOp::Rename => {
+                io::Error::new(io::ErrorKind::PermissionDenied, "simulated rename failure")
+            }
+            FsOp::CreateDir => io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "simulated create_dir failure",
+            ),
+            FsOp::Write => io::Error::other("simulated write failure"),
+        }
+    }
+}
+
+#[cfg(any(debug_assertions, feature = "simulate-errors"))]
+mod enabled {
+    use std::sync::{
+        OnceLock,
+        atomic::{AtomicU64, Ordering},
+    };
+
+    use super::FsOp;
+
+    /// Which calls fail
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum SimulatedErrors {
+        /// Every rename
+        RenameFail,
+        /// Every dir creation
+        CreateFail,
+        /// Every file write
+        WriteFail,
+        /// Any of them, `rate` of the time, in an order fixed by `seed`
+        Random { seed: u64, rate: f64 },
+    }
+
+    impl SimulatedErrors {
+        /// Parse the mode given to --simulate-errors
+        pub fn parse(s: &str) -> Result<SimulatedErrors, String> {
+            let expected = "expected rename-fail, create-fail, write-fail or random:SEED:RATE";
+            match s {
+                "rename-fail" => Ok(SimulatedErrors::RenameFail),
+                "create-fail" => Ok(SimulatedErrors::CreateFail),
+                "write-fail" => Ok(SimulatedErrors::WriteFail),
+                _ => {
+                    let mut parts = s.split(':');
+                    let (Some("random"), Some(seed), Some(rate), None) =
+                        (parts.next(), parts.next(), parts.next(), parts.next())
+                    else {
+                        return Err(format!("unknown error simulation '{s}' ({expected})"));
+                    };
+                    let seed = seed
+                        .parse()
+                        .map_err(|_| format!("'{seed}' is not a valid seed ({expected})"))?;
+                    let rate: f64 = rate
+                        .parse()
+                        .ok()
+                        .filter(|r| (0.0..=1.0).contains(r))
+                        .ok_or_else(|| format!("'{rate}' is not a rate between 0 and 1"))?;
+                    Ok(SimulatedErrors::Random { seed, rate })
+                }
+            }
+        }
+    }
+
+    static MODE: OnceLock<SimulatedErrors> = OnceLock::new();
+    static RNG: AtomicU64 = AtomicU64::new(0);
+
+    /// Start injecting failures for the rest of the run
+    pub fn enable(mode: SimulatedErrors) {
+        if let SimulatedErrors::Random { seed, .. } = mode {
+            // xorshift gets stuck on 0
+            RNG.store(seed.max(1), Ordering::Relaxed);
+        }
+        let _ = MODE.set(mode);
+    }
+
+    /// The next number from a xorshift64 generator, as a fraction below 1
+    fn next_fraction() -> f64 {
+        let mut x = RNG.load(Ordering::Relaxed);
+        loop {
+            let mut next = x;
+            next ^= next << 13;
+            next ^= next >> 7;
+            next ^= next << 17;
+            match RNG.compare_exchange_weak(x, next, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return (next >> 11) as f64 / (1u64 << 53) as f64,
+                Err(current) => x = current,
+            }
+        }
+    }
+
+    pub(super) fn should_fail(op: FsOp) -> bool {
+        match MODE.get() {
+            None => false,
+            Some(SimulatedErrors::RenameFail) => op == FsOp::Rename,
+            Some(SimulatedErrors::CreateFail) => op == FsOp::CreateDir,
+            Some(SimulatedErrors::WriteFail) => op == FsOp::Write,
+            Some(SimulatedErrors::Random { rate, .. }) => next_fraction() < *rate,
+        }
+    }
+}
+
+#[cfg(any(debug_assertions, feature = "simulate-errors"))]
+pub use enabled::{SimulatedErrors, enable};
+
+/// Fail with a simulated error if --simulate-errors asks for it, before doing `op` for real
+#[cfg(any(debug_assertions, feature = "simulate-errors"))]
+pub fn check(op: FsOp) -> io::Result<()> {
+    match enabled::should_fail(op) {
+        true => Err(op.error()),
+        false => Ok(()),
+    }
+}
+
+/// Fail with a simulated error if --simulate-errors asks for it, which it can't in this build
+#[cfg(not(any(debug_assertions, feature = "simulate-errors")))]
+#[inline(always)]
+pub fn check(_op: FsOp) -> io::Result<()> {
+    Ok(())
+}
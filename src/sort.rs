@@ -0,0 +1,65 @@
+// Description: Orders the data files of a child dir before they're written to the playlist.
+
+use std::{fs, path::PathBuf};
+
+use crate::{get_path_dir_name, has_extension, natural_cmp};
+
+/// How the entries of a playlist are ordered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Plain lexicographic order of the file names
+    Name,
+    /// Numbers in file names are compared by value, so `Disc 2` comes before `Disc 10`
+    #[default]
+    Natural,
+    /// Grouped by extension, then by name
+    Extension,
+    /// Smallest file first
+    Size,
+    /// Whatever order the filesystem returns them in
+    None,
+}
+
+impl SortOrder {
+    /// Parse the value of --sort
+    pub fn parse(name: &str) -> Option<SortOrder> {
+        match name.to_ascii_lowercase().as_str() {
+            "name" => Some(SortOrder::Name),
+            "natural" => Some(SortOrder::Natural),
+            "extension" => Some(SortOrder::Extension),
+            "size" => Some(SortOrder::Size),
+            "none" => Some(SortOrder::None),
+            _ => None,
+        }
+    }
+}
+
+/// Sort `files` in place
+pub fn sort_files(files: &mut [PathBuf], order: SortOrder) {
+    let name = |p: &PathBuf| get_path_dir_name(p);
+    let extension = |p: &PathBuf| {
+        p.extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    };
+    match order {
+        SortOrder::Name => files.sort_by_key(name),
+        SortOrder::Natural => files.sort_by(|a, b| natural_cmp(&name(a), &name(b))),
+        SortOrder::Extension => files.sort_by_key(|p| (extension(p), name(p))),
+        SortOrder::Size => {
+            files.sort_by_cached_key(|p| (fs::metadata(p).map(|m| m.len()).unwrap_or(0), name(p)))
+        }
+        SortOrder::None => (),
+    }
+}
+
+/// Put the files of the first extension in `priority` first, then those of the second and so on,
+/// with the extensions it doesn't list last. Files keep their order within each group.
+pub fn sort_by_extension_priority(files: &mut [PathBuf], priority: &[String]) {
+    files.sort_by_key(|p| {
+        priority
+            .iter()
+            .position(|ext| has_extension(p, &[ext.as_str()]))
+            .unwrap_or(priority.len())
+    });
+}
@@ -0,0 +1,241 @@
+// Description: Take stock of a parent dir for the stats subcommand: what files each game dir
+//              holds, whether it was already processed and how many discs it has. Nothing is
+//              moved or created.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    GenError, GenOptions, Json, build_path_from_parts, detect_discs, format_size,
+    get_path_dir_name, is_playlist_file, validate::child_dirs, verify_dir,
+};
+
+/// How many files with one extension there are and how big they are together
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExtensionStats {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl ExtensionStats {
+    fn to_json(self) -> Json {
+        Json::object(vec![
+            ("files", self.files.into()),
+            ("bytes", self.bytes.into()),
+        ])
+    }
+}
+
+/// What one game dir holds, its sub-dir included
+#[derive(Debug, Clone, Default)]
+pub struct DirStats {
+    pub path: PathBuf,
+    /// Files by lowercase extension, `""` for files without one
+    pub by_extension: BTreeMap<String, ExtensionStats>,
+    /// A .m3u, .m3u8 or .pls sits in the dir
+    pub has_playlist: bool,
+    /// The sub-dir the files would be moved into is there
+    pub has_sub_dir: bool,
+    /// Different discs found among the data files, 0 when there are none
+    pub discs: usize,
+}
+
+impl DirStats {
+    /// The dir as JSON
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("path", self.path.to_string_lossy().as_ref().into()),
+            ("files_by_extension", extensions_json(&self.by_extension)),
+            ("has_playlist", self.has_playlist.into()),
+            ("has_sub_dir", self.has_sub_dir.into()),
+            ("discs", self.discs.into()),
+        ])
+    }
+}
+
+/// What a stats run found
+#[derive(Debug, Default)]
+pub struct LibraryStats {
+    pub dirs: Vec<DirStats>,
+    pub errors: Vec<GenError>,
+}
+
+impl LibraryStats {
+    /// Files by extension over every dir
+    pub fn totals(&self) -> BTreeMap<String, ExtensionStats> {
+        let mut ret: BTreeMap<String, ExtensionStats> = BTreeMap::new();
+        for (ext, stats) in self.dirs.iter().flat_map(|d| &d.by_extension) {
+            let total = ret.entry(ext.clone()).or_default();
+            total.files += stats.files;
+            total.bytes += stats.bytes;
+        }
+        ret
+    }
+
+    /// A table with a row per dir followed by the totals
+    pub fn table(&self) -> String {
+        let name_width = self
+            .dirs
+            .iter()
+            .map(|d| get_path_dir_name(&d.path).chars().count())
+            .chain([3])
+            .max()
+            .unwrap_or_default();
+        let mut ret = format!("{:<name_width$}  PLAYLIST  SUB-DIR  DISCS  FILES\n", "DIR");
+        for dir in &self.dirs {
+            let files: Vec<String> = dir
+                .by_extension
+                .iter()
+                .map(|(ext, stats)| format_extension(ext, stats))
+                .collect();
+            ret.push_str(&format!(
+                "{:<name_width$}  {:<8}  {:<7}  {:<5}  {}\n",
+                get_path_dir_name(&dir.path),
+                yes_no(dir.has_playlist),
+                yes_no(dir.has_sub_dir),
+                dir.discs,
+                files.join(", ")
+            ));
+        }
+
+        let totals = self.totals();
+        let files: usize = totals.values().map(|s| s.files).sum();
+        let bytes: u64 = totals.values().map(|s| s.bytes).sum();
+        ret.push_str(&format!(
+            "\nTotal: {} dirs ({} with a playlist, {} with a sub-dir), {files} files, {}\n",
+            self.dirs.len(),
+            self.dirs.iter().filter(|d| d.has_playlist).count(),
+            self.dirs.iter().filter(|d| d.has_sub_dir).count(),
+            format_size(bytes)
+        ));
+        for (ext, stats) in &totals {
+            ret.push_str(&format!("  {}\n", format_extension(ext, stats)));
+        }
+        ret
+    }
+
+    /// Every dir and the totals as JSON
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            (
+                "dirs",
+                Json::Array(self.dirs.iter().map(DirStats::to_json).collect()),
+            ),
+            (
+                "total",
+                Json::object(vec![
+                    ("dirs", self.dirs.len().into()),
+                    (
+                        "with_playlist",
+                        self.dirs.iter().filter(|d| d.has_playlist).count().into(),
+                    ),
+                    (
+                        "with_sub_dir",
+                        self.dirs.iter().filter(|d| d.has_sub_dir).count().into(),
+                    ),
+                    ("files_by_extension", extensions_json(&self.totals())),
+                ]),
+            ),
+            (
+                "errors",
+                Json::Array(self.errors.iter().map(|e| e.to_string().into()).collect()),
+            ),
+        ])
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    match value {
+        true => "yes",
+        false => "no",
+    }
+}
+
+/// `chd: 2 (1.20 GiB)`, files without an extension show up as `(none)`
+fn format_extension(ext: &str, stats: &ExtensionStats) -> String {
+    let ext = match ext.is_empty() {
+        true => "(none)",
+        false => ext,
+    };
+    format!("{ext}: {} ({})", stats.files, format_size(stats.bytes))
+}
+
+fn extensions_json(by_extension: &BTreeMap<String, ExtensionStats>) -> Json {
+    Json::Object(
+        by_extension
+            .iter()
+            .map(|(ext, stats)| (ext.clone(), stats.to_json()))
+            .collect(),
+    )
+}
+
+/// The files directly in `dir`
+fn files_in(dir: &Path) -> Result<Vec<PathBuf>, GenError> {
+    let entries = fs::read_dir(dir).map_err(|source| GenError::ReadDirFailed {
+        path: dir.to_path_buf(),
+        source,
+    })?;
+    let mut ret: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+    ret.sort();
+    Ok(ret)
+}
+
+/// Take stock of one game dir and the sub-dir its files were (or would be) moved into
+fn dir_stats(child: &Path, options: &GenOptions) -> Result<DirStats, GenError> {
+    let sub_dir = build_path_from_parts(&[child, &options.sub_dir_path(&get_path_dir_name(child))]);
+    let top = files_in(child)?;
+    let mut stats = DirStats {
+        path: child.to_path_buf(),
+        has_playlist: top.iter().any(|f| is_playlist_file(f)),
+        has_sub_dir: sub_dir.is_dir(),
+        ..DirStats::default()
+    };
+
+    let mut files = top;
+    if stats.has_sub_dir {
+        files.extend(files_in(&sub_dir)?);
+    }
+    for file in &files {
+        let ext = file
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let entry = stats.by_extension.entry(ext).or_default();
+        entry.files += 1;
+        entry.bytes += fs::metadata(file).map(|m| m.len()).unwrap_or_default();
+    }
+
+    let data_files: Vec<PathBuf> = files
+        .into_iter()
+        .filter(|f| options.wants_file(f))
+        .collect();
+    stats.discs = detect_discs(
+        &data_files,
+        &options.first_pbp_name(&get_path_dir_name(child)),
+    )
+    .iter()
+    .map(|entry| entry.disc)
+    .collect::<BTreeSet<u32>>()
+    .len();
+    Ok(stats)
+}
+
+/// Count the files in every child dir of `parent` without changing anything
+pub fn stats_parent_dir(parent: &Path, options: &GenOptions) -> Result<LibraryStats, GenError> {
+    verify_dir(parent, false)?;
+    let mut ret = LibraryStats::default();
+    for child in child_dirs(parent, options)? {
+        match dir_stats(&child, options) {
+            Ok(stats) => ret.dirs.push(stats),
+            Err(e) => ret.errors.push(e),
+        }
+    }
+    Ok(ret)
+}
@@ -0,0 +1,122 @@
+// Description: Name templates like `{dir}_({ext}).m3u`, used to name the files we create.
+
+use std::fmt;
+
+/// Variables a template can use, for error messages
+const VARIABLES: &str = "{dir}, {ext} and {date}";
+
+/// One piece of a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    /// Name of the game dir
+    Dir,
+    /// Most common extension among the game's data files
+    Ext,
+    /// Today's date as YYYY-MM-DD
+    Date,
+}
+
+/// Values filled into a template
+#[derive(Debug, Clone, Default)]
+pub struct TemplateVars<'a> {
+    pub dir: &'a str,
+    pub ext: &'a str,
+    pub date: &'a str,
+}
+
+/// A parsed name template. `{{` and `}}` stand for literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    source: String,
+    parts: Vec<Part>,
+}
+
+impl NameTemplate {
+    /// Parse a template, rejecting unknown variables and anything that could make the name a
+    /// path
+    pub fn parse(template: &str) -> Result<NameTemplate, String> {
+        if template.trim().is_empty() {
+            return Err("the name template is empty".to_string());
+        }
+        if template.contains(['/', '\\', '\0']) {
+            return Err(format!(
+                "'{template}' can't contain path separators or NUL characters"
+            ));
+        }
+
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.next_if_eq(&'{').is_some() => literal.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => literal.push('}'),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("'{template}' has an unclosed '{{'")),
+                        }
+                    }
+                    let part = match name.as_str() {
+                        "dir" => Part::Dir,
+                        "ext" => Part::Ext,
+                        "date" => Part::Date,
+                        _ => {
+                            return Err(format!(
+                                "unknown template variable {{{name}}} (expected {VARIABLES})"
+                            ));
+                        }
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                }
+                '}' => return Err(format!("'{template}' has a '}}' without a '{{'")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(NameTemplate {
+            source: template.to_string(),
+            parts,
+        })
+    }
+
+    /// Check if the template needs the dominant extension, which takes a look at the files
+    pub fn uses_ext(&self) -> bool {
+        self.parts.contains(&Part::Ext)
+    }
+
+    /// Fill in the variables. Fails when the result isn't usable as a file name.
+    pub fn render(&self, vars: &TemplateVars) -> Result<String, String> {
+        let mut ret = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => ret.push_str(s),
+                Part::Dir => ret.push_str(vars.dir),
+                Part::Ext => ret.push_str(vars.ext),
+                Part::Date => ret.push_str(vars.date),
+            }
+        }
+        if ret.trim().is_empty() || ret == "." || ret == ".." || ret.contains(['/', '\\', '\0']) {
+            return Err(format!(
+                "'{}' gives '{ret}', which isn't a valid name",
+                self.source
+            ));
+        }
+        Ok(ret)
+    }
+}
+
+impl fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
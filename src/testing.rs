@@ -0,0 +1,56 @@
+// Description: Helpers shared by the unit tests.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A scratch dir under the system temp dir, removed again when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create an empty dir; `name` keeps tests running side by side apart
+    pub fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("m3ugen-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Write `contents` to `name` inside the dir, creating the dirs leading to it
+    pub fn write(&self, name: &str, contents: &[u8]) -> PathBuf {
+        let path = self.0.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Every file and dir under the dir, relative to it and sorted
+    pub fn tree(&self) -> Vec<PathBuf> {
+        fn walk(dir: &Path, root: &Path, ret: &mut Vec<PathBuf>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                ret.push(path.strip_prefix(root).unwrap().to_path_buf());
+                if path.is_dir() {
+                    walk(&path, root, ret);
+                }
+            }
+        }
+        let mut ret = Vec::new();
+        walk(&self.0, &self.0, &mut ret);
+        ret.sort();
+        ret
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,136 @@
+// Description: Calendar helpers for timestamps, so dates can be printed and parsed without a
+//              date/time dependency. Everything is in UTC.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A UTC date and time broken into its parts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+/// Days since 1970-01-01 for a calendar date (proleptic Gregorian)
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Calendar date for a number of days since 1970-01-01
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+impl DateTime {
+    /// Break a point in time into its UTC parts
+    pub fn from_system_time(time: SystemTime) -> DateTime {
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        };
+        let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+        let rem = secs.rem_euclid(86400);
+        DateTime {
+            year,
+            month,
+            day,
+            hour: (rem / 3600) as u32,
+            minute: (rem % 3600 / 60) as u32,
+            second: (rem % 60) as u32,
+        }
+    }
+
+    pub fn now() -> DateTime {
+        DateTime::from_system_time(SystemTime::now())
+    }
+
+    /// Turn the parts back into a point in time
+    pub fn to_system_time(&self) -> SystemTime {
+        let secs = days_from_civil(self.year, self.month, self.day) * 86400
+            + (self.hour * 3600 + self.minute * 60 + self.second) as i64;
+        match secs >= 0 {
+            true => UNIX_EPOCH + Duration::from_secs(secs as u64),
+            false => UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()),
+        }
+    }
+
+    /// `2025-10-15`
+    pub fn date_string(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    /// `2025-10-15T13:45:00Z`
+    pub fn iso_string(&self) -> String {
+        format!(
+            "{}T{:02}:{:02}:{:02}Z",
+            self.date_string(),
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+
+    /// Parse an ISO 8601 date (`2025-10-15`) or UTC date and time (`2025-10-15T13:45:00Z`, the
+    /// `Z` being optional)
+    pub fn parse_iso(s: &str) -> Option<DateTime> {
+        let s = s.trim();
+        let (date, time) = match s.split_once(['T', ' ']) {
+            Some((date, time)) => (date, Some(time.strip_suffix('Z').unwrap_or(time))),
+            None => (s, None),
+        };
+        let mut parts = date.splitn(3, '-');
+        let year: i64 = parts.next()?.parse().ok()?;
+        let month: u32 = parts.next()?.parse().ok()?;
+        let day: u32 = parts.next()?.parse().ok()?;
+        let mut ret = DateTime {
+            year,
+            month,
+            day,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        };
+        // a date like 02-31 doesn't survive the round trip through a day count
+        if !(1..=12).contains(&month)
+            || civil_from_days(days_from_civil(year, month, day)) != (year, month, day)
+        {
+            return None;
+        }
+        if let Some(time) = time {
+            let mut parts = time.splitn(3, ':');
+            ret.hour = parts.next()?.parse().ok()?;
+            ret.minute = parts.next()?.parse().ok()?;
+            ret.second = parts.next().map_or(Some(0), |s| s.parse().ok())?;
+            if ret.hour > 23 || ret.minute > 59 || ret.second > 59 {
+                return None;
+            }
+        }
+        Some(ret)
+    }
+
+    /// `20251015-134500`, safe to use in a file name
+    pub fn compact_string(&self) -> String {
+        format!(
+            "{:04}{:02}{:02}-{:02}{:02}{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
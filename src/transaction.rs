@@ -0,0 +1,308 @@
+// Description: Record every change a run makes to the filesystem so it can be rolled back with
+//              --undo.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crate::{
+    GenError, GenOptions, Json, ProcessReport, build_path_from_parts, move_file_with_fallback,
+    parse_json, time::DateTime, write_file_atomic,
+};
+
+/// Bumped whenever the layout of the log changes
+const LOG_VERSION: u64 = 1;
+
+/// A single change made to the filesystem
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// A directory was created
+    CreateDir { path: PathBuf },
+    /// An empty directory was removed
+    RemoveDir { path: PathBuf },
+    /// A file was written; `existed` is set when it replaced a file that was already there
+    CreateFile { path: PathBuf, existed: bool },
+    /// A file was deleted, with what it contained so it can be put back
+    RemoveFile { path: PathBuf, contents: String },
+    /// A file was moved
+    Rename { from: PathBuf, to: PathBuf },
+    /// A file was changed in place, with what it contained before
+    Rewrite { path: PathBuf, original: String },
+}
+
+/// Paths are stored absolute, so the log can be undone from any working directory
+fn path_json(path: &Path) -> Json {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    Json::from(path.to_string_lossy().to_string())
+}
+
+impl Operation {
+    /// The path the operation changed (the destination, for a rename)
+    pub fn path(&self) -> &Path {
+        match self {
+            Operation::CreateDir { path }
+            | Operation::RemoveDir { path }
+            | Operation::CreateFile { path, .. }
+            | Operation::RemoveFile { path, .. }
+            | Operation::Rewrite { path, .. } => path,
+            Operation::Rename { to, .. } => to,
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        match self {
+            Operation::CreateDir { path } => {
+                Json::object(vec![("op", "create_dir".into()), ("path", path_json(path))])
+            }
+            Operation::RemoveDir { path } => {
+                Json::object(vec![("op", "remove_dir".into()), ("path", path_json(path))])
+            }
+            Operation::CreateFile { path, existed } => Json::object(vec![
+                ("op", "create_file".into()),
+                ("path", path_json(path)),
+                ("existed", (*existed).into()),
+            ]),
+            Operation::RemoveFile { path, contents } => Json::object(vec![
+                ("op", "remove_file".into()),
+                ("path", path_json(path)),
+                ("contents", contents.as_str().into()),
+            ]),
+            Operation::Rename { from, to } => Json::object(vec![
+                ("op", "rename".into()),
+                ("from", path_json(from)),
+                ("to", path_json(to)),
+            ]),
+            Operation::Rewrite { path, original } => Json::object(vec![
+                ("op", "rewrite".into()),
+                ("path", path_json(path)),
+                ("original", original.as_str().into()),
+            ]),
+        }
+    }
+
+    fn from_json(value: &Json) -> Option<Operation> {
+        let path = |key: &str| value.get(key).and_then(Json::as_str).map(PathBuf::from);
+        let text = |key: &str| value.get(key).and_then(Json::as_str).map(str::to_string);
+        Some(match value.get("op")?.as_str()? {
+            "create_dir" => Operation::CreateDir {
+                path: path("path")?,
+            },
+            "remove_dir" => Operation::RemoveDir {
+                path: path("path")?,
+            },
+            "create_file" => Operation::CreateFile {
+                path: path("path")?,
+                existed: value.get("existed")?.as_bool()?,
+            },
+            "remove_file" => Operation::RemoveFile {
+                path: path("path")?,
+                contents: text("contents")?,
+            },
+            "rename" => Operation::Rename {
+                from: path("from")?,
+                to: path("to")?,
+            },
+            "rewrite" => Operation::Rewrite {
+                path: path("path")?,
+                original: text("original")?,
+            },
+            _ => return None,
+        })
+    }
+}
+
+/// Default location of the transaction log: a timestamped file in `dir` that doesn't exist yet
+pub fn default_transaction_log_path(dir: &Path) -> PathBuf {
+    let stamp = DateTime::now().compact_string();
+    let mut path = build_path_from_parts(&[dir, Path::new(&format!(".m3ugen-{stamp}.json"))]);
+    let mut n = 1;
+    while path.exists() {
+        path = build_path_from_parts(&[dir, Path::new(&format!(".m3ugen-{stamp}-{n}.json"))]);
+        n += 1;
+    }
+    path
+}
+
+/// Write `operations` (in the order they happened) to a transaction log
+pub fn write_transaction_log(path: &Path, operations: &[Operation]) -> Result<(), GenError> {
+    let log = Json::object(vec![
+        ("version", LOG_VERSION.into()),
+        ("created", DateTime::now().iso_string().into()),
+        (
+            "operations",
+            Json::Array(operations.iter().map(Operation::to_json).collect()),
+        ),
+    ]);
+    let mut contents = log.to_pretty();
+    contents.push('\n');
+    write_file_atomic(path, contents.as_bytes()).map_err(|source| GenError::WriteLogFailed {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Read the operations back out of a transaction log
+pub fn read_transaction_log(path: &Path) -> Result<Vec<Operation>, GenError> {
+    let invalid = |reason: String| GenError::InvalidTransactionLog {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let contents = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+    let log = parse_json(&contents).map_err(invalid)?;
+    if log.get("version").and_then(Json::as_u64) != Some(LOG_VERSION) {
+        return Err(invalid("unsupported log version".to_string()));
+    }
+    let Some(operations) = log.get("operations").and_then(Json::as_array) else {
+        return Err(invalid("missing operations".to_string()));
+    };
+    operations
+        .iter()
+        .enumerate()
+        .map(|(i, op)| {
+            Operation::from_json(op).ok_or_else(|| invalid(format!("operation {i} is invalid")))
+        })
+        .collect()
+}
+
+/// Reverse `operations`, newest first. The operations needed to redo them are recorded in the
+/// returned report so the rollback can itself be undone.
+pub fn undo_operations(operations: &[Operation], options: &GenOptions) -> ProcessReport {
+    let started = Instant::now();
+    let mut report = ProcessReport::default();
+    for op in operations.iter().rev() {
+        let undo = match op {
+            Operation::CreateDir { path } => Operation::RemoveDir { path: path.clone() },
+            Operation::RemoveDir { path } => Operation::CreateDir { path: path.clone() },
+            Operation::CreateFile {
+                path,
+                existed: true,
+            } => {
+                options.warn(format!(
+                    "WARNING: {} replaced an existing file and can't be restored",
+                    path.display()
+                ));
+                continue;
+            }
+            Operation::CreateFile { path, .. } => Operation::RemoveFile {
+                path: path.clone(),
+                contents: fs::read_to_string(path).unwrap_or_default(),
+            },
+            Operation::RemoveFile { path, .. } => Operation::CreateFile {
+                path: path.clone(),
+                existed: false,
+            },
+            Operation::Rename { from, to } => Operation::Rename {
+                from: to.clone(),
+                to: from.clone(),
+            },
+            Operation::Rewrite { path, .. } => Operation::Rewrite {
+                path: path.clone(),
+                original: fs::read_to_string(path).unwrap_or_default(),
+            },
+        };
+        if options.dry_run {
+            options.say(format!("WOULD UNDO: {}", op.to_json()));
+            continue;
+        }
+
+        let result = match op {
+            Operation::CreateDir { path } => fs::remove_dir(path),
+            Operation::RemoveDir { path } => fs::create_dir(path),
+            Operation::CreateFile { path, .. } => fs::remove_file(path),
+            Operation::RemoveFile { path, contents } => {
+                write_file_atomic(path, contents.as_bytes())
+            }
+            Operation::Rename { from, .. } if from.exists() => Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} is in the way", from.display()),
+            )),
+            Operation::Rename { from, to } => move_file_with_fallback(to, from, false).map(|_| ()),
+            Operation::Rewrite { path, original } => write_file_atomic(path, original.as_bytes()),
+        };
+        match result {
+            Ok(()) => {
+                if let Operation::Rename { from, .. } = op {
+                    let size = fs::metadata(from).map(|m| m.len()).unwrap_or(0);
+                    report.record_move(from, size);
+                }
+                report.operations.push(undo);
+            }
+            Err(source) => report.errors.push(GenError::UndoFailed {
+                path: op.path().to_path_buf(),
+                source,
+            }),
+        }
+    }
+    report.elapsed = started.elapsed();
+    report
+}
+
+/// Roll back the run recorded in `log_path`. The rollback writes its own transaction log (to
+/// `--transaction-log`, or next to the old one) so it can be undone too.
+pub fn undo_transaction_log(
+    log_path: &Path,
+    options: &GenOptions,
+) -> Result<ProcessReport, GenError> {
+    let operations = read_transaction_log(log_path)?;
+    let report = undo_operations(&operations, options);
+    if !options.dry_run && !report.operations.is_empty() {
+        let new_log = match options.transaction_log.as_deref() {
+            Some(path) => path.to_path_buf(),
+            None => default_transaction_log_path(log_path.parent().unwrap_or(Path::new("."))),
+        };
+        write_transaction_log(&new_log, &report.operations)?;
+        options.say(format!("INFO: rollback recorded in {}", new_log.display()));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn round_trips_absolute_paths() {
+        let tmp = TempDir::new("transaction-log");
+        let log = tmp.path().join("log.json");
+        let operations = vec![
+            Operation::CreateDir {
+                path: tmp.path().join("Game/.Game"),
+            },
+            Operation::Rename {
+                from: PathBuf::from("relative/Game.chd"),
+                to: PathBuf::from("relative/.Game/Game.chd"),
+            },
+        ];
+        write_transaction_log(&log, &operations).unwrap();
+
+        let read = read_transaction_log(&log).unwrap();
+        assert_eq!(read[0], operations[0]);
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            read[1],
+            Operation::Rename {
+                from: cwd.join("relative/Game.chd"),
+                to: cwd.join("relative/.Game/Game.chd"),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_corrupt_logs() {
+        let tmp = TempDir::new("transaction-corrupt");
+        for contents in ["", "{\"version\": 1}", &"[".repeat(100_000)] {
+            let log = tmp.write("log.json", contents.as_bytes());
+            assert!(
+                matches!(
+                    read_transaction_log(&log),
+                    Err(GenError::InvalidTransactionLog { .. })
+                ),
+                "{}",
+                &contents[..contents.len().min(20)]
+            );
+        }
+    }
+}
@@ -0,0 +1,303 @@
+// Description: Full screen interface for --tui: the child dirs and their status on the left, a
+//              log of what's being done on the right and a status bar along the bottom. Drawn
+//              with plain ANSI escapes while the library does the work on another thread.
+
+use std::{
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{atomic::Ordering, mpsc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use m3ugen::{
+    DirOutcome, GenError, GenOptions, ProcessReport, TuiEvent, get_path_dir_name,
+    process_parent_dir,
+};
+
+use crate::signal;
+
+/// How often the screen is redrawn while waiting for events
+const TICK: Duration = Duration::from_millis(100);
+
+/// Log lines kept for the right panel
+const MAX_LOG_LINES: usize = 1000;
+
+/// Where a child dir is at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pending,
+    InProgress,
+    Done,
+    Skipped,
+    Error,
+}
+
+impl Status {
+    /// Colored icon shown in front of the dir name
+    fn icon(&self) -> &'static str {
+        match self {
+            Status::Pending => "\x1b[2m·\x1b[0m",
+            Status::InProgress => "\x1b[33m▶\x1b[0m",
+            Status::Done => "\x1b[32m✔\x1b[0m",
+            Status::Skipped => "\x1b[34m↷\x1b[0m",
+            Status::Error => "\x1b[31m✖\x1b[0m",
+        }
+    }
+}
+
+/// Everything on screen
+#[derive(Debug)]
+struct State {
+    dirs: Vec<(PathBuf, Status)>,
+    log: Vec<(String, bool)>,
+    files_moved: usize,
+    done: usize,
+    started: Instant,
+}
+
+impl State {
+    fn new() -> Self {
+        State {
+            dirs: Vec::new(),
+            log: Vec::new(),
+            files_moved: 0,
+            done: 0,
+            started: Instant::now(),
+        }
+    }
+
+    fn set_status(&mut self, dir: &Path, status: Status) {
+        if let Some(row) = self.dirs.iter_mut().find(|(d, _)| d == dir) {
+            row.1 = status;
+        }
+    }
+
+    fn push_log(&mut self, line: String, warning: bool) {
+        self.log.push((line, warning));
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.remove(0);
+        }
+    }
+
+    fn apply(&mut self, event: TuiEvent) {
+        match event {
+            TuiEvent::DirsFound(dirs) => {
+                self.dirs = dirs.into_iter().map(|d| (d, Status::Pending)).collect();
+            }
+            TuiEvent::DirStarted(dir) => self.set_status(&dir, Status::InProgress),
+            TuiEvent::DirFinished(result) => {
+                let status = match result.outcome {
+                    _ if !result.errors.is_empty() => Status::Error,
+                    None => Status::Error,
+                    Some(DirOutcome::Skipped | DirOutcome::Conflicted) => Status::Skipped,
+                    Some(_) => Status::Done,
+                };
+                self.set_status(&result.path, status);
+                for e in &result.errors {
+                    self.push_log(format!("ERROR: {e}"), true);
+                }
+                self.files_moved += result.files_moved;
+                self.done += 1;
+            }
+            TuiEvent::Log(line) => self.push_log(line, false),
+            TuiEvent::Warning(line) => self.push_log(line, true),
+            TuiEvent::Done => (),
+        }
+    }
+
+    /// The bottom line: progress, files moved, elapsed time and time left
+    fn status_line(&self) -> String {
+        let total = self.dirs.len();
+        let percent = match total {
+            0 => 100,
+            _ => self.done * 100 / total,
+        };
+        let elapsed = self.started.elapsed();
+        let eta = match self.done {
+            0 => "--".to_string(),
+            done => format_duration(elapsed.mul_f64((total - done) as f64 / done as f64)),
+        };
+        format!(
+            " {percent:>3}% | {}/{total} dirs | {} files moved | elapsed {} | eta {eta}",
+            self.done,
+            self.files_moved,
+            format_duration(elapsed)
+        )
+    }
+}
+
+/// Show a duration as `m:ss` (or `h:mm:ss` once it's over an hour)
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs / 3600 {
+        0 => format!("{}:{:02}", secs / 60, secs % 60),
+        hours => format!("{hours}:{:02}:{:02}", secs / 60 % 60, secs % 60),
+    }
+}
+
+/// Cut `text` down to `width` characters and pad it out to exactly that many
+fn fit(text: &str, width: usize) -> String {
+    let mut ret: String = text.chars().take(width).collect();
+    let len = ret.chars().count();
+    ret.extend(std::iter::repeat_n(' ', width - len));
+    ret
+}
+
+/// Size of the terminal as (columns, rows)
+#[cfg(unix)]
+fn terminal_size() -> (usize, usize) {
+    use std::ffi::{c_int, c_ulong, c_ushort};
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct WinSize {
+        rows: c_ushort,
+        cols: c_ushort,
+        x_pixels: c_ushort,
+        y_pixels: c_ushort,
+    }
+    unsafe extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+    #[cfg(target_os = "linux")]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+    #[cfg(not(target_os = "linux"))]
+    const TIOCGWINSZ: c_ulong = 0x40087468;
+
+    let mut size = WinSize::default();
+    // SAFETY: TIOCGWINSZ only fills in the winsize struct it is handed
+    let ok = unsafe { ioctl(1, TIOCGWINSZ, &mut size as *mut WinSize) } == 0;
+    match ok && size.cols > 0 && size.rows > 0 {
+        true => (size.cols as usize, size.rows as usize),
+        false => (80, 24),
+    }
+}
+
+/// Size of the terminal as (columns, rows)
+#[cfg(not(unix))]
+fn terminal_size() -> (usize, usize) {
+    (80, 24)
+}
+
+/// The alternate screen, left again (with the cursor back) when dropped
+struct Screen;
+
+impl Screen {
+    fn enter() -> Self {
+        print!("\x1b[?1049h\x1b[?25l");
+        let _ = io::stdout().flush();
+        Screen
+    }
+}
+
+impl Screen {
+    /// Show the cursor and go back to the normal screen
+    fn leave(&self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        self.leave();
+    }
+}
+
+/// Draw the whole screen in one write so it doesn't flicker
+fn draw(state: &State) {
+    let (width, height) = terminal_size();
+    let rows = height.saturating_sub(2).max(1);
+    let left = (width * 2 / 5).clamp(12, width.saturating_sub(12).max(12));
+    let right = width.saturating_sub(left + 1);
+
+    // keep the first dir being worked on in view
+    let focus = state
+        .dirs
+        .iter()
+        .position(|(_, s)| *s == Status::InProgress || *s == Status::Pending)
+        .unwrap_or(state.dirs.len());
+    let first_dir = focus
+        .saturating_sub(rows / 2)
+        .min(state.dirs.len().saturating_sub(rows));
+    let first_log = state.log.len().saturating_sub(rows);
+
+    let mut out = String::from("\x1b[H");
+    out.push_str(&format!(
+        "\x1b[7m{}│{}\x1b[0m\r\n",
+        fit(" Directories", left),
+        fit(" Log", right)
+    ));
+    for row in 0..rows {
+        match state.dirs.get(first_dir + row) {
+            Some((dir, status)) => {
+                out.push_str(status.icon());
+                out.push(' ');
+                out.push_str(&fit(&get_path_dir_name(dir), left.saturating_sub(2)));
+            }
+            None => out.push_str(&fit("", left)),
+        }
+        out.push('│');
+        match state.log.get(first_log + row) {
+            Some((line, true)) => out.push_str(&format!("\x1b[33m{}\x1b[0m", fit(line, right))),
+            Some((line, false)) => out.push_str(&fit(line, right)),
+            None => out.push_str(&fit("", right)),
+        }
+        out.push_str("\r\n");
+    }
+    out.push_str(&format!(
+        "\x1b[7m{}\x1b[0m",
+        fit(&state.status_line(), width)
+    ));
+
+    let mut stdout = io::stdout().lock();
+    let _ = stdout.write_all(out.as_bytes());
+    let _ = stdout.flush();
+}
+
+/// Check if the TUI can be shown, it needs stdout to be a terminal
+pub fn is_available() -> bool {
+    io::stdout().is_terminal()
+}
+
+/// Process `parent` on a background thread while the main thread draws its progress
+pub fn run(parent: &Path, mut options: GenOptions) -> Result<ProcessReport, GenError> {
+    let (sender, events) = mpsc::channel();
+    options.events = Some(sender);
+    options.progress = false;
+    let stop = signal::stop_flag();
+
+    let mut state = State::new();
+    let screen = Screen::enter();
+    let result = thread::scope(|scope| {
+        let worker = scope.spawn(|| process_parent_dir(parent, &options));
+        loop {
+            let finished = worker.is_finished();
+            if let Ok(event) = events.recv_timeout(TICK) {
+                state.apply(event);
+            }
+            while let Ok(event) = events.try_recv() {
+                state.apply(event);
+            }
+            if stop.load(Ordering::SeqCst) {
+                // same as Ctrl+C without the TUI, but give the terminal back first
+                screen.leave();
+                process::exit(130);
+            }
+            draw(&state);
+            if finished {
+                break;
+            }
+        }
+        worker.join().unwrap_or_else(|_| process::exit(101))
+    });
+    drop(screen);
+
+    // the log panel is gone with the screen, so repeat the warnings where they'll stay
+    for (line, _) in state.log.iter().filter(|(_, warning)| *warning) {
+        eprintln!("{line}");
+    }
+    result
+}
@@ -0,0 +1,224 @@
+// Description: Undo the layout m3ugen creates, working from what is on disk instead of a
+//              transaction log: move the files out of the hidden sub-dir and remove the playlist.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crate::{
+    GenError, GenOptions, Operation, PlaylistFormat, ProcessReport, build_path_from_parts,
+    default_transaction_log_path, get_path_dir_name, has_extension, is_hidden,
+    parse_playlist_entries,
+    process::{absolute_location, find_cue_tracks, move_file, rewrite_moved_cues, write_run_logs},
+    verify_dir,
+};
+
+/// Unpack every child dir of `parent` that has a playlist and a hidden sub-dir. Like
+/// `process_parent_dir` only problems with `parent` itself are returned as an error.
+pub fn unpack_parent_dir(parent: &Path, options: &GenOptions) -> Result<ProcessReport, GenError> {
+    verify_dir(parent, false)?;
+    let started = Instant::now();
+    let mut report = ProcessReport::default();
+    let entries = fs::read_dir(parent).map_err(|source| GenError::ReadDirFailed {
+        path: parent.to_path_buf(),
+        source,
+    })?;
+    let mut children: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.is_dir() && !(options.skips_hidden() && is_hidden(p)))
+        .filter(|p| options.wants_dir(p))
+        .collect();
+    children.sort();
+
+    for child in children {
+        report.dirs_scanned += 1;
+        match unpack_child_dir(&child, options, &mut report) {
+            Ok(true) => report.dirs_processed += 1,
+            Ok(false) => (),
+            Err(e) => report.errors.push(e),
+        }
+    }
+    report.elapsed = started.elapsed();
+    if let Some(csv) = &options.csv {
+        csv.record(&report);
+    }
+
+    let log_path = match &options.transaction_log {
+        Some(path) => path.clone(),
+        None => default_transaction_log_path(parent),
+    };
+    write_run_logs(parent, options, &report, &log_path);
+    Ok(report)
+}
+
+/// Move the files listed in the playlists of `child` back out of its hidden sub-dir, then remove
+/// the playlists and the sub-dir. Returns false when the dir isn't one m3ugen set up.
+fn unpack_child_dir(
+    child: &Path,
+    options: &GenOptions,
+    report: &mut ProcessReport,
+) -> Result<bool, GenError> {
+    let name = get_path_dir_name(child);
+    let sub_dir = build_path_from_parts(&[child, &options.sub_dir_path(&name)]);
+    if !sub_dir.is_dir() {
+        return Ok(false);
+    }
+    let files = fs::read_dir(child).map_err(|source| GenError::ReadDirFailed {
+        path: child.to_path_buf(),
+        source,
+    })?;
+    let mut playlists: Vec<PathBuf> = files
+        .filter_map(Result::ok)
+        .map(|f| f.path())
+        .filter(|f| f.is_file() && has_extension(f, &["m3u", "m3u8", "pls"]))
+        .collect();
+    if playlists.is_empty() {
+        return Ok(false);
+    }
+    playlists.sort();
+    verify_dir(child, !options.dry_run)?;
+    verify_dir(&sub_dir, !options.dry_run)?;
+
+    // only take out what the playlists point at, the sub-dir could hold other things too
+    let sub_dir_abs = absolute_location(&sub_dir);
+    let mut listed: Vec<PathBuf> = Vec::new();
+    let mut originals: Vec<(PathBuf, String)> = Vec::new();
+    for playlist in &playlists {
+        let contents = fs::read(playlist).map_err(|source| GenError::AccessFailed {
+            path: playlist.clone(),
+            source,
+        })?;
+        let format = match has_extension(playlist, &["pls"]) {
+            true => PlaylistFormat::Pls,
+            false => PlaylistFormat::M3u,
+        };
+        for entry in parse_playlist_entries(&contents, format) {
+            let target =
+                absolute_location(&build_path_from_parts(&[child, Path::new(&entry.path)]));
+            if target.parent() != Some(&sub_dir_abs) || !target.is_file() {
+                continue;
+            }
+            let Some(file_name) = target.file_name() else {
+                continue;
+            };
+            let file = build_path_from_parts(&[&sub_dir, Path::new(file_name)]);
+            if !listed.contains(&file) {
+                listed.push(file);
+            }
+        }
+        originals.push((
+            playlist.clone(),
+            String::from_utf8_lossy(&contents).to_string(),
+        ));
+    }
+    let tracks = find_cue_tracks(&sub_dir, &listed, options);
+
+    // move everything back, leaving the playlists alone if anything couldn't be moved
+    let mut all_moved = true;
+    let mut moved: Vec<PathBuf> = Vec::new();
+    let mut moved_cues: Vec<PathBuf> = Vec::new();
+    for file in listed.iter().chain(&tracks) {
+        let dst = build_path_from_parts(&[child, Path::new(&get_path_dir_name(file))]);
+        if dst.exists() {
+            options.warn(format!(
+                "WARNING: not moving {} back, {} is in the way",
+                file.display(),
+                dst.display()
+            ));
+            all_moved = false;
+            continue;
+        }
+        if !move_file(file, &dst, options, report) {
+            all_moved = false;
+            continue;
+        }
+        moved.push(file.clone());
+        if !options.dry_run && has_extension(&dst, &["cue", "gdi"]) {
+            moved_cues.push(dst);
+        }
+    }
+    rewrite_moved_cues(moved_cues, &sub_dir, child, options, report);
+    if !all_moved {
+        options.warn(format!(
+            "WARNING: keeping the playlists in {} since not every file could be moved back",
+            child.display()
+        ));
+        return Ok(true);
+    }
+
+    for (playlist, contents) in originals {
+        if options.dry_run {
+            options.say(format!("WOULD REMOVE FILE: {}", playlist.display()));
+            continue;
+        }
+        match fs::remove_file(&playlist) {
+            Ok(()) => report.operations.push(Operation::RemoveFile {
+                path: playlist,
+                contents,
+            }),
+            Err(source) => report.errors.push(GenError::AccessFailed {
+                path: playlist,
+                source,
+            }),
+        }
+    }
+
+    // anything left in the sub-dir wasn't ours to move, so the sub-dir stays
+    let left = fs::read_dir(&sub_dir)
+        .map(|files| {
+            files
+                .filter_map(Result::ok)
+                .filter(|f| !moved.contains(&f.path()))
+                .count()
+        })
+        .unwrap_or(0);
+    if left > 0 {
+        options.warn(format!(
+            "WARNING: leaving {} in place, it still holds {left} file(s) not listed in a playlist",
+            sub_dir.display()
+        ));
+    } else {
+        remove_sub_dir(&sub_dir, options, report);
+    }
+    Ok(true)
+}
+
+/// Remove the emptied sub-dir, and the levels above it when --subdir-depth nested it. A level
+/// that holds anything besides the one below it stays.
+fn remove_sub_dir(sub_dir: &Path, options: &GenOptions, report: &mut ProcessReport) {
+    let mut dir = sub_dir.to_path_buf();
+    for level in 0..options.subdir_depth {
+        if level > 0 {
+            let others = fs::read_dir(&dir)
+                .map(|files| {
+                    files
+                        .filter_map(Result::ok)
+                        .filter(|f| !sub_dir.starts_with(f.path()))
+                        .count()
+                })
+                .unwrap_or(0);
+            if others > 0 {
+                return;
+            }
+        }
+        if options.dry_run {
+            options.say(format!("WOULD REMOVE DIR: {}", dir.display()));
+        } else if let Err(source) = fs::remove_dir(&dir) {
+            report
+                .errors
+                .push(GenError::AccessFailed { path: dir, source });
+            return;
+        } else {
+            report
+                .operations
+                .push(Operation::RemoveDir { path: dir.clone() });
+        }
+        match dir.parent() {
+            Some(parent) => dir = parent.to_path_buf(),
+            None => return,
+        }
+    }
+}
@@ -0,0 +1,332 @@
+// Description: Check the playlists in a parent dir for entries that point at missing files, and
+//              optionally repair them from the hidden sub-dir. Also checks the files against
+//              the checksums written by --checksums.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    DigestAlgorithm, GenError, GenOptions, PlaylistFormat, build_path_from_parts, compute_digest,
+    get_path_dir_name, gzip, has_extension, is_hidden, is_playlist_file, parse_checksums,
+    parse_playlist_entries, process::prepend_entry_path, read_playlist_file, strip_gz, verify_dir,
+    write_file_atomic,
+};
+
+/// A playlist entry that points at a file that isn't there
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenEntry {
+    /// The playlist holding the entry
+    pub playlist: PathBuf,
+    /// The entry as it is written in the playlist
+    pub entry: String,
+    /// What the entry was changed to by --fix
+    pub fixed: Option<String>,
+}
+
+/// What a validate run found
+#[derive(Debug, Default)]
+pub struct ValidateReport {
+    pub playlists_checked: usize,
+    pub entries_checked: usize,
+    pub broken: Vec<BrokenEntry>,
+    pub errors: Vec<GenError>,
+}
+
+impl ValidateReport {
+    /// Check if every entry is (now) usable
+    pub fn is_valid(&self) -> bool {
+        self.broken.iter().all(|b| b.fixed.is_some()) && self.errors.is_empty()
+    }
+}
+
+/// One file listed in a checksum file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumResult {
+    /// The file as it is on disk
+    pub file: PathBuf,
+    /// The hash the checksum file has for it
+    pub expected: String,
+    /// The hash it has now, `None` when it couldn't be read
+    pub actual: Option<String>,
+}
+
+impl ChecksumResult {
+    /// Check if the file still has the hash it was written with
+    pub fn matches(&self) -> bool {
+        self.actual.as_ref() == Some(&self.expected)
+    }
+}
+
+/// What a checksum verification found
+#[derive(Debug, Default)]
+pub struct ChecksumReport {
+    pub sidecars_checked: usize,
+    pub results: Vec<ChecksumResult>,
+    pub errors: Vec<GenError>,
+}
+
+impl ChecksumReport {
+    /// The files that no longer match
+    pub fn failures(&self) -> impl Iterator<Item = &ChecksumResult> {
+        self.results.iter().filter(|r| !r.matches())
+    }
+
+    /// Check if every file matched
+    pub fn is_valid(&self) -> bool {
+        self.failures().next().is_none() && self.errors.is_empty()
+    }
+}
+
+/// The child dirs of `parent` that validating looks at, in name order
+pub(crate) fn child_dirs(parent: &Path, options: &GenOptions) -> Result<Vec<PathBuf>, GenError> {
+    let entries = fs::read_dir(parent).map_err(|source| GenError::ReadDirFailed {
+        path: parent.to_path_buf(),
+        source,
+    })?;
+    let mut children: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.is_dir() && !(options.skips_hidden() && is_hidden(p)))
+        .filter(|p| options.wants_dir(p))
+        .collect();
+    children.sort();
+    Ok(children)
+}
+
+/// Hash every file listed in the `<dir name>.sha256` (or .md5, .sha1) files written by
+/// --checksums and compare it with the listed hash, using the algorithm the extension names
+pub fn verify_checksums(parent: &Path, options: &GenOptions) -> Result<ChecksumReport, GenError> {
+    verify_dir(parent, false)?;
+    let mut report = ChecksumReport::default();
+    for child in child_dirs(parent, options)? {
+        for algorithm in DigestAlgorithm::ALL {
+            verify_sidecar(&child, algorithm, &mut report);
+        }
+    }
+    Ok(report)
+}
+
+/// Check the files listed in the `algorithm` checksum file of `child`, if it has one
+fn verify_sidecar(child: &Path, algorithm: DigestAlgorithm, report: &mut ChecksumReport) {
+    let sidecar = build_path_from_parts(&[
+        child,
+        Path::new(&format!(
+            "{}.{}",
+            get_path_dir_name(child),
+            algorithm.extension()
+        )),
+    ]);
+    if !sidecar.is_file() {
+        return;
+    }
+    let contents = match fs::read_to_string(&sidecar) {
+        Ok(c) => c,
+        Err(source) => {
+            report.errors.push(GenError::AccessFailed {
+                path: sidecar,
+                source,
+            });
+            return;
+        }
+    };
+    report.sidecars_checked += 1;
+    for (expected, listed) in parse_checksums(&contents) {
+        let file = build_path_from_parts(&[child, Path::new(&listed)]);
+        let actual = compute_digest(&file, algorithm).ok();
+        report.results.push(ChecksumResult {
+            file,
+            expected,
+            actual,
+        });
+    }
+}
+
+/// Check every playlist in the child dirs of `parent`. With `fix` set, broken entries are pointed
+/// at a file with the same name in the dir's hidden sub-dir when there is one. With
+/// `strip_prepend` set, the --prepend-path is taken off the entries before they're checked.
+pub fn validate_parent_dir(
+    parent: &Path,
+    options: &GenOptions,
+    fix: bool,
+    strip_prepend: bool,
+) -> Result<ValidateReport, GenError> {
+    verify_dir(parent, false)?;
+    let mut report = ValidateReport::default();
+    for child in child_dirs(parent, options)? {
+        let files = match fs::read_dir(&child) {
+            Ok(files) => files,
+            Err(source) => {
+                report.errors.push(GenError::ReadDirFailed {
+                    path: child.clone(),
+                    source,
+                });
+                continue;
+            }
+        };
+        let mut playlists: Vec<PathBuf> = files
+            .filter_map(Result::ok)
+            .map(|f| f.path())
+            .filter(|f| f.is_file() && is_playlist_file(f))
+            .collect();
+        playlists.sort();
+        for playlist in playlists {
+            validate_playlist(&child, &playlist, options, fix, strip_prepend, &mut report);
+        }
+    }
+    Ok(report)
+}
+
+/// Check (and maybe fix) the entries of one playlist in `child`
+fn validate_playlist(
+    child: &Path,
+    playlist: &Path,
+    options: &GenOptions,
+    fix: bool,
+    strip_prepend: bool,
+    report: &mut ValidateReport,
+) {
+    let contents = match read_playlist_file(playlist) {
+        Ok(c) => c,
+        Err(source) => {
+            report.errors.push(GenError::AccessFailed {
+                path: playlist.to_path_buf(),
+                source,
+            });
+            return;
+        }
+    };
+    let format = match has_extension(&strip_gz(playlist), &["pls"]) {
+        true => PlaylistFormat::Pls,
+        false => PlaylistFormat::M3u,
+    };
+    report.playlists_checked += 1;
+
+    let playlist_dir = playlist.parent().unwrap_or(child);
+    let sub_dir_name = options.sub_dir_path(&get_path_dir_name(child));
+    let prefix = options
+        .prepend_path
+        .as_deref()
+        .filter(|p| strip_prepend && !p.is_empty())
+        .map(|p| p.trim_end_matches(['/', '\\']));
+    let mut fixes: Vec<(String, String)> = Vec::new();
+    for entry in parse_playlist_entries(&contents, format) {
+        report.entries_checked += 1;
+        let path = match prefix.and_then(|p| entry.path.strip_prefix(p)) {
+            Some(rest) => rest.trim_start_matches(['/', '\\']),
+            None => entry.path.as_str(),
+        };
+        let target = build_path_from_parts(&[playlist_dir, Path::new(path)]);
+        if target.exists() {
+            continue;
+        }
+
+        // look for a file with the same name in the hidden sub-dir, written the same way the
+        // broken entry was (absolute or relative to the playlist)
+        let fixed = match fix {
+            true => Path::new(path).file_name().and_then(|name| {
+                let found =
+                    build_path_from_parts(&[child, Path::new(&sub_dir_name), name.as_ref()]);
+                let fixed = found
+                    .is_file()
+                    .then(|| match Path::new(path).is_absolute() {
+                        true => found.to_string_lossy().to_string(),
+                        false => build_path_from_parts(&[Path::new(&sub_dir_name), name.as_ref()])
+                            .to_string_lossy()
+                            .to_string(),
+                    })?;
+                // a fixed entry keeps the prefix the broken one had
+                match path.len() != entry.path.len() {
+                    true => prefix.map(|p| prepend_entry_path(p, &fixed)),
+                    false => Some(fixed),
+                }
+            }),
+            false => None,
+        };
+        if let Some(fixed) = &fixed {
+            fixes.push((entry.path.clone(), fixed.clone()));
+        }
+        report.broken.push(BrokenEntry {
+            playlist: playlist.to_path_buf(),
+            entry: entry.path,
+            fixed,
+        });
+    }
+    if fixes.is_empty() {
+        return;
+    }
+
+    let result = match options.dry_run {
+        true => {
+            for (old, new) in &fixes {
+                options.say(format!(
+                    "WOULD FIX: {old} -> {new} in {}",
+                    playlist.display()
+                ));
+            }
+            Ok(false)
+        }
+        false => {
+            let repaired = replace_entries(&String::from_utf8_lossy(&contents), &fixes);
+            // a compressed playlist stays compressed
+            let repaired = match has_extension(playlist, &["gz"]) {
+                true => gzip(
+                    repaired.as_bytes(),
+                    &get_path_dir_name(&strip_gz(playlist)),
+                    options.compression_level,
+                ),
+                false => repaired.into_bytes(),
+            };
+            write_file_atomic(playlist, &repaired).map(|_| true)
+        }
+    };
+    let changed = match result {
+        Ok(changed) => changed,
+        Err(source) => {
+            report.errors.push(GenError::WriteEntryFailed {
+                path: playlist.to_path_buf(),
+                source,
+            });
+            false
+        }
+    };
+    if !changed {
+        report
+            .broken
+            .iter_mut()
+            .filter(|b| b.playlist == playlist)
+            .for_each(|b| b.fixed = None);
+    }
+}
+
+/// Swap the paths in `fixes` into the playlist text, leaving every other byte (including the
+/// line endings) as it was
+fn replace_entries(contents: &str, fixes: &[(String, String)]) -> String {
+    let mut ret = String::with_capacity(contents.len());
+    let contents = match contents.strip_prefix('\u{feff}') {
+        Some(rest) => {
+            ret.push('\u{feff}');
+            rest
+        }
+        None => contents,
+    };
+    for line in contents.split_inclusive('\n') {
+        let body = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[body.len()..];
+        // a .pls line looks like `File1=path`
+        let (prefix, path) = match body.split_once('=') {
+            Some((key, path)) if key.starts_with("File") => (&body[..key.len() + 1], path),
+            _ => ("", body),
+        };
+        match fixes.iter().find(|(old, _)| old == path.trim()) {
+            Some((_, new)) => {
+                ret.push_str(prefix);
+                ret.push_str(new);
+                ret.push_str(ending);
+            }
+            None => ret.push_str(line),
+        }
+    }
+    ret
+}
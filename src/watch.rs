@@ -0,0 +1,330 @@
+// Description: Keep an eye on a parent dir and process new child dirs as they show up.
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    GenError, GenOptions, ProcessReport, default_transaction_log_path, is_hidden,
+    process::{create_output_dir, process_child_report, write_run_logs},
+};
+
+/// How long to sleep between checks for new events and settled dirs
+const TICK: Duration = Duration::from_millis(100);
+
+/// A new child dir waiting for its contents to stop changing
+struct Pending {
+    dir: PathBuf,
+    since: Instant,
+    signature: Option<(usize, u64)>,
+}
+
+/// Reports dirs that appeared in a single dir by reading it every `interval` and comparing
+/// with what was there the last time, for filesystems that don't send inotify events
+struct DirPoller {
+    dir: PathBuf,
+    interval: Duration,
+    last: Instant,
+    seen: HashSet<PathBuf>,
+}
+
+impl DirPoller {
+    fn new(dir: &Path, interval: Duration) -> io::Result<Self> {
+        Ok(DirPoller {
+            seen: child_dirs(dir)?,
+            dir: dir.to_path_buf(),
+            interval,
+            last: Instant::now(),
+        })
+    }
+
+    /// Dirs that appeared since the last scan, empty until `interval` has passed
+    fn new_dirs(&mut self) -> io::Result<Vec<PathBuf>> {
+        if self.last.elapsed() < self.interval {
+            return Ok(Vec::new());
+        }
+        self.last = Instant::now();
+        let current = child_dirs(&self.dir)?;
+        let mut ret: Vec<PathBuf> = current.difference(&self.seen).cloned().collect();
+        ret.sort();
+        // a dir that's removed and added back between scans counts as new again
+        self.seen = current;
+        Ok(ret)
+    }
+}
+
+/// The dirs directly in `dir`
+fn child_dirs(dir: &Path) -> io::Result<HashSet<PathBuf>> {
+    Ok(fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect())
+}
+
+/// Where new dirs come from: inotify events, or re-reading the dir with --watch-poll
+enum DirSource {
+    Events(inotify::DirWatcher),
+    Poll(DirPoller),
+}
+
+impl DirSource {
+    /// Use inotify unless asked to poll, and poll when inotify isn't available
+    fn new(parent: &Path, options: &GenOptions) -> io::Result<Self> {
+        let interval = Duration::from_millis(options.watch_interval_ms);
+        if !options.watch_poll {
+            match inotify::DirWatcher::new(parent) {
+                Ok(watcher) => return Ok(DirSource::Events(watcher)),
+                Err(e) => options.warn(format!(
+                    "WARNING: unable to watch {} for events ({e}), checking it every {} ms \
+                     instead",
+                    parent.display(),
+                    options.watch_interval_ms
+                )),
+            }
+        }
+        Ok(DirSource::Poll(DirPoller::new(parent, interval)?))
+    }
+
+    fn new_dirs(&mut self) -> io::Result<Vec<PathBuf>> {
+        match self {
+            DirSource::Events(watcher) => watcher.new_dirs(),
+            DirSource::Poll(poller) => poller.new_dirs(),
+        }
+    }
+}
+
+/// Number of entries and their total size, used to tell if a copy is still going on
+fn dir_signature(dir: &Path) -> Option<(usize, u64)> {
+    let entries = fs::read_dir(dir).ok()?;
+    let mut count = 0;
+    let mut size = 0;
+    for entry in entries.filter_map(Result::ok) {
+        count += 1;
+        size += entry.metadata().map(|m| m.len()).unwrap_or(0);
+    }
+    Some((count, size))
+}
+
+/// Watch `parent` until `stop` is set, processing each new child dir once it has been left
+/// alone for `options.debounce_ms`. New dirs are noticed through inotify, or by re-reading
+/// `parent` every `options.watch_interval_ms` with --watch-poll. Only failing to watch `parent`
+/// at all is returned as an error, problems with a child dir are printed and the watch carries
+/// on.
+pub fn watch_parent_dir(
+    parent: &Path,
+    options: &GenOptions,
+    stop: &AtomicBool,
+) -> Result<ProcessReport, GenError> {
+    let watch_err = |source| GenError::WatchFailed {
+        path: parent.to_path_buf(),
+        source,
+    };
+    let mut watcher = DirSource::new(parent, options).map_err(watch_err)?;
+    let mut report = ProcessReport::default();
+    create_output_dir(options, &mut report)?;
+    let debounce = Duration::from_millis(options.debounce_ms);
+
+    // everything done while watching goes in a single log
+    let log_path = match &options.transaction_log {
+        Some(path) => path.clone(),
+        None => default_transaction_log_path(parent),
+    };
+    let mut pending: Vec<Pending> = Vec::new();
+    options.say(format!(
+        "INFO: watching {} for new dirs, press Ctrl+C to stop",
+        parent.display()
+    ));
+
+    while !stop.load(Ordering::SeqCst) {
+        match watcher.new_dirs() {
+            Ok(dirs) => {
+                for dir in dirs
+                    .into_iter()
+                    .filter(|d| options.wants_dir(d) && !(options.skips_hidden() && is_hidden(d)))
+                {
+                    if !pending.iter().any(|p| p.dir == dir) {
+                        pending.push(Pending {
+                            signature: dir_signature(&dir),
+                            dir,
+                            since: Instant::now(),
+                        });
+                    }
+                }
+            }
+            Err(source) if source.kind() == io::ErrorKind::NotFound => {
+                return Err(watch_err(source));
+            }
+            Err(e) => options.warn(format!(
+                "WARNING: unable to read events for {}: {e}",
+                parent.display()
+            )),
+        }
+
+        // a dir is only processed once nothing has been added to it for a while, so we don't
+        // move half copied files
+        let mut i = 0;
+        while i < pending.len() {
+            if pending[i].since.elapsed() < debounce {
+                i += 1;
+                continue;
+            }
+            let signature = dir_signature(&pending[i].dir);
+            if signature.is_none() {
+                // removed again before we got to it
+                pending.remove(i);
+                continue;
+            }
+            if signature != pending[i].signature {
+                pending[i].signature = signature;
+                pending[i].since = Instant::now();
+                i += 1;
+                continue;
+            }
+
+            let dir = pending.remove(i).dir;
+            let local = process_child_report(parent, &dir, options);
+            if local.dirs_processed > 0 {
+                options.say(format!("INFO: processed {}", dir.display()));
+            }
+            for e in &local.errors {
+                options.warn(format!("ERROR: {e}"));
+            }
+            if let Some(csv) = &options.csv {
+                csv.record(&local);
+            }
+            let changed = !local.operations.is_empty() || !local.conflicts.is_empty();
+            report.merge(local);
+            if changed {
+                write_run_logs(parent, options, &report, &log_path);
+            }
+        }
+        thread::sleep(TICK);
+    }
+    options.say(format!("INFO: stopped watching {}", parent.display()));
+    Ok(report)
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::{
+        ffi::{CString, c_char, c_int},
+        fs::File,
+        io::{self, Read},
+        os::{fd::FromRawFd, unix::ffi::OsStrExt},
+        path::{Path, PathBuf},
+    };
+
+    use crate::build_path_from_parts;
+
+    unsafe extern "C" {
+        fn inotify_init1(flags: c_int) -> c_int;
+        fn inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int;
+    }
+
+    const IN_NONBLOCK: c_int = 0o4000;
+    const IN_CLOEXEC: c_int = 0o2000000;
+    const IN_MOVED_TO: u32 = 0x80;
+    const IN_CREATE: u32 = 0x100;
+    const IN_DELETE_SELF: u32 = 0x400;
+    const IN_MOVE_SELF: u32 = 0x800;
+    const IN_IGNORED: u32 = 0x8000;
+    const IN_ISDIR: u32 = 0x4000_0000;
+
+    /// Size of `struct inotify_event` without the trailing name
+    const EVENT_HEADER: usize = 16;
+
+    /// Reports dirs created in (or moved into) a single dir
+    pub struct DirWatcher {
+        dir: PathBuf,
+        file: File,
+        buf: Vec<u8>,
+    }
+
+    impl DirWatcher {
+        pub fn new(dir: &Path) -> io::Result<Self> {
+            let path = CString::new(dir.as_os_str().as_bytes())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            // SAFETY: plain syscalls, the fd is owned by `file` from here on
+            let fd = unsafe { inotify_init1(IN_NONBLOCK | IN_CLOEXEC) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let file = unsafe { File::from_raw_fd(fd) };
+            let mask = IN_CREATE | IN_MOVED_TO | IN_DELETE_SELF | IN_MOVE_SELF;
+            if unsafe { inotify_add_watch(fd, path.as_ptr(), mask) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(DirWatcher {
+                dir: dir.to_path_buf(),
+                file,
+                buf: vec![0; 64 * 1024],
+            })
+        }
+
+        /// Dirs that appeared since the last call, without blocking
+        pub fn new_dirs(&mut self) -> io::Result<Vec<PathBuf>> {
+            let mut ret = Vec::new();
+            loop {
+                let len = match self.file.read(&mut self.buf) {
+                    Ok(n) => n,
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(ret),
+                    Err(e) => return Err(e),
+                };
+                let mut offset = 0;
+                while offset + EVENT_HEADER <= len {
+                    let field = |at: usize| {
+                        let bytes = &self.buf[offset + at..offset + at + 4];
+                        u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+                    };
+                    let mask = field(4);
+                    let name_len = field(12) as usize;
+                    let name = &self.buf[offset + EVENT_HEADER..offset + EVENT_HEADER + name_len];
+                    offset += EVENT_HEADER + name_len;
+
+                    if mask & (IN_DELETE_SELF | IN_MOVE_SELF | IN_IGNORED) != 0 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::NotFound,
+                            "the watched dir was removed or moved",
+                        ));
+                    }
+                    if mask & IN_ISDIR != 0 && mask & (IN_CREATE | IN_MOVED_TO) != 0 {
+                        // the name is padded with NULs
+                        let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+                        let name = std::ffi::OsStr::from_bytes(&name[..end]);
+                        ret.push(build_path_from_parts(&[&self.dir, Path::new(name)]));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod inotify {
+    use std::{
+        io,
+        path::{Path, PathBuf},
+    };
+
+    /// Watching for events needs inotify, which only Linux has, so --watch polls elsewhere
+    pub struct DirWatcher;
+
+    impl DirWatcher {
+        pub fn new(_dir: &Path) -> io::Result<Self> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "inotify is only available on Linux",
+            ))
+        }
+
+        pub fn new_dirs(&mut self) -> io::Result<Vec<PathBuf>> {
+            Ok(Vec::new())
+        }
+    }
+}
@@ -0,0 +1,53 @@
+// Description: Roll back a run with --undo from a different working directory than the run
+//              itself was started in.
+
+use std::{fs, path::PathBuf, process::Command};
+
+fn m3ugen() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_m3ugen"))
+}
+
+#[test]
+fn undo_from_another_cwd() {
+    let root = std::env::temp_dir().join(format!("m3ugen-undo-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let game = root.join("t1/lib/Game");
+    fs::create_dir_all(&game).unwrap();
+    for disc in ["Game (Disc 1).cue", "Game (Disc 2).cue"] {
+        fs::write(game.join(disc), "").unwrap();
+    }
+
+    // the run is given a relative path
+    let status = m3ugen()
+        .arg("lib")
+        .current_dir(root.join("t1"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(game.join("Game.m3u").is_file());
+    let log: PathBuf = fs::read_dir(root.join("t1/lib"))
+        .unwrap()
+        .map(|f| f.unwrap().path())
+        .find(|f| f.extension().is_some_and(|e| e == "json"))
+        .expect("no transaction log was written");
+
+    // and undone from the dir above it
+    let relative_log = log.strip_prefix(&root).unwrap();
+    let output = m3ugen()
+        .arg("--undo")
+        .arg(relative_log)
+        .current_dir(&root)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(game.join("Game (Disc 1).cue").is_file());
+    assert!(game.join("Game (Disc 2).cue").is_file());
+    assert!(!game.join("Game.m3u").exists());
+    assert!(!game.join(".Game").exists());
+
+    let _ = fs::remove_dir_all(&root);
+}